@@ -322,9 +322,8 @@ impl CmdRun {
                 let repo = spfs::storage::ProxyRepository::from_config(proxy_config)
                     .await
                     .wrap_err("Failed to build proxy repository for environment resolution")?;
-                for item in reference.iter().filter(|i| !i.is_livelayer()) {
-                    let digest = item.resolve_digest(&repo).await?;
-                    runtime.push_digest(digest);
+                for resolution in resolve_layers(reference, &repo).await {
+                    runtime.push_digest(resolution.digest?);
                 }
             }
             tracing::debug!("synced all the referenced objects locally");
@@ -379,3 +378,28 @@ impl CmdRun {
             .wrap_err("Failed to execute runtime command")
     }
 }
+
+/// One [`spfs::tracking::EnvSpecItem`] resolved to the digest it
+/// refers to, or the error encountered while resolving it.
+struct LayerResolution {
+    reference: spfs::tracking::EnvSpecItem,
+    digest: Result<spfs::encoding::Digest>,
+}
+
+/// Resolve every non-live-layer item in `reference` to the digest it
+/// refers to, without syncing or otherwise changing anything, to
+/// build the digest stack for a run.
+async fn resolve_layers<R>(reference: &spfs::tracking::EnvSpec, repo: &R) -> Vec<LayerResolution>
+where
+    R: spfs::storage::Repository + ?Sized,
+{
+    let mut resolved = Vec::new();
+    for item in reference.iter().filter(|i| !i.is_livelayer()) {
+        let digest = item.resolve_digest(repo).await.into_diagnostic();
+        resolved.push(LayerResolution {
+            reference: item.clone(),
+            digest,
+        });
+    }
+    resolved
+}
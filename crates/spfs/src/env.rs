@@ -1590,13 +1590,13 @@ async fn mount_live_layers(rt: &runtime::Runtime) -> Result<()> {
     // in the runtime, or by an earlier call to
     // ensure_extra_bind_mount_locations_exist() made in
     // initialize_runtime()
-    let live_layers = rt.live_layers();
+    let live_layers = rt.effective_live_layers()?;
     if !live_layers.is_empty() {
         let spfs_config = crate::Config::current()?;
         if spfs_config.filesystem.use_mount_syscalls {
-            mount_live_layers_syscalls(live_layers)?;
+            mount_live_layers_syscalls(&live_layers)?;
         } else {
-            mount_live_layers_command(live_layers).await?;
+            mount_live_layers_command(&live_layers).await?;
         }
     }
 
@@ -1716,13 +1716,13 @@ fn mount_live_layers_syscalls(live_layers: &Vec<runtime::LiveLayer>) -> Result<(
 
 /// Unmount the bind mounted items from the live layers
 async fn unmount_live_layers(rt: &runtime::Runtime) -> Result<()> {
-    let live_layers = rt.live_layers();
+    let live_layers = rt.effective_live_layers()?;
     if !live_layers.is_empty() {
         let spfs_config = crate::Config::current()?;
         if spfs_config.filesystem.use_mount_syscalls {
-            unmount_live_layers_syscalls(live_layers)?;
+            unmount_live_layers_syscalls(&live_layers)?;
         } else {
-            unmount_live_layers_command(live_layers).await?;
+            unmount_live_layers_command(&live_layers).await?;
         }
     }
 
@@ -10,6 +10,7 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
 use super::spec_api_version::SpecApiVersion;
+use crate::env::SPFS_DIR_PREFIX;
 use crate::{Error, Result};
 
 #[cfg(test)]
@@ -48,6 +49,30 @@ impl BindMount {
 
         Ok(())
     }
+
+    /// The hardcoded destination base that [`Self::with_dest_prefix`] rewrites.
+    const PROJECT_DEST_BASE: &'static str = "/spfs/project";
+
+    /// Returns a copy of this bind mount with `prefix` substituted for
+    /// [`Self::PROJECT_DEST_BASE`] at the start of its `dest`, so the same
+    /// live layer spec can be reused across runtimes with different
+    /// project roots. Binds whose `dest` does not start with
+    /// [`Self::PROJECT_DEST_BASE`] are returned unchanged.
+    pub(crate) fn with_dest_prefix(&self, prefix: &str) -> Result<Self> {
+        let Some(rest) = self.dest.strip_prefix(Self::PROJECT_DEST_BASE) else {
+            return Ok(self.clone());
+        };
+        let dest = format!("{prefix}{rest}");
+        if !dest.starts_with(SPFS_DIR_PREFIX) {
+            return Err(Error::String(format!(
+                "Bind mount destination prefix is not valid: {dest} is not under {SPFS_DIR_PREFIX}"
+            )));
+        }
+        Ok(Self {
+            src: self.src.clone(),
+            dest,
+        })
+    }
 }
 
 impl Display for BindMount {
@@ -128,6 +153,25 @@ impl LiveLayer {
         Ok(())
     }
 
+    /// Returns a copy of this live layer with [`BindMount::with_dest_prefix`]
+    /// applied to each bind mount, for reuse across runtimes with
+    /// different project roots.
+    pub(crate) fn with_dest_prefix(&self, prefix: &str) -> Result<Self> {
+        let contents = self
+            .contents
+            .iter()
+            .map(|c| match c {
+                LiveLayerContents::BindMount(bm) => bm
+                    .with_dest_prefix(prefix)
+                    .map(LiveLayerContents::BindMount),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            api: self.api,
+            contents,
+        })
+    }
+
     /// Sets the live layer's parent directory, which updates its
     /// contents, and then validates its contents.
     pub fn set_parent_and_validate(&mut self, parent: PathBuf) -> Result<()> {
@@ -8,6 +8,7 @@ use std::borrow::Cow;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env::temp_dir;
 use std::fs::OpenOptions;
+use std::io::Write;
 #[cfg(unix)]
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 #[cfg(windows)]
@@ -17,6 +18,7 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use futures::{Stream, StreamExt, TryStreamExt};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
 use tokio::io::AsyncReadExt;
@@ -34,7 +36,7 @@ use crate::prelude::*;
 use crate::runtime::LiveLayer;
 use crate::storage::RepositoryHandle;
 use crate::storage::fs::DURABLE_EDITS_DIR;
-use crate::{Error, Result, bootstrap, graph, storage, tracking};
+use crate::{Error, Result, bootstrap, encoding, graph, storage, tracking};
 
 #[cfg(test)]
 #[path = "./storage_test.rs"]
@@ -46,6 +48,26 @@ pub const STARTUP_FILES_LOCATION: &str = "/spfs/etc/spfs/startup.d";
 /// The environment variable that can be used to specify the runtime fs size
 const SPFS_FILESYSTEM_TMPFS_SIZE: &str = "SPFS_FILESYSTEM_TMPFS_SIZE";
 
+// Maps the content digest of an extra-mounts layer (see
+// `digest_of_mount_points`) to the digest of the layer already created
+// for it this process, so that an identical set of live layer bind mounts
+// doesn't pay for a temp dir, a manifest walk and a repository write on
+// every runtime that uses it.
+static EXTRA_MOUNT_LAYER_CACHE: Lazy<std::sync::Mutex<HashMap<Digest, Digest>>> =
+    Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
+
+/// A digest of the ordered list of extra bind mount points that would be
+/// created in an extra-mounts layer, used to recognize when two runtimes
+/// would produce byte-identical layers without having to build either one.
+fn digest_of_mount_points(mount_points: &[(String, bool)]) -> Digest {
+    let mut hasher = encoding::Hasher::new_sync();
+    for (mountpoint, is_dir) in mount_points {
+        let kind = if *is_dir { "dir" } else { "file" };
+        let _ = hasher.write_all(format!("{kind}:{mountpoint}\n").as_bytes());
+    }
+    hasher.digest()
+}
+
 // For durable parameter of create_runtime()
 #[cfg(test)]
 const TRANSIENT: bool = false;
@@ -176,6 +198,11 @@ pub struct Config {
     /// List of live layers to add on top of the runtime's overlayfs
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub live_layers: Vec<LiveLayer>,
+    /// When set, rewrites each live layer bind mount's destination under
+    /// `/spfs/project` to use this prefix instead, so the same live layer
+    /// spec can be reused across runtimes with different project roots.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dest_prefix: Option<String>,
 }
 
 impl Default for Config {
@@ -220,6 +247,7 @@ impl Config {
             include_secondary_tags: default_proxy_repo_include_secondary_tags(),
             durable: false,
             live_layers: Vec::new(),
+            dest_prefix: None,
         }
     }
 
@@ -609,6 +637,20 @@ impl Runtime {
         &self.config.live_layers
     }
 
+    /// The live layers for this runtime, with each bind mount's
+    /// destination re-rooted under [`Config::dest_prefix`] when one is set.
+    pub fn effective_live_layers(&self) -> Result<Vec<LiveLayer>> {
+        match &self.config.dest_prefix {
+            Some(prefix) => self
+                .config
+                .live_layers
+                .iter()
+                .map(|layer| layer.with_dest_prefix(prefix))
+                .collect(),
+            None => Ok(self.config.live_layers.clone()),
+        }
+    }
+
     /// Prepares the runtime's layer stack for the live layers
     pub async fn prepare_live_layers(&mut self) -> Result<()> {
         // Any bind mount point destinations for live layers must
@@ -622,17 +664,17 @@ impl Runtime {
     /// adding a new layer to the runtime that contains all the
     /// directory paths.
     async fn ensure_extra_bind_mount_locations_exist(&mut self) -> Result<()> {
-        let live_layers = self.live_layers();
+        let live_layers = self.effective_live_layers()?;
         if !live_layers.is_empty() {
-            // Make a layer that contains paths to all the mount locations.
-            // This layer is added to the runtime so all the mount paths are
-            // present for the extra mounts. This avoids having to check all
-            // the other layers in the runtime to see which extra mounts
-            // locations are missing. Only directory and file mounts are supported.
-            let tmp_dir = TempDir::new().map_err(|err| Error::String(err.to_string()))?;
+            // The generated layer's contents are fully determined by the
+            // ordered list of mount points and whether each is a dir or
+            // a file, so that list is hashed up front and checked against
+            // layers already created this process before paying for a
+            // temp dir, a manifest walk and a write to the repository.
+            let mut mount_points = Vec::new();
             let mut seen_dir_mounts = HashMap::new();
 
-            for layer in live_layers {
+            for layer in &live_layers {
                 let injection_mounts = layer.bind_mounts();
 
                 for extra_mount in injection_mounts {
@@ -640,18 +682,13 @@ impl Runtime {
                         Some(mp) => mp.to_string(),
                         None => extra_mount.dest.clone(),
                     };
-                    let mountpoint = PathBuf::from(tmp_dir.path()).join(extra_mountpoint);
-                    tracing::debug!("extra bind mount point: {:?}", mountpoint);
+                    let relative_mountpoint = PathBuf::from(&extra_mountpoint);
 
                     if extra_mount.src.is_dir() {
-                        tracing::debug!("extra bind mount point is a dir");
-                        std::fs::create_dir_all(mountpoint.clone()).expect(
-                            "failed to make extra mount directory location: {mountpoint:?}",
-                        );
-                        seen_dir_mounts.insert(mountpoint.clone(), extra_mount);
+                        seen_dir_mounts.insert(relative_mountpoint.clone(), extra_mount);
+                        mount_points.push((extra_mountpoint, true));
                     } else if extra_mount.src.is_file() {
-                        tracing::debug!("extra bind mount point is a file");
-                        if let Some(parent) = mountpoint.parent() {
+                        if let Some(parent) = relative_mountpoint.parent() {
                             // Because extra mounts are bind mounted in order, if there
                             // is a directory mount in the list of dirs that have already
                             // been processed, its mount will clobber this file mount
@@ -661,7 +698,7 @@ impl Runtime {
                             if let Some(dir_mount) = seen_dir_mounts.get(&parent.to_path_buf()) {
                                 let existing_file = dir_mount
                                     .src
-                                    .join(mountpoint.as_path().file_name().unwrap());
+                                    .join(relative_mountpoint.as_path().file_name().unwrap());
                                 tracing::debug!("file to test will be: {existing_file:?}");
                                 if !existing_file.exists() {
                                     // This file's mount will fail because of the earlier
@@ -672,17 +709,8 @@ impl Runtime {
                                     )));
                                 }
                             }
-
-                            std::fs::create_dir_all(parent).expect(
-                                "failed to make extra mount file location's parent: {mountpoint:?}",
-                            );
                         }
-                        OpenOptions::new()
-                            .write(true)
-                            .create(true)
-                            .truncate(true)
-                            .open(mountpoint)
-                            .expect("failed to make extra mount file location: {mountpoint:?}");
+                        mount_points.push((extra_mountpoint, false));
                     } else {
                         // Only dirs and files are supported in spfs as bind mounts
                         return Err(Error::String(format!(
@@ -693,6 +721,47 @@ impl Runtime {
                 }
             }
 
+            let content_key = digest_of_mount_points(&mount_points);
+            let cached_layer_digest = EXTRA_MOUNT_LAYER_CACHE
+                .lock()
+                .unwrap()
+                .get(&content_key)
+                .cloned();
+            if let Some(layer_digest) = cached_layer_digest {
+                tracing::debug!("reusing cached extra mount layer: {layer_digest}");
+                self.push_digest(layer_digest);
+                return Ok(());
+            }
+
+            // Make a layer that contains paths to all the mount locations.
+            // This layer is added to the runtime so all the mount paths are
+            // present for the extra mounts. This avoids having to check all
+            // the other layers in the runtime to see which extra mounts
+            // locations are missing. Only directory and file mounts are supported.
+            let tmp_dir = TempDir::new().map_err(|err| Error::String(err.to_string()))?;
+            for (extra_mountpoint, is_dir) in &mount_points {
+                let mountpoint = PathBuf::from(tmp_dir.path()).join(extra_mountpoint);
+                tracing::debug!("extra bind mount point: {:?}", mountpoint);
+                if *is_dir {
+                    tracing::debug!("extra bind mount point is a dir");
+                    std::fs::create_dir_all(&mountpoint)
+                        .expect("failed to make extra mount directory location: {mountpoint:?}");
+                } else {
+                    tracing::debug!("extra bind mount point is a file");
+                    if let Some(parent) = mountpoint.parent() {
+                        std::fs::create_dir_all(parent).expect(
+                            "failed to make extra mount file location's parent: {mountpoint:?}",
+                        );
+                    }
+                    OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(true)
+                        .open(&mountpoint)
+                        .expect("failed to make extra mount file location: {mountpoint:?}");
+                }
+            }
+
             let manifest = crate::tracking::compute_manifest(tmp_dir.path()).await?;
 
             // This creates and saves the layer into the same repo as
@@ -702,11 +771,17 @@ impl Runtime {
                 .inner
                 .create_layer_from_manifest(&manifest)
                 .await?;
-            tracing::debug!("new layer saved with digest: {}", layer.digest()?);
+            let layer_digest = layer.digest()?;
+            tracing::debug!("new layer saved with digest: {layer_digest}");
+
+            EXTRA_MOUNT_LAYER_CACHE
+                .lock()
+                .unwrap()
+                .insert(content_key, layer_digest);
 
             // TODO: do we want to tag this extra layer as well?
             // self.storage.push_tag(&tag_spec, &layer.digest()?).await?;
-            self.push_digest(layer.digest()?);
+            self.push_digest(layer_digest);
         }
         Ok(())
     }
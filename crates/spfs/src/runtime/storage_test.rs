@@ -598,6 +598,124 @@ async fn test_runtime_ensure_extra_bind_mount_locations_exist(tmpdir: tempfile::
     assert!(runtime.prepare_live_layers().await.is_ok())
 }
 
+#[rstest]
+#[tokio::test]
+async fn test_runtime_ensure_extra_bind_mount_locations_exist_reuses_identical_layers(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path().to_string_lossy().to_string();
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::MaybeOpenFsRepository::create(root)
+            .await
+            .unwrap(),
+    );
+    let storage = Storage::new(repo).unwrap();
+
+    let live_layer = || LiveLayer {
+        api: SpecApiVersion::V0Layer,
+        contents: vec![LiveLayerContents::BindMount(BindMount {
+            src: "/tmp".into(),
+            dest: "tests/tests/tests".to_string(),
+        })],
+    };
+
+    let keep_runtime = false;
+    let mut first = storage
+        .create_runtime(keep_runtime, vec![live_layer()])
+        .await
+        .expect("failed to create first runtime in storage");
+    first
+        .prepare_live_layers()
+        .await
+        .expect("failed to prepare first runtime's live layers");
+
+    let mut second = storage
+        .create_runtime(keep_runtime, vec![live_layer()])
+        .await
+        .expect("failed to create second runtime in storage");
+    second
+        .prepare_live_layers()
+        .await
+        .expect("failed to prepare second runtime's live layers");
+
+    assert_eq!(
+        first.status.stack.to_top_down(),
+        second.status.stack.to_top_down(),
+        "identical live layer bind mounts should produce the same cached extra-mounts layer"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_runtime_effective_live_layers_re_roots_binds_under_a_dest_prefix(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path().to_string_lossy().to_string();
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::MaybeOpenFsRepository::create(root)
+            .await
+            .unwrap(),
+    );
+    let storage = Storage::new(repo).unwrap();
+
+    let mount = BindMount {
+        src: "/tmp".into(),
+        dest: "/spfs/project/src".to_string(),
+    };
+    let live_layer = LiveLayer {
+        api: SpecApiVersion::V0Layer,
+        contents: vec![LiveLayerContents::BindMount(mount)],
+    };
+
+    let keep_runtime = false;
+    let mut runtime = storage
+        .create_runtime(keep_runtime, vec![live_layer])
+        .await
+        .expect("failed to create runtime in storage");
+
+    // Unset, the bind's destination is used as-is.
+    let unprefixed = runtime.effective_live_layers().unwrap();
+    assert_eq!(unprefixed[0].bind_mounts()[0].dest, "/spfs/project/src");
+
+    runtime.config.dest_prefix = Some("/spfs/checkout-123".to_string());
+    let rerooted = runtime.effective_live_layers().unwrap();
+    assert_eq!(rerooted[0].bind_mounts()[0].dest, "/spfs/checkout-123/src");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_runtime_effective_live_layers_rejects_a_dest_prefix_outside_of_spfs(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path().to_string_lossy().to_string();
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::MaybeOpenFsRepository::create(root)
+            .await
+            .unwrap(),
+    );
+    let storage = Storage::new(repo).unwrap();
+
+    let mount = BindMount {
+        src: "/tmp".into(),
+        dest: "/spfs/project/src".to_string(),
+    };
+    let live_layer = LiveLayer {
+        api: SpecApiVersion::V0Layer,
+        contents: vec![LiveLayerContents::BindMount(mount)],
+    };
+
+    let keep_runtime = false;
+    let mut runtime = storage
+        .create_runtime(keep_runtime, vec![live_layer])
+        .await
+        .expect("failed to create runtime in storage");
+
+    runtime.config.dest_prefix = Some("/tmp/escaped".to_string());
+    runtime
+        .effective_live_layers()
+        .expect_err("dest_prefix must keep binds under /spfs");
+}
+
 #[cfg(unix)]
 #[rstest]
 fn test_makedirs_dont_change_existing(tmpdir: tempfile::TempDir) {
@@ -1014,7 +1014,8 @@ pub struct Repositories {
     /// origin@2022-10-11T13:00.12). This time affects all interactions and
     /// queries in the repository, effectively making it look like it did in the past.
     /// It will cause errors for any operation that attempts to make changes to
-    /// the repository, even if the time is in the future.
+    /// the repository, even if the time is in the future. "local" cannot be
+    /// given a time specifier, since it isn't time-addressable.
     #[clap(long, short = 'r')]
     pub enable_repo: Vec<String>,
 
@@ -1059,19 +1060,38 @@ impl Repositories {
     /// `--no-local-repo` is used.
     pub async fn get_repos_for_destructive_operation(
         &self,
+    ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
+        self.get_repos_for_destructive_operation_impl(&[]).await
+    }
+
+    /// Like [`Repositories::get_repos_for_destructive_operation`], but
+    /// also enables any repository named in `workspace`'s `repositories:`
+    /// list that the command line didn't already explicitly enable or
+    /// disable.
+    ///
+    /// `--enable-repo`/`--disable-repo` are always authoritative: an
+    /// explicit choice on the command line is never overridden by the
+    /// workspace. A workspace's `repositories:` only fills in repos a
+    /// user hasn't already made an explicit choice about, so a project
+    /// can pin the repos it needs without every invocation requiring `-r`.
+    pub async fn get_repos_for_destructive_operation_with_workspace(
+        &self,
+        workspace: &spk_workspace::Workspace,
+    ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
+        self.get_repos_for_destructive_operation_impl(workspace.repositories())
+            .await
+    }
+
+    async fn get_repos_for_destructive_operation_impl(
+        &self,
+        workspace_repositories: &[String],
     ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
         if let Some(repo_path) = &self.wrap_origin {
             spk_storage::inject_path_repo_into_spfs_config(repo_path)?;
         }
 
-        let mut enabled = Vec::with_capacity(self.enable_repo.len());
+        let enabled = parse_enabled_repos(&self.effective_enable_repo(workspace_repositories))?;
         let disabled: HashSet<&str> = self.disable_repo.iter().map(String::as_str).collect();
-        for r in self.enable_repo.iter() {
-            match r.find(['~', '@']) {
-                Some(i) => enabled.push((&r[..i], Some(spfs::tracking::TimeSpec::parse(&r[i..])?))),
-                None => enabled.push((r, None)),
-            };
-        }
 
         let mut repos = Vec::with_capacity(enabled.len());
         if !self.no_local_repo
@@ -1122,19 +1142,39 @@ impl Repositories {
     /// "origin".
     pub async fn get_repos_for_non_destructive_operation(
         &self,
+    ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
+        self.get_repos_for_non_destructive_operation_impl(&[])
+            .await
+    }
+
+    /// Like [`Repositories::get_repos_for_non_destructive_operation`],
+    /// but also enables any repository named in `workspace`'s
+    /// `repositories:` list that the command line didn't already
+    /// explicitly enable or disable.
+    ///
+    /// `--enable-repo`/`--disable-repo` are always authoritative: an
+    /// explicit choice on the command line is never overridden by the
+    /// workspace. A workspace's `repositories:` only fills in repos a
+    /// user hasn't already made an explicit choice about, so a project
+    /// can pin the repos it needs without every invocation requiring `-r`.
+    pub async fn get_repos_for_non_destructive_operation_with_workspace(
+        &self,
+        workspace: &spk_workspace::Workspace,
+    ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
+        self.get_repos_for_non_destructive_operation_impl(workspace.repositories())
+            .await
+    }
+
+    async fn get_repos_for_non_destructive_operation_impl(
+        &self,
+        workspace_repositories: &[String],
     ) -> Result<Vec<(String, storage::RepositoryHandle)>> {
         if let Some(repo_path) = &self.wrap_origin {
             spk_storage::inject_path_repo_into_spfs_config(repo_path)?;
         }
 
-        let mut enabled = Vec::with_capacity(self.enable_repo.len());
+        let enabled = parse_enabled_repos(&self.effective_enable_repo(workspace_repositories))?;
         let disabled: HashSet<&str> = self.disable_repo.iter().map(String::as_str).collect();
-        for r in self.enable_repo.iter() {
-            match r.find(['~', '@']) {
-                Some(i) => enabled.push((&r[..i], Some(spfs::tracking::TimeSpec::parse(&r[i..])?))),
-                None => enabled.push((r, None)),
-            };
-        }
 
         let mut repos = Vec::new();
         if !self.no_local_repo
@@ -1191,6 +1231,58 @@ impl Repositories {
         }
         Ok(repos)
     }
+
+    /// Merge `self.enable_repo` with `workspace_repositories`, appending
+    /// any workspace-declared name that isn't already explicitly
+    /// enabled or disabled on the command line.
+    fn effective_enable_repo(&self, workspace_repositories: &[String]) -> Vec<String> {
+        let disabled: HashSet<&str> = self.disable_repo.iter().map(String::as_str).collect();
+        let explicit: HashSet<&str> = self
+            .enable_repo
+            .iter()
+            .map(|entry| repo_entry_name(entry))
+            .collect();
+
+        let mut merged = self.enable_repo.clone();
+        for name in workspace_repositories {
+            if disabled.contains(name.as_str()) || explicit.contains(name.as_str()) {
+                continue;
+            }
+            merged.push(name.clone());
+        }
+        merged
+    }
+}
+
+/// The repository name portion of an `--enable-repo` entry, with any
+/// `~`/`@` time specifier stripped off.
+fn repo_entry_name(entry: &str) -> &str {
+    match entry.find(['~', '@']) {
+        Some(i) => &entry[..i],
+        None => entry,
+    }
+}
+
+/// Split each `--enable-repo` entry on its first `~`/`@` time
+/// specifier, parsing the remainder with [`spfs::tracking::TimeSpec`].
+///
+/// `local` is rejected if it carries a time specifier, since the
+/// local repository isn't time-addressable.
+fn parse_enabled_repos(
+    entries: &[String],
+) -> Result<Vec<(&str, Option<spfs::tracking::TimeSpec>)>> {
+    let mut enabled = Vec::with_capacity(entries.len());
+    for r in entries {
+        let (name, ts) = match r.find(['~', '@']) {
+            Some(i) => (&r[..i], Some(spfs::tracking::TimeSpec::parse(&r[i..])?)),
+            None => (r.as_str(), None),
+        };
+        if name == "local" && ts.is_some() {
+            bail!("the \"local\" repository is not time-addressable, remove the time specifier from --enable-repo {r}");
+        }
+        enabled.push((name, ts));
+    }
+    Ok(enabled)
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
@@ -115,3 +115,70 @@ async fn test_get_solver_with_host_options(
         }
     }
 }
+
+#[rstest]
+#[case::bare_name("origin", "origin", false)]
+#[case::rfc3339("origin@2022-10-11T13:00:00Z", "origin", true)]
+#[case::shorthand_relative("origin~10m", "origin", true)]
+#[case::shorthand_absolute_date("origin@2022-10-11", "origin", true)]
+fn test_parse_enabled_repos_splits_name_and_time(
+    #[case] entry: &str,
+    #[case] expected_name: &str,
+    #[case] expect_time: bool,
+) {
+    let enabled = super::parse_enabled_repos(&[entry.to_string()]).unwrap();
+    assert_eq!(enabled.len(), 1);
+    assert_eq!(enabled[0].0, expected_name);
+    assert_eq!(enabled[0].1.is_some(), expect_time);
+}
+
+#[test]
+fn test_parse_enabled_repos_rejects_a_time_specifier_on_local() {
+    let err = super::parse_enabled_repos(&["local@2022-10-11".to_string()])
+        .expect_err("local is not time-addressable");
+    assert!(format!("{err:?}").contains("not time-addressable"));
+}
+
+#[test]
+fn test_parse_enabled_repos_allows_local_without_a_time_specifier() {
+    let enabled = super::parse_enabled_repos(&["local".to_string()]).unwrap();
+    assert_eq!(enabled, vec![("local", None)]);
+}
+
+fn repos_with(enable_repo: &[&str], disable_repo: &[&str]) -> crate::flags::Repositories {
+    crate::flags::Repositories {
+        local_repo_only: false,
+        no_local_repo: false,
+        enable_repo: enable_repo.iter().map(ToString::to_string).collect(),
+        disable_repo: disable_repo.iter().map(ToString::to_string).collect(),
+        when: None,
+        wrap_origin: None,
+    }
+}
+
+#[test]
+fn test_effective_enable_repo_appends_a_workspace_repository() {
+    let repos = repos_with(&[], &[]);
+
+    let merged = repos.effective_enable_repo(&["staging".to_string()]);
+
+    assert_eq!(merged, vec!["staging".to_string()]);
+}
+
+#[test]
+fn test_effective_enable_repo_does_not_duplicate_an_already_enabled_repository() {
+    let repos = repos_with(&["staging"], &[]);
+
+    let merged = repos.effective_enable_repo(&["staging".to_string()]);
+
+    assert_eq!(merged, vec!["staging".to_string()]);
+}
+
+#[test]
+fn test_effective_enable_repo_omits_a_workspace_repository_that_was_explicitly_disabled() {
+    let repos = repos_with(&[], &["staging"]);
+
+    let merged = repos.effective_enable_repo(&["staging".to_string()]);
+
+    assert!(merged.is_empty());
+}
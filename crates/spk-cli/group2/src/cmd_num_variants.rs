@@ -30,12 +30,15 @@ impl Run for NumVariants {
 
     async fn run(&mut self) -> Result<Self::Output> {
         let options = self.options.get_options()?;
-        let names_and_repos = self.repos.get_repos_for_non_destructive_operation().await?;
+        let mut workspace = self.workspace.load_or_default()?;
+        let names_and_repos = self
+            .repos
+            .get_repos_for_non_destructive_operation_with_workspace(&workspace)
+            .await?;
         let repos = names_and_repos
             .into_iter()
             .map(|(_, r)| Arc::new(r))
             .collect::<Vec<_>>();
-        let mut workspace = self.workspace.load_or_default()?;
 
         let (spec_data, filename) = flags::find_package_recipe_from_workspace_or_repo(
             self.package.as_ref(),
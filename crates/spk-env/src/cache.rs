@@ -0,0 +1,203 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Caching package-resolution results keyed by the request that
+//! produced them.
+//!
+//! `spenv` does not perform package resolution itself; this module
+//! provides the caching primitive that a solver integration consults
+//! via [`resolve_with_cache`]. No `spenv` command or flag reads from or
+//! writes to the cache; `spenv prune-cache` only deletes expired entries.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::CacheError;
+
+#[cfg(test)]
+#[path = "cache_test.rs"]
+mod cache_test;
+
+/// The subdirectory created under the platform cache directory.
+const CACHE_SUBDIR: &str = "spenv/solutions";
+
+/// A cached package-resolution result, keyed by the request that
+/// produced it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedSolution {
+    /// The repositories that were in scope when this solution was cached
+    pub repo_set: Vec<String>,
+    /// The serialized solution
+    pub solution: String,
+    /// The unix timestamp, in seconds, when this entry was written
+    pub cached_at: u64,
+}
+
+/// A disk-backed cache of [`CachedSolution`]s, keyed by a hash of the
+/// request that produced them and invalidated by age or repository
+/// changes.
+#[derive(Debug, Clone)]
+pub struct SolutionCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl SolutionCache {
+    /// Create a cache rooted at `dir`, with entries expiring after `ttl`.
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+        }
+    }
+
+    /// The default cache directory, under the platform's XDG (or
+    /// equivalent) cache directory.
+    pub fn default_dir() -> Result<PathBuf, CacheError> {
+        dirs::cache_dir()
+            .map(|dir| dir.join(CACHE_SUBDIR))
+            .ok_or(CacheError::NoCacheDir)
+    }
+
+    /// Compute the cache key for a package resolution request. The
+    /// same packages, options and repo set always hash to the same key.
+    pub fn key(
+        packages: &[String],
+        package_options: &BTreeMap<String, String>,
+        repo_set: &[String],
+    ) -> String {
+        use std::hash::{Hash, Hasher};
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        packages.hash(&mut hasher);
+        package_options.hash(&mut hasher);
+        repo_set.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    /// Look up `key`, returning the cached solution if present, not
+    /// expired, and still associated with the given `repo_set`.
+    pub fn get(&self, key: &str, repo_set: &[String]) -> Option<CachedSolution> {
+        let contents = std::fs::read_to_string(self.entry_path(key)).ok()?;
+        let cached: CachedSolution = serde_yaml::from_str(&contents).ok()?;
+        if cached.repo_set != repo_set {
+            return None;
+        }
+        let age = unix_now().saturating_sub(cached.cached_at);
+        if age >= self.ttl.as_secs() {
+            return None;
+        }
+        Some(cached)
+    }
+
+    /// Store `solution` under `key`, recording the `repo_set` it was
+    /// resolved against.
+    pub fn put(&self, key: &str, solution: &str, repo_set: &[String]) -> Result<(), CacheError> {
+        std::fs::create_dir_all(&self.dir).map_err(|source| CacheError::CreateDirFailed {
+            path: self.dir.clone(),
+            source,
+        })?;
+        let entry = CachedSolution {
+            repo_set: repo_set.to_vec(),
+            solution: solution.to_owned(),
+            cached_at: unix_now(),
+        };
+        let path = self.entry_path(key);
+        let yaml = serde_yaml::to_string(&entry).map_err(CacheError::Serialize)?;
+        std::fs::write(&path, yaml).map_err(|source| CacheError::WriteFailed { path, source })
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.yaml"))
+    }
+
+    /// Delete every entry in this cache that has outlived its `ttl`,
+    /// returning the number removed.
+    ///
+    /// An entry that can no longer be parsed (e.g. written by an
+    /// incompatible version) is treated as expired and removed too,
+    /// since there is nothing else useful to do with it.
+    pub fn prune(&self) -> Result<usize, CacheError> {
+        let entries = match std::fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(source) => {
+                return Err(CacheError::ReadFailed {
+                    path: self.dir.clone(),
+                    source,
+                });
+            }
+        };
+
+        let now = unix_now();
+        let mut pruned = 0;
+        for entry in entries {
+            let path = entry
+                .map_err(|source| CacheError::ReadFailed {
+                    path: self.dir.clone(),
+                    source,
+                })?
+                .path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+
+            let expired = match std::fs::read_to_string(&path) {
+                Ok(contents) => match serde_yaml::from_str::<CachedSolution>(&contents) {
+                    Ok(cached) => now.saturating_sub(cached.cached_at) >= self.ttl.as_secs(),
+                    Err(_) => true,
+                },
+                Err(_) => true,
+            };
+            if !expired {
+                continue;
+            }
+
+            std::fs::remove_file(&path)
+                .map_err(|source| CacheError::RemoveFailed { path, source })?;
+            pruned += 1;
+        }
+        Ok(pruned)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Resolve a package request, consulting `cache` first when given.
+///
+/// On a cache hit, `solve` is never called. On a miss, `solve` is
+/// called to compute the solution, which is then stored in the cache
+/// for next time. A cache write failure is not fatal; the freshly
+/// solved result is still returned.
+pub fn resolve_with_cache<F, E>(
+    cache: Option<&SolutionCache>,
+    packages: &[String],
+    package_options: &BTreeMap<String, String>,
+    repo_set: &[String],
+    solve: F,
+) -> Result<String, E>
+where
+    F: FnOnce() -> Result<String, E>,
+{
+    let Some(cache) = cache else {
+        return solve();
+    };
+
+    let key = SolutionCache::key(packages, package_options, repo_set);
+    if let Some(cached) = cache.get(&key, repo_set) {
+        return Ok(cached.solution);
+    }
+
+    let solution = solve()?;
+    let _ = cache.put(&key, &solution, repo_set);
+    Ok(solution)
+}
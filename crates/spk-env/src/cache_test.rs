@@ -0,0 +1,123 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use rstest::{fixture, rstest};
+
+use super::{SolutionCache, resolve_with_cache};
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_resolve_with_cache_hits_then_misses_on_differing_options(tmpdir: tempfile::TempDir) {
+    let cache = SolutionCache::new(tmpdir.path(), Duration::from_secs(60));
+    let packages = vec!["foo".to_string()];
+    let repo_set = vec!["main".to_string()];
+    let solve_calls = Cell::new(0);
+
+    let mut options_a = BTreeMap::new();
+    options_a.insert("variant".to_string(), "a".to_string());
+
+    let solve = || -> Result<String, std::convert::Infallible> {
+        solve_calls.set(solve_calls.get() + 1);
+        Ok("solved-a".to_string())
+    };
+    let first = resolve_with_cache(Some(&cache), &packages, &options_a, &repo_set, solve).unwrap();
+    assert_eq!(first, "solved-a");
+    assert_eq!(solve_calls.get(), 1, "a cache miss should call solve");
+
+    let solve_again = || -> Result<String, std::convert::Infallible> {
+        solve_calls.set(solve_calls.get() + 1);
+        Ok("should-not-be-used".to_string())
+    };
+    let second =
+        resolve_with_cache(Some(&cache), &packages, &options_a, &repo_set, solve_again).unwrap();
+    assert_eq!(
+        second, "solved-a",
+        "a cache hit should return the stored solution"
+    );
+    assert_eq!(
+        solve_calls.get(),
+        1,
+        "a cache hit should not call solve again"
+    );
+
+    let mut options_b = BTreeMap::new();
+    options_b.insert("variant".to_string(), "b".to_string());
+    let solve_b = || -> Result<String, std::convert::Infallible> {
+        solve_calls.set(solve_calls.get() + 1);
+        Ok("solved-b".to_string())
+    };
+    let third =
+        resolve_with_cache(Some(&cache), &packages, &options_b, &repo_set, solve_b).unwrap();
+    assert_eq!(third, "solved-b");
+    assert_eq!(
+        solve_calls.get(),
+        2,
+        "differing package options should be a distinct cache key, causing a miss"
+    );
+}
+
+#[rstest]
+fn test_resolve_with_cache_ignores_stale_entries(tmpdir: tempfile::TempDir) {
+    let cache = SolutionCache::new(tmpdir.path(), Duration::from_secs(0));
+    let packages = vec!["foo".to_string()];
+    let options = BTreeMap::new();
+    let repo_set = vec!["main".to_string()];
+
+    cache
+        .put(
+            &SolutionCache::key(&packages, &options, &repo_set),
+            "stale",
+            &repo_set,
+        )
+        .unwrap();
+
+    let solved = resolve_with_cache(
+        Some(&cache),
+        &packages,
+        &options,
+        &repo_set,
+        || -> Result<String, std::convert::Infallible> { Ok("fresh".to_string()) },
+    )
+    .unwrap();
+    assert_eq!(
+        solved, "fresh",
+        "an expired entry should be treated as a miss"
+    );
+}
+
+#[rstest]
+fn test_prune_removes_expired_entries_but_keeps_fresh_ones(tmpdir: tempfile::TempDir) {
+    let cache = SolutionCache::new(tmpdir.path(), Duration::from_secs(60));
+    let old = super::CachedSolution {
+        repo_set: Vec::new(),
+        solution: "stale".to_string(),
+        cached_at: 0,
+    };
+    std::fs::write(
+        tmpdir.path().join("expired.yaml"),
+        serde_yaml::to_string(&old).unwrap(),
+    )
+    .unwrap();
+    cache.put("fresh", "current", &[]).unwrap();
+
+    let pruned = cache.prune().unwrap();
+
+    assert_eq!(pruned, 1);
+    assert!(cache.get("fresh", &[]).is_some());
+    assert!(
+        !tmpdir.path().join("expired.yaml").exists(),
+        "the expired entry should have been deleted from disk"
+    );
+}
@@ -0,0 +1,38 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::ComposedEnvironment;
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "cat_test.rs"]
+mod cat_test;
+
+/// Print the composed environment flattened into a single,
+/// machine-consumable spec document
+#[derive(Debug, Args)]
+pub struct Cat {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// Omit the provenance header listing the layers the output was
+    /// consolidated from, producing the tersest valid spec
+    #[clap(long)]
+    strip_comments: bool,
+}
+
+impl Cat {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_overlay(env)?;
+
+        print!("{}", env.to_yaml(self.strip_comments));
+        Ok(0)
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::Cat;
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn cat(dir: &std::path::Path, strip_comments: bool) -> Cat {
+    Cat {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        strip_comments,
+    }
+}
+
+#[rstest]
+fn test_cat_runs_successfully_with_and_without_strip_comments(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    assert_eq!(cat(dir, false).run().unwrap(), 0);
+    assert_eq!(cat(dir, true).run().unwrap(), 0);
+}
@@ -0,0 +1,251 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result, miette};
+use spk_env::{ComposedEnvironment, EnvLock, LockChange, format_changes};
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "check_test.rs"]
+mod check_test;
+
+/// How a `spenv check` result should be printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+enum CheckFormat {
+    /// One line per change, in the shared format used wherever a
+    /// change list is reported to a user
+    #[default]
+    Text,
+    /// One standalone JSON object per change, one per line. Every line
+    /// is rendered before the first is printed; output is not streamed
+    /// as each change is found
+    Ndjson,
+    /// A single JSON object, `{"matches": bool, "changes": [...]}`,
+    /// buffered and printed once the full comparison is known.
+    /// Suited to CI dashboards that want one document per invocation
+    /// rather than a stream.
+    Json,
+}
+
+/// Check a composed environment against its lock file for drift
+#[derive(Debug, Args)]
+pub struct Check {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// The lock file to check against, defaults to `.spenv.lock.yaml` in the current directory
+    #[clap(long)]
+    lock_file: Option<PathBuf>,
+
+    /// Only check whether the locked spec files have changed on disk,
+    /// skipping layer resolution entirely. Works offline and is much
+    /// faster, at the cost of missing drift from moved or reordered layers.
+    #[clap(long)]
+    sources_only: bool,
+
+    /// Treat a missing lock file as an error instead of a no-op.
+    /// Overrides the `lock.strict` setting from the spec, if any
+    #[clap(long)]
+    strict: bool,
+
+    /// When drift is detected, regenerate the lock file in place from
+    /// the freshly resolved environment instead of just reporting it.
+    /// Must always be given explicitly; `--strict` never implies it,
+    /// so CI can't be surprised into silently rewriting a lock
+    #[clap(long)]
+    fix: bool,
+
+    /// Print a human-readable summary of any drift without rewriting
+    /// the lock file, and exit 0 regardless, so it's safe to run
+    /// anywhere without affecting a script's exit code
+    #[clap(long)]
+    diff: bool,
+
+    /// How to print any detected drift
+    #[clap(long, value_enum, default_value_t = CheckFormat::Text)]
+    format: CheckFormat,
+
+    /// Only check the lock file's own internal consistency (hashes
+    /// look well-formed, timestamps aren't in the future, and so on),
+    /// without resolving an environment or comparing it against one.
+    /// Catches a hand-edited or corrupted lock before the rest of
+    /// `check` tries to use it. Cannot be combined with `--fix`,
+    /// `--diff` or `--sources-only`.
+    #[clap(long)]
+    validate: bool,
+}
+
+impl Check {
+    pub fn run(self) -> Result<i32> {
+        let lock_file = self
+            .lock_file
+            .unwrap_or_else(|| PathBuf::from(EnvLock::FILE_NAME));
+
+        if self.validate {
+            if self.fix || self.diff || self.sources_only {
+                return Err(miette!(
+                    "--validate cannot be combined with --fix, --diff, or --sources-only"
+                ));
+            }
+            let lock = EnvLock::load_file(&lock_file).into_diagnostic()?;
+            return match lock.validate() {
+                Ok(()) => {
+                    println!("{} is internally consistent", lock_file.display());
+                    Ok(0)
+                }
+                Err(err) => {
+                    println!("{}: {err}", lock_file.display());
+                    Ok(1)
+                }
+            };
+        }
+
+        if self.fix && self.sources_only {
+            return Err(miette!(
+                "--fix requires resolving the full environment, and cannot be combined with --sources-only"
+            ));
+        }
+        if self.fix && self.diff {
+            return Err(miette!("--fix and --diff cannot be combined"));
+        }
+
+        // `--sources-only` exists to skip layer resolution entirely, so
+        // it also skips the spec-driven lock policy, which can only be
+        // read off of the resolved layers.
+        if self.sources_only {
+            let lock = match EnvLock::load_file(&lock_file) {
+                Ok(lock) => lock,
+                Err(_) if !self.strict => {
+                    println!("no lock file found, skipping");
+                    return Ok(0);
+                }
+                Err(err) => return Err(err).into_diagnostic(),
+            };
+            let changes = spk_env::verify_sources(&lock);
+            return if self.diff {
+                Self::diff(changes, self.format)
+            } else {
+                Self::report(changes, self.format)
+            };
+        }
+
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_overlay(env)?;
+
+        let policy = env.effective_lock_policy();
+        if !policy.enabled {
+            println!("lock checking disabled by spec");
+            return Ok(0);
+        }
+        let strict = self.strict || policy.strict;
+
+        let lock = match EnvLock::load_file(&lock_file) {
+            Ok(lock) => lock,
+            Err(_) if !strict => {
+                println!("no lock file found, skipping");
+                return Ok(0);
+            }
+            Err(err) => return Err(err).into_diagnostic(),
+        };
+
+        let changes = spk_env::verify_lock(&env, &lock);
+        if self.fix {
+            return Self::fix(&env, &lock_file, changes, self.format);
+        }
+        if self.diff {
+            return Self::diff(changes, self.format);
+        }
+        Self::report(changes, self.format)
+    }
+
+    /// Regenerate the lock file at `lock_file` from `env`, if `changes`
+    /// shows there was any drift to fix.
+    fn fix(
+        env: &ComposedEnvironment,
+        lock_file: &std::path::Path,
+        changes: Vec<LockChange>,
+        format: CheckFormat,
+    ) -> Result<i32> {
+        if changes.is_empty() {
+            report_no_drift(format);
+            return Ok(0);
+        }
+        EnvLock::generate(env)
+            .save_file(lock_file)
+            .into_diagnostic()?;
+        if format != CheckFormat::Json {
+            println!("regenerated {} after detecting drift:", lock_file.display());
+        }
+        print_changes(&changes, format);
+        Ok(0)
+    }
+
+    /// Print a summary of `changes` and exit 0 regardless, whether or
+    /// not any drift was found.
+    fn diff(changes: Vec<LockChange>, format: CheckFormat) -> Result<i32> {
+        if changes.is_empty() {
+            report_no_drift(format);
+            return Ok(0);
+        }
+        print_changes(&changes, format);
+        Ok(0)
+    }
+
+    fn report(changes: Vec<LockChange>, format: CheckFormat) -> Result<i32> {
+        if changes.is_empty() {
+            report_no_drift(format);
+            return Ok(0);
+        }
+        print_changes(&changes, format);
+        Ok(1)
+    }
+}
+
+/// Report that no drift was found, in the requested `format`.
+fn report_no_drift(format: CheckFormat) {
+    if format == CheckFormat::Json {
+        print_changes(&[], format);
+    } else {
+        println!("no drift detected");
+    }
+}
+
+/// Print `changes` in the requested `format`, one line at a time, or as
+/// a single JSON document for [`CheckFormat::Json`].
+fn print_changes(changes: &[LockChange], format: CheckFormat) {
+    for line in render_changes(changes, format) {
+        println!("{line}");
+    }
+}
+
+/// Render `changes` in the requested `format`. In [`CheckFormat::Ndjson`],
+/// each change becomes its own standalone line; all lines are returned
+/// together, not streamed one at a time. In [`CheckFormat::Json`],
+/// all of `changes` are buffered into one `{"matches", "changes"}` object.
+fn render_changes(changes: &[LockChange], format: CheckFormat) -> Vec<String> {
+    match format {
+        CheckFormat::Text => vec![format_changes(changes)],
+        CheckFormat::Ndjson => changes
+            .iter()
+            .map(|change| {
+                serde_json::to_string(change)
+                    .expect("serializing a lock change to JSON should not fail")
+            })
+            .collect(),
+        CheckFormat::Json => vec![
+            serde_json::to_string(&serde_json::json!({
+                "matches": changes.is_empty(),
+                "changes": changes,
+            }))
+            .expect("serializing a lock change report to JSON should not fail"),
+        ],
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+use spk_env::{ComposedEnvironment, EnvLock};
+
+use super::{Check, CheckFormat, render_changes};
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn check(dir: &std::path::Path, fix: bool) -> Check {
+    Check {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        lock_file: Some(dir.join(EnvLock::FILE_NAME)),
+        sources_only: false,
+        strict: false,
+        fix,
+        diff: false,
+        format: CheckFormat::Text,
+        validate: false,
+    }
+}
+
+#[rstest]
+fn test_fix_regenerates_a_drifted_lock_so_a_later_check_reports_no_drift(
+    tmpdir: tempfile::TempDir,
+) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    let code = check(dir, true).run().unwrap();
+    assert_eq!(code, 0);
+
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /usr/bin\n",
+    )
+    .unwrap();
+
+    let code = check(dir, true).run().unwrap();
+    assert_eq!(code, 0);
+
+    let code = check(dir, false).run().unwrap();
+    assert_eq!(code, 0);
+}
+
+#[rstest]
+fn test_fix_is_a_no_op_when_there_is_no_drift(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+    let before = std::fs::read_to_string(dir.join(EnvLock::FILE_NAME)).unwrap();
+
+    assert_eq!(check(dir, true).run().unwrap(), 0);
+    assert_eq!(check(dir, true).run().unwrap(), 0);
+    let after = std::fs::read_to_string(dir.join(EnvLock::FILE_NAME)).unwrap();
+    assert_eq!(before, after);
+}
+
+#[rstest]
+fn test_diff_exits_zero_even_when_drift_is_detected(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /usr/bin\n",
+    )
+    .unwrap();
+
+    // A plain check would exit 1 here, but --diff never affects the
+    // exit code, and the lock file is left untouched.
+    assert_eq!(check(dir, false).run().unwrap(), 1);
+    let diff = Check {
+        diff: true,
+        ..check(dir, false)
+    };
+    assert_eq!(diff.run().unwrap(), 0);
+
+    let lock = EnvLock::load_file(dir.join(EnvLock::FILE_NAME)).unwrap();
+    assert!(!spk_env::verify_lock(&ComposedEnvironment::resolve(dir).unwrap(), &lock).is_empty());
+}
+
+#[rstest]
+fn test_ndjson_format_emits_one_standalone_json_line_per_change(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 2\n").unwrap();
+    let lock = EnvLock::load_file(dir.join(EnvLock::FILE_NAME)).unwrap();
+    let changes = spk_env::verify_lock(&ComposedEnvironment::resolve(dir).unwrap(), &lock);
+
+    let lines = render_changes(&changes, CheckFormat::Ndjson);
+
+    assert_eq!(lines.len(), changes.len());
+    assert!(!lines.is_empty());
+    let kinds: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|line| {
+            let parsed: serde_json::Value =
+                serde_json::from_str(line).expect("each line should parse on its own");
+            parsed["kind"].clone()
+        })
+        .collect();
+    assert!(kinds.contains(&serde_json::Value::from("priority_changed")));
+}
+
+#[rstest]
+fn test_diff_and_fix_cannot_be_combined(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    assert_eq!(check(dir, true).run().unwrap(), 0);
+
+    let both = Check {
+        diff: true,
+        ..check(dir, true)
+    };
+    assert!(both.run().is_err());
+}
+
+#[rstest]
+fn test_validate_accepts_a_well_formed_lock(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    let validate = Check {
+        validate: true,
+        ..check(dir, false)
+    };
+    assert_eq!(validate.run().unwrap(), 0);
+}
+
+#[rstest]
+fn test_validate_rejects_a_corrupted_lock(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let mut lock = EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap());
+    lock.metadata.source_hashes[0].hash = "not-hex".to_string();
+    lock.save_file(dir.join(EnvLock::FILE_NAME)).unwrap();
+
+    let validate = Check {
+        validate: true,
+        ..check(dir, false)
+    };
+    assert_eq!(validate.run().unwrap(), 1);
+}
+
+#[rstest]
+fn test_json_format_reports_matches_true_when_there_is_no_drift(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    let json = Check {
+        format: CheckFormat::Json,
+        ..check(dir, false)
+    };
+    assert_eq!(json.run().unwrap(), 0);
+}
+
+#[rstest]
+fn test_json_format_reports_matches_false_with_the_detected_changes(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    EnvLock::generate(&ComposedEnvironment::resolve(dir).unwrap())
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 2\n").unwrap();
+    let lock = EnvLock::load_file(dir.join(EnvLock::FILE_NAME)).unwrap();
+    let changes = spk_env::verify_lock(&ComposedEnvironment::resolve(dir).unwrap(), &lock);
+
+    let lines = render_changes(&changes, CheckFormat::Json);
+    assert_eq!(lines.len(), 1);
+    let parsed: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(parsed["matches"], serde_json::Value::from(false));
+    assert!(!parsed["changes"].as_array().unwrap().is_empty());
+
+    let json = Check {
+        format: CheckFormat::Json,
+        ..check(dir, false)
+    };
+    assert_eq!(json.run().unwrap(), 1);
+}
+
+#[rstest]
+fn test_validate_cannot_be_combined_with_fix(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "priority: 1\n").unwrap();
+
+    let both = Check {
+        validate: true,
+        ..check(dir, true)
+    };
+    assert!(both.run().is_err());
+}
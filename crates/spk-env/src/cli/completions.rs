@@ -0,0 +1,28 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::Write;
+
+use clap::{Args, CommandFactory};
+use clap_complete::Shell;
+use miette::Result;
+
+use crate::cli::Opt;
+
+/// Print a shell completion script for `spenv` to stdout
+#[derive(Debug, Args)]
+#[command(hide = true)]
+pub struct Completions {
+    /// The shell to generate completions for
+    pub shell: Shell,
+}
+
+impl Completions {
+    pub fn run(self) -> Result<i32> {
+        let mut buf = vec![];
+        clap_complete::generate(self.shell, &mut Opt::command(), "spenv", &mut buf);
+        std::io::stdout().write_all(&buf).unwrap_or(());
+        Ok(0)
+    }
+}
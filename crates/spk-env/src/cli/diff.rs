@@ -0,0 +1,258 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+use spk_env::{BindMount, ComposedEnvironment, EnvOp};
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "diff_test.rs"]
+mod diff_test;
+
+/// How a `spenv diff` result should be printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+enum DiffFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+/// Compare two composed environments and report how they differ
+#[derive(Debug, Args)]
+pub struct Diff {
+    /// The first environment's start directory
+    path_a: PathBuf,
+
+    /// The second environment's start directory
+    path_b: PathBuf,
+
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// How to print the result
+    #[clap(long, value_enum, default_value_t = DiffFormat::Human)]
+    format: DiffFormat,
+
+    /// Disable ANSI coloring of added/removed lines, for terminals or
+    /// log viewers that don't render it cleanly
+    #[clap(long)]
+    no_color: bool,
+}
+
+impl Diff {
+    pub fn run(self) -> Result<i32> {
+        let options = self.discovery.options();
+        let env_a = ComposedEnvironment::resolve_with_options(&self.path_a, options.clone())
+            .into_diagnostic()?;
+        let env_a = self.discovery.apply_overlay(env_a)?;
+        let env_b =
+            ComposedEnvironment::resolve_with_options(&self.path_b, options).into_diagnostic()?;
+        let env_b = self.discovery.apply_overlay(env_b)?;
+
+        let layers_a: Vec<String> = env_a
+            .layers
+            .iter()
+            .map(|l| l.file_path.display().to_string())
+            .collect();
+        let layers_b: Vec<String> = env_b
+            .layers
+            .iter()
+            .map(|l| l.file_path.display().to_string())
+            .collect();
+        let ops_a: Vec<String> = env_a.effective_ops().iter().map(format_op).collect();
+        let ops_b: Vec<String> = env_b.effective_ops().iter().map(format_op).collect();
+        let binds_a: Vec<String> = env_a
+            .layers
+            .iter()
+            .flat_map(|l| l.binds.iter())
+            .map(format_bind)
+            .collect();
+        let binds_b: Vec<String> = env_b
+            .layers
+            .iter()
+            .flat_map(|l| l.binds.iter())
+            .map(format_bind)
+            .collect();
+
+        // Layer paths are shown for context, but aren't part of the
+        // identity check: two coworkers' environments live at
+        // different paths on disk by definition, so the paths
+        // themselves always differ even when nothing meaningful has.
+        let identical = ops_a == ops_b && binds_a == binds_b;
+
+        match self.format {
+            DiffFormat::Human => {
+                let mut printed = false;
+                printed |= print_section(self.no_color, "layers", &layers_a, &layers_b);
+                printed |= print_section(self.no_color, "env ops", &ops_a, &ops_b);
+                printed |= print_section(self.no_color, "binds", &binds_a, &binds_b);
+                if !printed {
+                    println!("no differences found");
+                }
+            }
+            DiffFormat::Json => {
+                let delta = EnvDiff {
+                    layers: delta_section(&layers_a, &layers_b),
+                    ops: delta_section(&ops_a, &ops_b),
+                    binds: delta_section(&binds_a, &binds_b),
+                };
+                println!(
+                    "{}",
+                    serde_json::to_string(&delta)
+                        .expect("serializing a diff to JSON should not fail")
+                );
+            }
+        }
+
+        Ok(i32::from(!identical))
+    }
+}
+
+pub(super) fn format_op(op: &EnvOp) -> String {
+    match op {
+        EnvOp::Set { var, value } => format!("set {var}={value}"),
+        EnvOp::Prepend { var, value } => format!("prepend {var}={value}"),
+        EnvOp::Append { var, value } => format!("append {var}={value}"),
+        EnvOp::Default { var, value } => format!("default {var}={value}"),
+        EnvOp::PathRemove {
+            var,
+            value,
+            separator,
+        } => format!(
+            "path_remove {var}={value} (separator {})",
+            separator.as_deref().unwrap_or(":")
+        ),
+        EnvOp::Unset { var } => format!("unset {var}"),
+        EnvOp::Source { source } => format!("source {source}"),
+    }
+}
+
+pub(super) fn format_bind(bind: &BindMount) -> String {
+    format!("{} -> {}", bind.source.display(), bind.dest.display())
+}
+
+/// A single line of a [`diff_lines`] result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum DiffLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A minimal LCS-based line diff: enough to produce a stable unified
+/// `+`/`-` listing for the small lists `spenv diff` compares, without
+/// pulling in a dedicated diffing dependency.
+fn diff_lines(a: &[String], b: &[String]) -> Vec<DiffLine> {
+    let (n, m) = (a.len(), b.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            out.push(DiffLine::Context(a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(DiffLine::Removed(a[i].clone()));
+            i += 1;
+        } else {
+            out.push(DiffLine::Added(b[j].clone()));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().cloned().map(DiffLine::Removed));
+    out.extend(b[j..].iter().cloned().map(DiffLine::Added));
+    out
+}
+
+/// Print `name`'s unified diff, if `a` and `b` differ. Returns whether
+/// anything was printed.
+fn print_section(no_color: bool, name: &str, a: &[String], b: &[String]) -> bool {
+    match render_section(no_color, name, a, b) {
+        Some(section) => {
+            print!("{section}");
+            true
+        }
+        None => false,
+    }
+}
+
+/// Render `name`'s unified diff as a single block, or `None` if `a`
+/// and `b` don't differ. Colored with ANSI escapes unless `no_color`.
+fn render_section(no_color: bool, name: &str, a: &[String], b: &[String]) -> Option<String> {
+    let lines = diff_lines(a, b);
+    if lines
+        .iter()
+        .all(|line| matches!(line, DiffLine::Context(_)))
+    {
+        return None;
+    }
+    let mut out = format!("== {name} ==\n");
+    for line in lines {
+        out.push_str(&render_diff_line(no_color, &line));
+        out.push('\n');
+    }
+    Some(out)
+}
+
+/// Render a single diff line, colorizing added/removed lines unless
+/// `no_color`.
+fn render_diff_line(no_color: bool, line: &DiffLine) -> String {
+    match line {
+        DiffLine::Context(text) => format!("  {text}"),
+        DiffLine::Removed(text) => {
+            let line = format!("- {text}");
+            if no_color {
+                line
+            } else {
+                line.red().to_string()
+            }
+        }
+        DiffLine::Added(text) => {
+            let line = format!("+ {text}");
+            if no_color {
+                line
+            } else {
+                line.green().to_string()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DiffSection {
+    removed: Vec<String>,
+    added: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EnvDiff {
+    layers: DiffSection,
+    ops: DiffSection,
+    binds: DiffSection,
+}
+
+fn delta_section(a: &[String], b: &[String]) -> DiffSection {
+    DiffSection {
+        removed: a.iter().filter(|x| !b.contains(x)).cloned().collect(),
+        added: b.iter().filter(|x| !a.contains(x)).cloned().collect(),
+    }
+}
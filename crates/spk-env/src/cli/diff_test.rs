@@ -0,0 +1,141 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{Diff, DiffFormat, delta_section, diff_lines, format_bind, format_op, render_section};
+use crate::cli::DiscoveryArgs;
+use spk_env::{BindMount, EnvOp};
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn diff(path_a: std::path::PathBuf, path_b: std::path::PathBuf) -> Diff {
+    Diff {
+        path_a,
+        path_b,
+        discovery: DiscoveryArgs {
+            path: None,
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        format: DiffFormat::Human,
+        no_color: false,
+    }
+}
+
+#[test]
+fn test_diff_lines_reports_additions_and_removals_around_shared_context() {
+    let a = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+    let b = vec!["one".to_string(), "four".to_string(), "three".to_string()];
+
+    let lines = diff_lines(&a, &b);
+
+    assert_eq!(lines.len(), 4);
+    assert!(format!("{lines:?}").contains("Removed(\"two\")"));
+    assert!(format!("{lines:?}").contains("Added(\"four\")"));
+}
+
+#[test]
+fn test_delta_section_only_reports_lines_unique_to_each_side() {
+    let a = vec!["one".to_string(), "two".to_string()];
+    let b = vec!["two".to_string(), "three".to_string()];
+
+    let section = delta_section(&a, &b);
+
+    assert_eq!(section.removed, vec!["one".to_string()]);
+    assert_eq!(section.added, vec!["three".to_string()]);
+}
+
+#[test]
+fn test_format_op_and_format_bind_produce_readable_lines() {
+    let op = EnvOp::Set {
+        var: "PATH".to_string(),
+        value: "/bin".to_string(),
+    };
+    assert_eq!(format_op(&op), "set PATH=/bin");
+
+    let bind = BindMount {
+        source: std::path::PathBuf::from("/host/data"),
+        dest: std::path::PathBuf::from("/data"),
+    };
+    assert_eq!(format_bind(&bind), "/host/data -> /data");
+
+    let op = EnvOp::Source {
+        source: "/opt/tool/env.sh".to_string(),
+    };
+    assert_eq!(format_op(&op), "source /opt/tool/env.sh");
+}
+
+#[test]
+fn test_no_color_renders_plain_ascii_markers() {
+    let a = vec!["one".to_string(), "two".to_string()];
+    let b = vec!["one".to_string(), "three".to_string()];
+
+    // Force coloring on, as if run in a color-capable terminal, so
+    // `no_color` can be shown to override it rather than merely
+    // agreeing with `colored`'s own non-tty auto-detection.
+    colored::control::set_override(true);
+    let colored = render_section(false, "env ops", &a, &b).unwrap();
+    let plain = render_section(true, "env ops", &a, &b).unwrap();
+    colored::control::unset_override();
+
+    assert!(
+        colored.bytes().any(|b| b == 0x1b),
+        "forced-color rendering should contain ANSI escapes to compare against"
+    );
+    assert!(plain.is_ascii());
+    assert!(!plain.bytes().any(|b| b == 0x1b));
+    assert!(plain.contains("- two"));
+    assert!(plain.contains("+ three"));
+}
+
+#[rstest]
+fn test_identical_environments_exit_zero(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let a = root.join("a");
+    let b = root.join("b");
+    std::fs::create_dir_all(&a).unwrap();
+    std::fs::create_dir_all(&b).unwrap();
+    let contents = "ops:\n  - op: set\n    var: PATH\n    value: /bin\n";
+    std::fs::write(a.join(".spenv.yaml"), contents).unwrap();
+    std::fs::write(b.join(".spenv.yaml"), contents).unwrap();
+
+    let code = diff(a, b).run().unwrap();
+    assert_eq!(code, 0);
+}
+
+#[rstest]
+fn test_differing_environments_exit_one(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let a = root.join("a");
+    let b = root.join("b");
+    std::fs::create_dir_all(&a).unwrap();
+    std::fs::create_dir_all(&b).unwrap();
+    std::fs::write(
+        a.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        b.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /usr/bin\n",
+    )
+    .unwrap();
+
+    let code = diff(a, b).run().unwrap();
+    assert_eq!(code, 1);
+}
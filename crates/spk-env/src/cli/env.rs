@@ -0,0 +1,78 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::runtime::{
+    RuntimeOptions, ShellKind, generate_startup_script,
+    generate_startup_scripts_by_priority_with_options,
+};
+use spk_env::{ComposedEnvironment, EnvLock};
+
+use super::DiscoveryArgs;
+
+/// Print a startup script that applies the composed environment,
+/// for `eval "$(spenv env)"` style shell integration
+#[derive(Debug, Args)]
+pub struct Env {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// The shell dialect to generate, defaults to detecting from `$SHELL`
+    #[clap(long, value_enum)]
+    shell: Option<ShellKind>,
+
+    /// Split the output into one script per distinct priority value,
+    /// each preceded by a `# <startup.d filename>` header, instead of
+    /// a single combined script
+    #[clap(long)]
+    by_priority: bool,
+
+    /// Requires --by-priority. Added to each generated filename's
+    /// priority, so spenv's scripts can be placed in a band reserved
+    /// for it (e.g. 80-89) relative to other tools writing into the
+    /// same `startup.d` directory
+    #[clap(long, requires = "by_priority", default_value_t = 0)]
+    priority_base: i32,
+
+    /// Requires --by-priority. Emit one script per contributing spec,
+    /// numbered by that spec's own priority, instead of one script
+    /// per distinct priority value shared by consecutive layers
+    #[clap(long, requires = "by_priority")]
+    split_startup_scripts: bool,
+}
+
+impl Env {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_overlay(env)?;
+        let lock = EnvLock::load_file(EnvLock::FILE_NAME).ok();
+
+        let shell = self.shell.unwrap_or_else(|| {
+            let shell_path = std::env::var("SHELL").unwrap_or_default();
+            ShellKind::from_shell_path(&shell_path)
+        });
+
+        if self.by_priority {
+            let options = RuntimeOptions {
+                priority_base: self.priority_base,
+                split_startup_scripts: self.split_startup_scripts,
+            };
+            for script in generate_startup_scripts_by_priority_with_options(
+                &env,
+                lock.as_ref(),
+                shell,
+                options,
+            ) {
+                println!("# {}", script.filename);
+                print!("{}", script.script);
+            }
+        } else {
+            print!("{}", generate_startup_script(&env, lock.as_ref(), shell));
+        }
+        Ok(0)
+    }
+}
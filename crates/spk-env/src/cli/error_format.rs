@@ -0,0 +1,49 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use serde::Serialize;
+
+#[cfg(test)]
+#[path = "error_format_test.rs"]
+mod error_format_test;
+
+/// How a failing command's error should be printed to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum ErrorFormat {
+    /// miette's default human-readable diagnostic rendering
+    #[default]
+    Human,
+    /// A single-line JSON object with the error's code, message and help,
+    /// for editor and CI integrations
+    Json,
+}
+
+/// The machine-readable rendering of a [`miette::Diagnostic`] error,
+/// used by `--error-format json`.
+#[derive(Debug, Serialize)]
+struct JsonDiagnostic {
+    code: Option<String>,
+    message: String,
+    help: Option<String>,
+}
+
+impl JsonDiagnostic {
+    fn from_report(err: &miette::Report) -> Self {
+        Self {
+            code: err.code().map(|c| c.to_string()),
+            message: err.to_string(),
+            help: err.help().map(|h| h.to_string()),
+        }
+    }
+}
+
+/// Render a failing command's error for stderr, honoring `format`.
+pub fn render_error(err: &miette::Report, format: ErrorFormat) -> String {
+    match format {
+        ErrorFormat::Human => err.to_string(),
+        ErrorFormat::Json => serde_json::to_string(&JsonDiagnostic::from_report(err))
+            .expect("serializing a diagnostic to JSON should not fail"),
+    }
+}
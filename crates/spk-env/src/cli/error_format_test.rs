@@ -0,0 +1,33 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use spk_env::error::LoadSpecError;
+
+use super::{ErrorFormat, render_error};
+
+#[test]
+fn test_a_load_spec_error_serializes_with_its_diagnostic_code() {
+    let err = miette::Report::new(LoadSpecError::NotFound(PathBuf::from(
+        "/missing/.spenv.yaml",
+    )));
+
+    let rendered = render_error(&err, ErrorFormat::Json);
+
+    let value: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+    assert_eq!(value["code"], "spenv::spec_not_found");
+    assert!(value["message"].as_str().unwrap().contains("/missing"));
+}
+
+#[test]
+fn test_human_format_renders_plain_display() {
+    let err = miette::Report::new(LoadSpecError::NotFound(PathBuf::from(
+        "/missing/.spenv.yaml",
+    )));
+
+    let rendered = render_error(&err, ErrorFormat::Human);
+
+    assert_eq!(rendered, err.to_string());
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::ComposedEnvironment;
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "export_test.rs"]
+mod export_test;
+
+/// The manifest format a `spenv export` should produce
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "kebab-case")]
+enum ExportFormat {
+    /// A Docker/OCI-style environment manifest, see [`spk_env::EnvManifest`]
+    #[default]
+    OciEnv,
+    /// A single, self-contained `.spenv.yaml` with every include
+    /// inlined, for sharing with someone who can't reach our include
+    /// paths
+    SpenvYaml,
+}
+
+/// Export the composed environment as a portable manifest, for
+/// bridging a resolved environment into a container, or flattening it
+/// into a standalone spec file for sharing
+#[derive(Debug, Args)]
+pub struct Export {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// The manifest format to produce
+    #[clap(long = "format", value_enum, default_value_t = ExportFormat::OciEnv)]
+    format: ExportFormat,
+
+    /// Write the export to this file instead of printing it to stdout
+    #[clap(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+impl Export {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_overlay(env)?;
+
+        let rendered = match self.format {
+            ExportFormat::OciEnv => {
+                let manifest = env.to_env_manifest();
+                serde_json::to_string_pretty(&manifest)
+                    .expect("serializing an env manifest to JSON should not fail")
+            }
+            ExportFormat::SpenvYaml => env.to_standalone_yaml(),
+        };
+
+        match &self.output {
+            Some(path) => std::fs::write(path, rendered).into_diagnostic()?,
+            None => println!("{rendered}"),
+        }
+        Ok(0)
+    }
+}
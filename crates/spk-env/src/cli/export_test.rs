@@ -0,0 +1,95 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+use spk_env::ComposedEnvironment;
+
+use super::{Export, ExportFormat};
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn export(dir: &std::path::Path) -> Export {
+    Export {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        format: ExportFormat::OciEnv,
+        output: None,
+    }
+}
+
+#[rstest]
+fn test_export_runs_successfully(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    assert_eq!(export(dir).run().unwrap(), 0);
+}
+
+#[rstest]
+fn test_export_spenv_yaml_round_trips_to_an_identical_environment(tmpdir: tempfile::TempDir) {
+    let src = tmpdir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(
+        src.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: BASE\n    value: '1'\n",
+    )
+    .unwrap();
+    std::fs::write(
+        src.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: prepend\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    let out = tmpdir.path().join("out.spenv.yaml");
+    let exported = Export {
+        discovery: DiscoveryArgs {
+            path: Some(src.clone()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        format: ExportFormat::SpenvYaml,
+        output: Some(out.clone()),
+    };
+    assert_eq!(exported.run().unwrap(), 0);
+
+    let original = ComposedEnvironment::resolve(&src).unwrap();
+
+    let exported_dir = tmpdir.path().join("exported");
+    std::fs::create_dir(&exported_dir).unwrap();
+    std::fs::copy(&out, exported_dir.join(".spenv.yaml")).unwrap();
+    let reloaded = ComposedEnvironment::resolve(&exported_dir).unwrap();
+
+    assert_eq!(reloaded.effective_ops(), original.effective_ops());
+    assert_eq!(reloaded.layers.len(), 1);
+    assert!(!reloaded.layers[0].inherit);
+    assert!(reloaded.layers[0].includes.is_empty());
+}
@@ -0,0 +1,58 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result, miette};
+use spk_env::{ComposedEnvironment, DiscoveryOptions};
+
+#[cfg(test)]
+#[path = "init_test.rs"]
+mod init_test;
+
+const STARTER_SPEC: &str = "ops: []\n";
+
+/// Scaffold a new `.spenv.yaml`
+///
+/// With no `--from`, writes a minimal starter spec. With `--from`,
+/// resolves the environment already discoverable at that path and
+/// flattens it into the new file instead, the same way `spenv export
+/// --format spenv-yaml` would, so a project that already has a working
+/// environment somewhere else can be onboarded without hand-copying it.
+#[derive(Debug, Args)]
+pub struct Init {
+    /// Flatten the environment discovered from this path into the new
+    /// spec, instead of writing a minimal starter spec
+    #[clap(long, value_name = "PATH")]
+    from: Option<PathBuf>,
+
+    /// Where to write the new spec, defaults to `.spenv.yaml` in the
+    /// current directory
+    #[clap(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+}
+
+impl Init {
+    pub fn run(self) -> Result<i32> {
+        let output = self.output.unwrap_or_else(|| PathBuf::from(".spenv.yaml"));
+        if output.exists() {
+            return Err(miette!("{} already exists", output.display()));
+        }
+
+        let contents = match &self.from {
+            Some(path) => {
+                let env =
+                    ComposedEnvironment::resolve_with_options(path, DiscoveryOptions::default())
+                        .into_diagnostic()?;
+                env.to_standalone_yaml()
+            }
+            None => STARTER_SPEC.to_string(),
+        };
+
+        std::fs::write(&output, contents).into_diagnostic()?;
+        println!("wrote {}", output.display());
+        Ok(0)
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+use spk_env::ComposedEnvironment;
+
+use super::Init;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_init_with_no_from_writes_a_minimal_starter_spec(tmpdir: tempfile::TempDir) {
+    let output = tmpdir.path().join(".spenv.yaml");
+    let init = Init {
+        from: None,
+        output: Some(output.clone()),
+    };
+
+    assert_eq!(init.run().unwrap(), 0);
+
+    let spec = std::fs::read_to_string(&output).unwrap();
+    assert_eq!(spec, "ops: []\n");
+}
+
+#[rstest]
+fn test_init_refuses_to_overwrite_an_existing_file(tmpdir: tempfile::TempDir) {
+    let output = tmpdir.path().join(".spenv.yaml");
+    std::fs::write(&output, "ops: []\n").unwrap();
+
+    let init = Init {
+        from: None,
+        output: Some(output.clone()),
+    };
+
+    init.run().expect_err("should refuse to overwrite the file");
+}
+
+#[rstest]
+fn test_init_from_an_existing_environment_flattens_it_into_the_new_spec(tmpdir: tempfile::TempDir) {
+    let src = tmpdir.path().join("src");
+    std::fs::create_dir(&src).unwrap();
+    std::fs::write(
+        src.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: BASE\n    value: '1'\n",
+    )
+    .unwrap();
+    std::fs::write(
+        src.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: prepend\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    let output = tmpdir.path().join("new").join(".spenv.yaml");
+    std::fs::create_dir(output.parent().unwrap()).unwrap();
+    let init = Init {
+        from: Some(src.clone()),
+        output: Some(output.clone()),
+    };
+
+    assert_eq!(init.run().unwrap(), 0);
+
+    let original = ComposedEnvironment::resolve(&src).unwrap();
+    let reloaded_dir = output.parent().unwrap();
+    let reloaded = ComposedEnvironment::resolve(reloaded_dir).unwrap();
+
+    assert_eq!(reloaded.effective_ops(), original.effective_ops());
+    assert_eq!(reloaded.layers.len(), 1);
+}
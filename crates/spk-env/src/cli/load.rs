@@ -0,0 +1,282 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use miette::{Context, IntoDiagnostic, Result, miette};
+use spk_env::error::RuntimeError;
+use spk_env::runtime::{
+    AsUser, SPENV_ACTIVE_VAR, active_runtime, resolve_as_user, resolve_cwd,
+    resolve_env_vars_with_lock,
+};
+use spk_env::{ComposedEnvironment, EnvLock, format_changes, verify_lock};
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "load_test.rs"]
+mod load_test;
+
+/// Run a command inside the composed environment
+#[derive(Debug, Args)]
+pub struct Load {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// The directory to start the command in, instead of inheriting
+    /// the invocation's current directory
+    #[clap(long)]
+    cwd: Option<PathBuf>,
+
+    /// The command to run, defaults to the user's shell
+    #[clap(trailing_var_arg = true)]
+    command: Vec<String>,
+
+    /// The lock file to consult, defaults to `.spenv.lock.yaml` in the current directory
+    #[clap(long)]
+    lock_file: Option<PathBuf>,
+
+    /// Fail if no lock file is found, instead of running unlocked.
+    /// Overrides the `lock.strict` setting from the spec, if any
+    #[clap(long)]
+    strict: bool,
+
+    /// Allow loading from within an already-active spenv runtime,
+    /// i.e. when `SPENV_ACTIVE` is already set. Without this, a
+    /// nested `spenv load` is refused, since it would apply this
+    /// environment's operations on top of an already-loaded one.
+    #[clap(long)]
+    nested: bool,
+
+    /// Require `.spenv.lock.yaml` to exist and refuse to load unless
+    /// the current specs still match it exactly, for reproducible
+    /// entry into a previously locked environment. Overrides the
+    /// spec's `lock` policy
+    #[clap(long)]
+    locked: bool,
+
+    /// Allow `--locked` to proceed even though the lock has drifted
+    /// from the current specs
+    #[clap(long)]
+    force: bool,
+
+    /// Spawn the command as a different `uid[:gid]`, dropping
+    /// privilege before it runs. Requires the current process to be
+    /// running as root, or already as the requested uid
+    #[clap(long, value_name = "uid[:gid]")]
+    as_user: Option<AsUser>,
+
+    /// Allow loading an environment that contributes no layers,
+    /// packages, ops, binds or contents, instead of refusing
+    #[clap(long)]
+    allow_empty: bool,
+
+    /// Skip applying the composed environment's ops (set/prepend/
+    /// append/default/unset/source), so the command runs with only
+    /// the ambient environment, unmodified by any spec. Useful when
+    /// debugging whether a problem comes from spenv's own op
+    /// resolution or from somewhere else
+    #[clap(long)]
+    no_startup: bool,
+
+    /// Print each layer's file path and content digest, then exit
+    /// without running the command. Read-only: no lock file is
+    /// consulted and no environment variables are resolved. A layer
+    /// whose file can no longer be read is reported as an error
+    /// rather than aborting the rest of the listing
+    #[clap(long)]
+    dry_run: bool,
+}
+
+impl Load {
+    pub fn run(self) -> Result<i32> {
+        if !self.nested
+            && let Some(active) = active_runtime()
+        {
+            return Err(miette!(
+                "a spenv runtime is already active ({active:?}); pass --nested to load another one inside it"
+            ));
+        }
+
+        let start = self.discovery.start_dir()?;
+        let marker = start.display().to_string();
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_layers(env)?;
+        let env = self.discovery.apply_overlay(env)?;
+        if env.is_empty() && !self.allow_empty {
+            return Err(RuntimeError::EmptyEnvironment).into_diagnostic();
+        }
+
+        if self.dry_run {
+            return Ok(print_layer_digests(&env));
+        }
+
+        let lock_file = self
+            .lock_file
+            .unwrap_or_else(|| PathBuf::from(EnvLock::FILE_NAME));
+
+        let policy = env.effective_lock_policy();
+        let lock = if self.locked {
+            let lock = EnvLock::load_file(&lock_file).into_diagnostic()?;
+            let changes = verify_lock(&env, &lock);
+            if !changes.is_empty() && !self.force {
+                return Err(miette!(
+                    "lock has drifted from the current specs, refusing to load:\n{}\npass --force to load the pinned environment anyway",
+                    format_changes(&changes)
+                ));
+            }
+            Some(lock)
+        } else if !policy.enabled {
+            None
+        } else if self.strict || policy.strict {
+            Some(EnvLock::load_file(&lock_file).into_diagnostic()?)
+        } else {
+            EnvLock::load_file(&lock_file).ok()
+        };
+        if let Some((_, description)) = env.descriptions().last() {
+            println!("Entering: {description}");
+        }
+
+        let mut vars = if self.no_startup {
+            HashMap::new()
+        } else {
+            resolve_env_vars_with_lock(&env, lock.as_ref())
+        };
+        vars.insert(SPENV_ACTIVE_VAR.to_string(), marker);
+        let cwd = resolve_cwd(self.cwd.as_deref()).into_diagnostic()?;
+        let as_user = resolve_as_user(self.as_user).into_diagnostic()?;
+
+        let mut args = self.command.into_iter();
+        let program = args
+            .next()
+            .or_else(|| std::env::var("SHELL").ok())
+            .unwrap_or_else(|| "/bin/sh".to_string());
+
+        let status = build_command(&program, args.collect(), vars, cwd.as_deref(), as_user)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to resolve credentials for {program:?}"))?
+            .status()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to run {program:?}"))?;
+
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// Print `reference -> digest` for each of `env`'s layers, in
+/// composition order, or `reference -> error: ...` for a layer whose
+/// file can no longer be read. Returns 1 if any layer failed to
+/// resolve, 0 otherwise, mirroring a shell exit code.
+fn print_layer_digests(env: &ComposedEnvironment) -> i32 {
+    let mut failed = false;
+    for layer in &env.layers {
+        let reference = layer.file_path.display();
+        match digest_of(&layer.file_path) {
+            Ok(digest) => println!("{reference} -> {digest}"),
+            Err(err) => {
+                failed = true;
+                println!("{reference} -> error: {err}");
+            }
+        }
+    }
+    if failed { 1 } else { 0 }
+}
+
+/// The content digest of the spec file at `path`, the same hash
+/// [`spk_env::EnvLock`] records for drift detection.
+fn digest_of(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path).into_diagnostic()?;
+    Ok(spk_env::hash_contents(&contents))
+}
+
+/// Build the child process command for `program`, applying `cwd` as
+/// its working directory if one was given, and dropping privilege to
+/// `as_user` if one was given.
+///
+/// Errors if `as_user`'s credentials can't be resolved (the passwd
+/// lookup or `getgrouplist` fails); a uid with no passwd entry is not
+/// an error, see [`resolve_drop_credentials`]. Resolution happens here,
+/// in the parent, rather than inside the `pre_exec` closure below.
+fn build_command(
+    program: &str,
+    args: Vec<String>,
+    vars: HashMap<String, String>,
+    cwd: Option<&Path>,
+    as_user: Option<AsUser>,
+) -> std::io::Result<std::process::Command> {
+    let mut command = std::process::Command::new(program);
+    command.args(args).envs(vars);
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    if let Some(as_user) = as_user {
+        use std::os::unix::process::CommandExt;
+        let creds = resolve_drop_credentials(&as_user).map_err(std::io::Error::from)?;
+        // SAFETY: the closure only calls async-signal-safe libc
+        // functions (setgroups, setgid, setuid) on `creds`, already
+        // resolved above in the parent, and does not allocate or
+        // otherwise touch process state.
+        unsafe {
+            command.pre_exec(move || drop_privileges_to(&creds));
+        }
+    }
+    Ok(command)
+}
+
+/// `uid`/`gid`/supplementary groups already resolved in the parent,
+/// ready to be applied by [`drop_privileges_to`] with no further NSS
+/// lookups or allocation.
+struct DropCredentials {
+    uid: nix::unistd::Uid,
+    gid: nix::unistd::Gid,
+    groups: Vec<nix::unistd::Gid>,
+}
+
+/// Resolve the uid/gid/supplementary-group list for `as_user`, doing
+/// every NSS lookup and allocation here in the parent, before `fork`,
+/// so the `pre_exec` closure that actually applies them has neither to
+/// do.
+///
+/// `as_user.gid` defaults to the target uid's primary group if not
+/// given. The supplementary group list is the target user's own, via
+/// `getgrouplist`, falling back to no supplementary groups at all if
+/// `as_user.uid` has no passwd entry.
+fn resolve_drop_credentials(as_user: &AsUser) -> nix::Result<DropCredentials> {
+    let uid = nix::unistd::Uid::from_raw(as_user.uid);
+    let user = nix::unistd::User::from_uid(uid)?;
+    let gid = as_user
+        .gid
+        .map(nix::unistd::Gid::from_raw)
+        .or_else(|| user.as_ref().map(|user| user.gid))
+        .unwrap_or_else(|| nix::unistd::Gid::from_raw(as_user.uid));
+    let groups = match &user {
+        Some(user) => {
+            let name = std::ffi::CString::new(user.name.clone()).map_err(|_| nix::Error::EINVAL)?;
+            nix::unistd::getgrouplist(&name, gid)?
+        }
+        None => Vec::new(),
+    };
+    Ok(DropCredentials { uid, gid, groups })
+}
+
+/// Apply already-resolved `creds` to the calling (expected to be
+/// root) process, run from the child between `fork` and `exec`.
+///
+/// This replaces `Command::uid`/`gid`, which only change the child's
+/// uid/gid and leave its supplementary groups untouched, so a
+/// root-spawned command would keep root's full group membership
+/// despite "dropping" to another user. Order matters: groups and gid
+/// must be set while still privileged, before the final `setuid`
+/// gives that up. Every value here was already resolved in the
+/// parent by [`resolve_drop_credentials`], so this only makes the
+/// async-signal-safe `setgroups`/`setgid`/`setuid` calls themselves.
+fn drop_privileges_to(creds: &DropCredentials) -> std::io::Result<()> {
+    nix::unistd::setgroups(&creds.groups).map_err(std::io::Error::from)?;
+    nix::unistd::setgid(creds.gid).map_err(std::io::Error::from)?;
+    nix::unistd::setuid(creds.uid).map_err(std::io::Error::from)?;
+    Ok(())
+}
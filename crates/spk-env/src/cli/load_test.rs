@@ -0,0 +1,268 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+
+use rstest::{fixture, rstest};
+use spk_env::runtime::SPENV_ACTIVE_VAR;
+use spk_env::{ComposedEnvironment, EnvLock};
+
+use super::{Load, build_command};
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn load(dir: &std::path::Path) -> Load {
+    Load {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        lock_file: Some(dir.join(EnvLock::FILE_NAME)),
+        cwd: None,
+        command: vec!["true".to_string()],
+        strict: false,
+        nested: false,
+        locked: false,
+        force: false,
+        as_user: None,
+        // Most of these tests use a trivial spec that contributes
+        // nothing, to focus on the behavior under test rather than on
+        // package resolution; opt out of the empty-environment guard
+        // here so it doesn't get in the way.
+        allow_empty: true,
+        no_startup: false,
+        dry_run: false,
+    }
+}
+
+#[rstest]
+fn test_cwd_sets_the_child_process_working_directory(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path().canonicalize().unwrap();
+
+    let output = build_command("pwd", Vec::new(), HashMap::new(), Some(&dir), None)
+        .expect("no as_user, so no credential resolution to fail")
+        .output()
+        .expect("spawn pwd");
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim(),
+        dir.to_str().unwrap()
+    );
+}
+
+#[rstest]
+fn test_nesting_guard_triggers_when_the_marker_is_set(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "ops: []\n").unwrap();
+
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::set_var(SPENV_ACTIVE_VAR, "/some/other/env");
+    }
+    let result = load(dir).run();
+    unsafe {
+        std::env::remove_var(SPENV_ACTIVE_VAR);
+    }
+
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn test_nested_flag_bypasses_the_guard(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "ops: []\n").unwrap();
+
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::set_var(SPENV_ACTIVE_VAR, "/some/other/env");
+    }
+    let result = Load {
+        nested: true,
+        ..load(dir)
+    }
+    .run();
+    unsafe {
+        std::env::remove_var(SPENV_ACTIVE_VAR);
+    }
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_locked_refuses_to_load_a_stale_lock(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let spec_path = dir.join(".spenv.yaml");
+    std::fs::write(&spec_path, "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(dir).unwrap();
+    EnvLock::generate(&env)
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    std::fs::write(&spec_path, "priority: 2\n").unwrap();
+    let result = Load {
+        locked: true,
+        ..load(dir)
+    }
+    .run();
+
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn test_locked_force_loads_a_stale_lock_anyway(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let spec_path = dir.join(".spenv.yaml");
+    std::fs::write(&spec_path, "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(dir).unwrap();
+    EnvLock::generate(&env)
+        .save_file(dir.join(EnvLock::FILE_NAME))
+        .unwrap();
+
+    std::fs::write(&spec_path, "priority: 2\n").unwrap();
+    let result = Load {
+        locked: true,
+        force: true,
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_locked_requires_a_lock_file_to_exist(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "ops: []\n").unwrap();
+
+    let result = Load {
+        locked: true,
+        ..load(dir)
+    }
+    .run();
+
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn test_empty_environment_is_refused_by_default(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "ops: []\n").unwrap();
+
+    let result = Load {
+        allow_empty: false,
+        ..load(dir)
+    }
+    .run();
+
+    assert!(result.is_err());
+}
+
+#[rstest]
+fn test_allow_empty_bypasses_the_empty_environment_guard(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "ops: []\n").unwrap();
+
+    let result = Load {
+        allow_empty: true,
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_the_environments_ops_apply_by_default(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: SPENV_TEST_FOO\n    value: bar\n",
+    )
+    .unwrap();
+
+    let result = Load {
+        command: vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "[ \"$SPENV_TEST_FOO\" = bar ]".to_string(),
+        ],
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_dry_run_reports_success_without_running_the_command(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "note: pinned for CVE fix\n").unwrap();
+
+    let result = Load {
+        dry_run: true,
+        // If dry-run actually ran this, it would fail and the
+        // assertion below would catch it.
+        command: vec!["false".to_string()],
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_no_startup_skips_applying_the_environments_ops(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: SPENV_TEST_FOO\n    value: bar\n",
+    )
+    .unwrap();
+
+    let result = Load {
+        no_startup: true,
+        command: vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "[ -z \"$SPENV_TEST_FOO\" ]".to_string(),
+        ],
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
+
+#[rstest]
+fn test_an_environment_with_ops_is_not_considered_empty(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: FOO\n    value: bar\n",
+    )
+    .unwrap();
+
+    let result = Load {
+        allow_empty: false,
+        ..load(dir)
+    }
+    .run();
+
+    assert_eq!(result.unwrap(), 0);
+}
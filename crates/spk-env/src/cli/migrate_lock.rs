@@ -0,0 +1,30 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::EnvLock;
+
+/// Upgrade a lock file written by an older version of spenv to the
+/// current schema, rewriting it in place
+#[derive(Debug, Args)]
+pub struct MigrateLock {
+    /// The lock file to migrate, defaults to `.spenv.lock.yaml` in the current directory
+    #[clap(long)]
+    lock_file: Option<PathBuf>,
+}
+
+impl MigrateLock {
+    pub fn run(self) -> Result<i32> {
+        let lock_file = self
+            .lock_file
+            .unwrap_or_else(|| PathBuf::from(EnvLock::FILE_NAME));
+        let lock = spk_env::migrate_lock_file(&lock_file).into_diagnostic()?;
+        lock.save_file(&lock_file).into_diagnostic()?;
+        println!("migrated {} to the current schema", lock_file.display());
+        Ok(0)
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Subcommand implementations for the `spenv` binary.
+
+mod cat;
+mod check;
+mod completions;
+mod diff;
+mod env;
+mod error_format;
+mod export;
+mod init;
+mod load;
+mod migrate_lock;
+mod prune_cache;
+mod prune_includes;
+mod schema;
+mod shell;
+mod show;
+mod validate;
+mod verify_includes;
+
+#[cfg(test)]
+#[path = "mod_test.rs"]
+mod mod_test;
+
+pub use cat::Cat;
+pub use check::Check;
+pub use completions::Completions;
+pub use diff::Diff;
+pub use env::Env;
+pub use error_format::{ErrorFormat, render_error};
+pub use export::Export;
+pub use init::Init;
+pub use load::Load;
+pub use migrate_lock::MigrateLock;
+pub use prune_cache::PruneCache;
+pub use prune_includes::PruneIncludes;
+pub use schema::Schema;
+pub use shell::Shell;
+pub use show::Show;
+pub use validate::Validate;
+pub use verify_includes::VerifyIncludes;
+
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use miette::{IntoDiagnostic, Result};
+use spk_env::DiscoveryOptions;
+
+/// Arguments shared by every subcommand that discovers and composes
+/// an environment from `.spenv.yaml` specs on disk.
+#[derive(Debug, Args)]
+pub struct DiscoveryArgs {
+    /// The directory to start discovery from, defaults to the current
+    /// directory. If this points directly at a spec file instead of a
+    /// directory, its parent is used as the start directory and that
+    /// exact file is loaded as the start spec, regardless of `--filename`.
+    #[clap(short = 'f', long, global = true)]
+    pub path: Option<PathBuf>,
+
+    /// The maximum number of parent directories to search when
+    /// `inherit: true` is set, counted from the start directory.
+    /// A value of 0 searches only the start directory. Defaults to
+    /// the `SPENV_MAX_DEPTH` environment variable, or no limit.
+    #[clap(long, global = true)]
+    pub max_depth: Option<usize>,
+
+    /// A file or directory name that marks the top of a project.
+    /// May be repeated. Once a directory containing one of these is
+    /// visited, discovery stops ascending, even if its spec has
+    /// `inherit: true`. Defaults to the colon-separated
+    /// `SPENV_STOP_AT` environment variable, or no markers.
+    #[clap(long = "stop-at", global = true)]
+    pub stop_at: Vec<String>,
+
+    /// The file name to look for in each directory, in place of
+    /// `.spenv.yaml`. Defaults to the `SPENV_FILENAME` environment
+    /// variable, or `.spenv.yaml`.
+    #[clap(long, global = true)]
+    pub filename: Option<String>,
+
+    /// Compose a named overlay spec from `~/.spenv/overlays` on top of
+    /// the discovered environment, giving it the highest precedence of
+    /// any layer
+    #[clap(long, global = true)]
+    pub overlay: Option<String>,
+
+    /// Compose the machine-wide default spec (see `--system-default-path`)
+    /// as the lowest-precedence layer, before any discovered spec.
+    /// Defaults to the `SPENV_SYSTEM_DEFAULTS` environment variable, or
+    /// disabled. A missing file is not an error.
+    #[clap(long, global = true)]
+    pub system_defaults: bool,
+
+    /// The path to the machine-wide default spec consulted when
+    /// `--system-defaults` is set. Defaults to the
+    /// `SPENV_SYSTEM_DEFAULT_PATH` environment variable, or
+    /// `/etc/spenv/default.spenv.yaml`.
+    #[clap(long, global = true)]
+    pub system_default_path: Option<PathBuf>,
+
+    /// Refuse to inherit a parent spec that is world-writable or
+    /// owned by a UID other than the current user or root, instead of
+    /// only warning about it. Defaults to the `SPENV_TRUSTED_ONLY`
+    /// environment variable, or disabled. Unix-specific.
+    #[clap(long, global = true)]
+    pub trusted_only: bool,
+
+    /// Compose a named overlay spec from `~/.spenv/overlays` into the
+    /// environment, the same way `--overlay` does, without making it
+    /// the highest-precedence layer. May be repeated. Defaults to the
+    /// colon- or comma-separated `SPENV_LAYERS` environment variable,
+    /// or none
+    #[clap(long = "layer", global = true)]
+    pub layers: Vec<String>,
+
+    /// Where `--layer`/`SPENV_LAYERS` entries are composed relative
+    /// to the discovered layers. Defaults to the `SPENV_LAYERS_MODE`
+    /// environment variable, or `after`
+    #[clap(long, global = true, value_enum)]
+    pub layers_mode: Option<spk_env::LayerInsertMode>,
+}
+
+impl DiscoveryArgs {
+    /// The directory to start discovery from. If `--path` points
+    /// directly at a file rather than a directory, its parent is used
+    /// instead, so that `-f ./foo/.spenv.yaml` starts discovery in
+    /// `./foo` rather than treating `.spenv.yaml` itself as a directory.
+    pub fn start_dir(&self) -> Result<PathBuf> {
+        match &self.path {
+            Some(path) if path.is_file() => Ok(path
+                .parent()
+                .map(Path::to_owned)
+                .unwrap_or_else(|| PathBuf::from("."))),
+            Some(path) => Ok(path.clone()),
+            None => std::env::current_dir().into_diagnostic(),
+        }
+    }
+
+    /// The effective discovery options, falling back to `SPENV_MAX_DEPTH`,
+    /// `SPENV_STOP_AT` and `SPENV_FILENAME` for any flag that was not
+    /// given explicitly. If `--path` points directly at a file, that
+    /// file's own name is used as the filename to look for, taking
+    /// precedence over `--filename` and `SPENV_FILENAME`.
+    pub fn options(&self) -> DiscoveryOptions {
+        let from_env = DiscoveryOptions::from_env();
+        let file_filename = self
+            .path
+            .as_ref()
+            .filter(|path| path.is_file())
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned());
+        DiscoveryOptions {
+            max_depth: self.max_depth.or(from_env.max_depth),
+            root_markers: if self.stop_at.is_empty() {
+                from_env.root_markers
+            } else {
+                self.stop_at.clone()
+            },
+            filename: file_filename
+                .or_else(|| self.filename.clone())
+                .unwrap_or(from_env.filename),
+            system_defaults: self.system_defaults || from_env.system_defaults,
+            system_default_path: self
+                .system_default_path
+                .clone()
+                .unwrap_or(from_env.system_default_path),
+            trusted_only: self.trusted_only || from_env.trusted_only,
+        }
+    }
+
+    /// Compose the `--overlay` spec, if one was given, onto `env` as
+    /// its final, highest-precedence layer.
+    pub fn apply_overlay(
+        &self,
+        env: spk_env::ComposedEnvironment,
+    ) -> Result<spk_env::ComposedEnvironment> {
+        let Some(name) = &self.overlay else {
+            return Ok(env);
+        };
+        let overlays_dir = spk_env::default_overlays_dir().into_diagnostic()?;
+        let overlay = spk_env::resolve_overlay(&overlays_dir, name).into_diagnostic()?;
+        Ok(env.with_overlay(overlay))
+    }
+
+    /// Resolve `--layer`/`SPENV_LAYERS` into overlay specs and compose
+    /// them onto `env` at the position `--layers-mode`/`SPENV_LAYERS_MODE`
+    /// selects.
+    pub fn apply_layers(
+        &self,
+        env: spk_env::ComposedEnvironment,
+    ) -> Result<spk_env::ComposedEnvironment> {
+        let names = if self.layers.is_empty() {
+            spk_env::layer_names_from_env()
+        } else {
+            self.layers.clone()
+        };
+        if names.is_empty() {
+            return Ok(env);
+        }
+        let overlays_dir = spk_env::default_overlays_dir().into_diagnostic()?;
+        let layers = names
+            .iter()
+            .map(|name| spk_env::resolve_overlay(&overlays_dir, name).into_diagnostic())
+            .collect::<Result<Vec<_>>>()?;
+        let mode = self
+            .layers_mode
+            .unwrap_or_else(spk_env::layers_mode_from_env);
+        Ok(match mode {
+            spk_env::LayerInsertMode::Before => env.with_layers_before(layers),
+            spk_env::LayerInsertMode::After => env.with_layers_after(layers),
+        })
+    }
+}
+
+/// Compose and work with scripting environments described by `.spenv.yaml`
+#[derive(Debug, Parser)]
+#[command(name = "spenv")]
+#[command(author, version, about, long_about = None)]
+pub struct Opt {
+    /// How to render a failing command's error to stderr
+    #[clap(long, global = true, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format: ErrorFormat,
+
+    #[clap(subcommand)]
+    pub cmd: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Print the resolved, composed environment
+    Show(Show),
+    /// Upgrade a lock file to the current schema
+    MigrateLock(MigrateLock),
+    /// Run a command inside the composed environment
+    Load(Load),
+    /// Start an interactive shell inside the composed environment
+    Shell(Shell),
+    /// Check a composed environment against its lock file for drift
+    Check(Check),
+    /// Print a startup script that applies the composed environment
+    Env(Env),
+    /// Compare two composed environments and report how they differ
+    Diff(Diff),
+    /// Lint a composed environment for common spec authoring mistakes
+    Validate(Validate),
+    /// Print the composed environment flattened into a single spec document
+    Cat(Cat),
+    /// Export the composed environment as a portable manifest
+    Export(Export),
+    /// Scaffold a new `.spenv.yaml`, optionally from an existing environment
+    Init(Init),
+    /// Delete expired entries from a solution cache directory
+    PruneCache(PruneCache),
+    /// Remove includes from a spec that are proven fully redundant after composition
+    PruneIncludes(PruneIncludes),
+    /// Check that every declared include resolves to a real file, without composing
+    VerifyIncludes(VerifyIncludes),
+    /// Print the JSON Schema for `.spenv.yaml` spec files to stdout
+    Schema(Schema),
+    /// Print a shell completion script for `spenv` to stdout
+    Completions(Completions),
+}
+
+impl Opt {
+    /// Run the selected subcommand.
+    pub fn run(self) -> Result<i32> {
+        match self.cmd {
+            Command::Show(cmd) => cmd.run(),
+            Command::MigrateLock(cmd) => cmd.run(),
+            Command::Load(cmd) => cmd.run(),
+            Command::Shell(cmd) => cmd.run(),
+            Command::Check(cmd) => cmd.run(),
+            Command::Env(cmd) => cmd.run(),
+            Command::Diff(cmd) => cmd.run(),
+            Command::Validate(cmd) => cmd.run(),
+            Command::Cat(cmd) => cmd.run(),
+            Command::Export(cmd) => cmd.run(),
+            Command::Init(cmd) => cmd.run(),
+            Command::PruneCache(cmd) => cmd.run(),
+            Command::PruneIncludes(cmd) => cmd.run(),
+            Command::VerifyIncludes(cmd) => cmd.run(),
+            Command::Schema(cmd) => cmd.run(),
+            Command::Completions(cmd) => cmd.run(),
+        }
+    }
+}
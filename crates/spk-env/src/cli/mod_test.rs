@@ -0,0 +1,71 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn discovery(path: std::path::PathBuf) -> DiscoveryArgs {
+    DiscoveryArgs {
+        path: Some(path),
+        max_depth: None,
+        stop_at: Vec::new(),
+        filename: None,
+        overlay: None,
+        system_defaults: false,
+        system_default_path: None,
+        trusted_only: false,
+        layers: Vec::new(),
+        layers_mode: None,
+    }
+}
+
+#[rstest]
+fn test_start_dir_passes_through_a_directory_path(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path().to_owned();
+    let args = discovery(dir.clone());
+
+    assert_eq!(args.start_dir().unwrap(), dir);
+}
+
+#[rstest]
+fn test_start_dir_uses_the_parent_of_a_file_path(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let spec_path = dir.join("foo.spenv.yaml");
+    std::fs::write(&spec_path, "priority: 1\n").unwrap();
+    let args = discovery(spec_path);
+
+    assert_eq!(args.start_dir().unwrap(), dir);
+}
+
+#[rstest]
+fn test_options_uses_a_file_paths_own_name_as_the_filename(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let spec_path = dir.join("foo.spenv.yaml");
+    std::fs::write(&spec_path, "priority: 1\n").unwrap();
+    let args = discovery(spec_path);
+
+    assert_eq!(args.options().filename, "foo.spenv.yaml");
+}
+
+#[rstest]
+fn test_file_path_filename_overrides_an_explicit_filename_flag(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let spec_path = dir.join("foo.spenv.yaml");
+    std::fs::write(&spec_path, "priority: 1\n").unwrap();
+    let args = DiscoveryArgs {
+        filename: Some("other.yaml".to_string()),
+        ..discovery(spec_path)
+    };
+
+    assert_eq!(args.options().filename, "foo.spenv.yaml");
+}
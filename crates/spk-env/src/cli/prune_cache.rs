@@ -0,0 +1,38 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::SolutionCache;
+
+/// Delete expired entries from a solution cache directory
+#[derive(Debug, Args)]
+pub struct PruneCache {
+    /// The cache directory to prune, defaults to the `spenv/solutions`
+    /// subdirectory of the platform cache directory
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// The age, in seconds, after which an entry is considered
+    /// expired. Must match the TTL the cache was populated with, or
+    /// entries will be pruned too early or too late
+    #[clap(long, default_value_t = 86400)]
+    ttl_seconds: u64,
+}
+
+impl PruneCache {
+    pub fn run(self) -> Result<i32> {
+        let cache_dir = match self.cache_dir {
+            Some(dir) => dir,
+            None => SolutionCache::default_dir().into_diagnostic()?,
+        };
+        let cache = SolutionCache::new(cache_dir, Duration::from_secs(self.ttl_seconds));
+        let pruned = cache.prune().into_diagnostic()?;
+        println!("pruned {pruned} expired cache entries");
+        Ok(0)
+    }
+}
@@ -0,0 +1,57 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::EnvSpec;
+
+/// Remove includes from a spec that are proven fully redundant after composition
+#[derive(Debug, Args)]
+pub struct PruneIncludes {
+    /// The spec file to prune, defaults to `.spenv.yaml` in the current directory
+    #[clap(long)]
+    spec_file: Option<PathBuf>,
+
+    /// Rewrite the spec file in place, removing the dead includes.
+    /// Without this, pruning is only reported, not applied
+    #[clap(long)]
+    force: bool,
+}
+
+impl PruneIncludes {
+    pub fn run(self) -> Result<i32> {
+        let spec_file = self
+            .spec_file
+            .unwrap_or_else(|| PathBuf::from(EnvSpec::FILE_NAME));
+
+        let dead = spk_env::redundant_includes(&spec_file).into_diagnostic()?;
+        if dead.is_empty() {
+            println!("no redundant includes found");
+            return Ok(0);
+        }
+
+        for path in &dead {
+            println!("redundant include: {}", path.display());
+        }
+
+        if !self.force {
+            println!(
+                "re-run with --force to remove {} redundant include(s) from {}",
+                dead.len(),
+                spec_file.display()
+            );
+            return Ok(1);
+        }
+
+        spk_env::remove_includes(&spec_file, &dead).into_diagnostic()?;
+        println!(
+            "removed {} redundant include(s) from {}",
+            dead.len(),
+            spec_file.display()
+        );
+        Ok(0)
+    }
+}
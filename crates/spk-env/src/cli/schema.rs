@@ -0,0 +1,22 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+
+/// Print the JSON Schema for `.spenv.yaml` spec files to stdout, for
+/// editor integrations (e.g. a YAML language server) that want
+/// autocompletion and inline validation
+#[derive(Debug, Args)]
+pub struct Schema {}
+
+impl Schema {
+    pub fn run(self) -> Result<i32> {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&spk_env::spec_json_schema()).into_diagnostic()?
+        );
+        Ok(0)
+    }
+}
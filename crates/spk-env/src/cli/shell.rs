@@ -0,0 +1,156 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use miette::{Context, IntoDiagnostic, Result};
+use spk_env::runtime::{resolve_cwd, resolve_env_vars_with_lock};
+use spk_env::{ComposedEnvironment, EnvLock};
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "shell_test.rs"]
+mod shell_test;
+
+/// Start an interactive shell inside the composed environment
+#[derive(Debug, Args)]
+pub struct Shell {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// The directory to start the shell in, instead of inheriting
+    /// the invocation's current directory
+    #[clap(long)]
+    cwd: Option<PathBuf>,
+
+    /// Skip applying the composed environment's ops (set/prepend/
+    /// append/default/unset/source), so the shell starts with only
+    /// the ambient environment, unmodified by any spec. Useful when
+    /// debugging whether a problem comes from spenv's own op
+    /// resolution or from somewhere else
+    #[clap(long)]
+    no_startup: bool,
+
+    /// After the shell's own normal startup files have run, also
+    /// source this file. Supported for bash and zsh, which each get
+    /// their own way of layering an extra rc on top of the user's
+    /// real one; ignored (with a warning) for shells that have no
+    /// equivalent mechanism
+    #[clap(long)]
+    rcfile: Option<PathBuf>,
+}
+
+impl Shell {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_layers(env)?;
+        let env = self.discovery.apply_overlay(env)?;
+        let lock = EnvLock::load_file(EnvLock::FILE_NAME).ok();
+        let vars = if self.no_startup {
+            HashMap::new()
+        } else {
+            resolve_env_vars_with_lock(&env, lock.as_ref())
+        };
+        let cwd = resolve_cwd(self.cwd.as_deref()).into_diagnostic()?;
+
+        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string());
+        let shell_name = Path::new(&shell)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(shell.as_str());
+
+        let mut command = std::process::Command::new(&shell);
+        let _rcfile_guard = match (&self.rcfile, shell_name) {
+            (Some(rcfile), "bash") => Some(RcFileGuard::Bash(bash_rcfile(&mut command, rcfile)?)),
+            (Some(rcfile), "zsh") => Some(RcFileGuard::Zsh(zsh_rcfile(&mut command, rcfile)?)),
+            (Some(_), other) => {
+                eprintln!("spenv: --rcfile is not supported for {other}, ignoring");
+                None
+            }
+            (None, _) => None,
+        };
+        command.arg("-i").envs(vars);
+        if let Some(cwd) = &cwd {
+            command.current_dir(cwd);
+        }
+        let status = command
+            .status()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to start interactive shell {shell:?}"))?;
+
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// Holds whatever temporary file or directory a `--rcfile` wrapper
+/// created, so it stays alive until after the shell has been spawned.
+/// Never read, only kept around for its `Drop` impl
+#[allow(dead_code)]
+enum RcFileGuard {
+    Bash(tempfile::NamedTempFile),
+    Zsh(tempfile::TempDir),
+}
+
+/// Arrange for bash to source `rcfile` after its normal startup, by
+/// generating a temporary rc that sources both `~/.bashrc` and
+/// `rcfile`, and pointing bash at it via its native `--rcfile`
+fn bash_rcfile(
+    command: &mut std::process::Command,
+    rcfile: &Path,
+) -> Result<tempfile::NamedTempFile> {
+    let wrapper = tempfile::Builder::new()
+        .prefix("spenv-rcfile-")
+        .tempfile()
+        .into_diagnostic()
+        .wrap_err("failed to create a temporary rcfile for bash")?;
+    std::fs::write(
+        wrapper.path(),
+        format!(
+            "[ -f ~/.bashrc ] && . ~/.bashrc\n. {}\n",
+            shell_quote(&rcfile.display().to_string())
+        ),
+    )
+    .into_diagnostic()
+    .wrap_err("failed to write the temporary bash rcfile")?;
+    command.arg("--rcfile").arg(wrapper.path());
+    Ok(wrapper)
+}
+
+/// Arrange for zsh to source `rcfile` after its normal startup. zsh
+/// has no `--rcfile` flag; instead this points `ZDOTDIR` at a
+/// temporary directory whose `.zshrc` sources the real one (from the
+/// original `ZDOTDIR`, or `$HOME`) followed by `rcfile`
+fn zsh_rcfile(command: &mut std::process::Command, rcfile: &Path) -> Result<tempfile::TempDir> {
+    let real_zdotdir = std::env::var("ZDOTDIR")
+        .or_else(|_| std::env::var("HOME"))
+        .unwrap_or_default();
+    let dir = tempfile::Builder::new()
+        .prefix("spenv-rcfile-")
+        .tempdir()
+        .into_diagnostic()
+        .wrap_err("failed to create a temporary ZDOTDIR for zsh")?;
+    std::fs::write(
+        dir.path().join(".zshrc"),
+        format!(
+            "[ -f {real}/.zshrc ] && . {real}/.zshrc\n. {extra}\n",
+            real = shell_quote(&real_zdotdir),
+            extra = shell_quote(&rcfile.display().to_string()),
+        ),
+    )
+    .into_diagnostic()
+    .wrap_err("failed to write the temporary zsh rcfile")?;
+    command.env("ZDOTDIR", dir.path());
+    Ok(dir)
+}
+
+/// Quote `value` as a single POSIX shell word, safe to embed in a
+/// generated rc file regardless of its contents
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
@@ -0,0 +1,57 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::rstest;
+
+use super::{bash_rcfile, shell_quote};
+
+#[rstest]
+fn test_shell_quote_escapes_an_embedded_single_quote() {
+    assert_eq!(shell_quote("it's"), r"'it'\''s'");
+}
+
+#[rstest]
+fn test_bash_rcfile_sources_the_extra_file_after_the_default_rc() {
+    let tmpdir = tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files");
+    let extra = tmpdir.path().join("extra.sh");
+    std::fs::write(&extra, "export SPENV_TEST_EXTRA_RC=hit\n").unwrap();
+
+    let mut command = std::process::Command::new("bash");
+    command.env("HOME", tmpdir.path());
+    let _wrapper = bash_rcfile(&mut command, &extra).unwrap();
+    command
+        .arg("-i")
+        .arg("-c")
+        .arg("echo \"$SPENV_TEST_EXTRA_RC\"");
+
+    let output = command.output().expect("spawn bash");
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "hit");
+}
+
+#[rstest]
+fn test_bash_rcfile_tolerates_a_missing_bashrc() {
+    let tmpdir = tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files");
+    let extra = tmpdir.path().join("extra.sh");
+    std::fs::write(&extra, "export SPENV_TEST_EXTRA_RC=hit\n").unwrap();
+
+    let mut command = std::process::Command::new("bash");
+    // No ~/.bashrc exists under this HOME; the wrapper must still run.
+    command.env("HOME", tmpdir.path().join("no-such-home"));
+    let _wrapper = bash_rcfile(&mut command, &extra).unwrap();
+    command
+        .arg("-i")
+        .arg("-c")
+        .arg("echo \"$SPENV_TEST_EXTRA_RC\"");
+
+    let output = command.output().expect("spawn bash");
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "hit");
+}
@@ -0,0 +1,532 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use serde::Serialize;
+use spk_env::{ComposedEnvironment, CountDimension, EnvStats, LayerSource};
+
+use super::DiscoveryArgs;
+use super::diff::format_op;
+
+/// How a `spenv show` result should be printed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+enum ShowFormat {
+    /// One layer's file path per line
+    #[default]
+    Text,
+    /// A single JSON object describing every layer
+    Json,
+    /// One JSON object per line: a file record for every discovered
+    /// file, followed by a layer record for every composed layer. Every
+    /// line is rendered before the first is printed; output is not
+    /// streamed as each record is produced
+    Ndjson,
+    /// Graphviz DOT, for `--graph`. Piping into `dot -Tpng` or similar
+    /// renders the include/inherit tree as an image
+    Dot,
+}
+
+/// A single `spenv show --format ndjson` line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ShowNdjsonLine<'a> {
+    File {
+        path: &'a str,
+    },
+    Layer {
+        path: &'a str,
+        note: Option<&'a str>,
+        /// The layer's content digest, `None` unless `--resolve` was given
+        digest: Option<&'a str>,
+    },
+    Platform {
+        key: &'a str,
+    },
+}
+
+/// The machine-readable rendering of a composed environment, for
+/// `spenv show --format json`.
+#[derive(Debug, Serialize)]
+struct ShowJson {
+    /// Every layer's spec file path, in composition order
+    discovered_files: Vec<String>,
+    /// Every layer's spec file path, in composition order
+    layers: Vec<String>,
+    /// The effective environment variable operations, in the order
+    /// they apply
+    environment: Vec<String>,
+    /// Each layer's raw spec file contents, in the same order as `layers`
+    contents: Vec<String>,
+    /// Each layer's note, in the same order as `layers`, `null` if it
+    /// didn't set one
+    notes: Vec<Option<String>>,
+    /// The number of distinct spec files contributing to the environment
+    total_files: usize,
+    /// The number of layers composed into the environment
+    total_layers: usize,
+    /// Every `platform` key that matched the current host, sorted and deduplicated
+    active_platform_keys: Vec<String>,
+    /// Each layer's content digest, in the same order as `layers`,
+    /// `None` unless `--resolve` was given
+    digests: Option<Vec<String>>,
+}
+
+/// Print the resolved, composed environment
+#[derive(Debug, Args)]
+pub struct Show {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// Additional start paths to discover and compose independently,
+    /// each printed as its own labeled section. May be given more
+    /// than once; with none given, only `--path` (or the current
+    /// directory) is shown, with no labeling.
+    #[clap(value_name = "PATH")]
+    paths: Vec<PathBuf>,
+
+    /// When more than one start path is given, keep processing the
+    /// rest after one fails instead of aborting immediately
+    #[clap(long)]
+    keep_going: bool,
+
+    /// Instead of printing the environment, print a single integer count
+    /// for the given dimension (layers, unique-layers, files, env-ops, binds, packages)
+    #[clap(long, value_name = "DIMENSION")]
+    count_only: Option<String>,
+
+    /// Validate each layer's spec file against the generated JSON
+    /// Schema, reporting any structural violations instead of
+    /// printing the environment
+    #[clap(long)]
+    json_schema_validate: bool,
+
+    /// How to print the resolved environment
+    #[clap(long, value_enum, default_value_t = ShowFormat::Text)]
+    format: ShowFormat,
+
+    /// Increase diagnostic verbosity. May be repeated; at `-v`, layers
+    /// reachable through both discovery and an include are flagged as
+    /// redundant; at `-vv`, the directories visited during discovery
+    /// and why the walk stopped ascending are also printed
+    #[clap(short = 'v', long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Time each `includes` load and print a breakdown to stderr,
+    /// slowest first, for diagnosing slow discovery
+    #[clap(long)]
+    profile_includes: bool,
+
+    /// Alongside each layer, print a content digest of its spec file
+    /// (the same hash a lock file records), so a reference can
+    /// be audited against what it currently resolves to. Computing
+    /// these is lazy: without this flag, `show` never reads a
+    /// layer's file contents just to hash them
+    #[clap(long)]
+    resolve: bool,
+
+    /// Instead of the flat layer list, print the include/inherit tree:
+    /// the discovery root, each in-tree parent reached via
+    /// `inherit: true`, and the include edges declared by `includes`.
+    /// Combine with `--format dot` to emit Graphviz DOT instead of an
+    /// indented text tree
+    #[clap(long)]
+    graph: bool,
+}
+
+#[cfg(test)]
+#[path = "show_test.rs"]
+mod show_test;
+
+impl Show {
+    pub fn run(self) -> Result<i32> {
+        let labeled = !self.paths.is_empty();
+        let start_paths = self.start_paths()?;
+
+        let mut worst_code = 0;
+        for start in &start_paths {
+            if labeled {
+                println!("== {} ==", start.display());
+            }
+            match self.show_one(start) {
+                Ok(code) => worst_code = worst_code.max(code),
+                Err(err) if labeled && self.keep_going => {
+                    eprintln!("{err}");
+                    worst_code = 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(worst_code)
+    }
+
+    /// The start paths to discover and compose, independently, one
+    /// per labeled section. Falls back to the single `--path` start
+    /// directory when no positional paths were given.
+    fn start_paths(&self) -> Result<Vec<PathBuf>> {
+        if self.paths.is_empty() {
+            Ok(vec![self.discovery.start_dir()?])
+        } else {
+            Ok(self.paths.clone())
+        }
+    }
+
+    /// Run the full show pipeline for a single start path.
+    fn show_one(&self, start: &std::path::Path) -> Result<i32> {
+        let options = self.discovery.options();
+
+        if self.verbose >= 2 {
+            let (_, trace) =
+                spk_env::discover_specs_traced(start, options.clone()).into_diagnostic()?;
+            println!("{trace}");
+        }
+
+        let env = if self.profile_includes {
+            let (env, timings) =
+                ComposedEnvironment::resolve_with_profiling(start, options).into_diagnostic()?;
+            print_include_timings(sorted_by_duration_desc(timings));
+            env
+        } else {
+            ComposedEnvironment::resolve_with_options(start, options).into_diagnostic()?
+        };
+        let env = self.discovery.apply_layers(env)?;
+        let env = self.discovery.apply_overlay(env)?;
+
+        if self.verbose >= 1 {
+            for path in env.cross_mechanism_duplicates() {
+                println!(
+                    "warning: {} is reachable through both discovery and an include",
+                    path.display()
+                );
+            }
+        }
+
+        for conflict in env.conflicts() {
+            println!(
+                "warning: {} is set to conflicting values across layers: {}",
+                conflict.name,
+                conflict.values.join(", ")
+            );
+        }
+
+        if self.json_schema_validate {
+            let mut violation_count = 0;
+            for layer in &env.layers {
+                let contents = std::fs::read_to_string(&layer.file_path).into_diagnostic()?;
+                let violations = spk_env::validate_spec_yaml(&contents).into_diagnostic()?;
+                for violation in violations {
+                    println!("{}: {violation}", layer.file_path.display());
+                    violation_count += 1;
+                }
+            }
+            if violation_count == 0 {
+                println!("no schema violations found");
+                return Ok(0);
+            }
+            return Ok(1);
+        }
+
+        if let Some(dimension) = &self.count_only {
+            let dimension: CountDimension = dimension.parse().into_diagnostic()?;
+            let stats = EnvStats::compute(&env);
+            println!("{}", stats.get(dimension));
+            return Ok(0);
+        }
+
+        if self.graph {
+            if self.format == ShowFormat::Dot {
+                println!("{}", render_graph_dot(&env));
+            } else {
+                print!("{}", render_graph_tree(&env));
+            }
+            return Ok(0);
+        }
+
+        if self.format == ShowFormat::Json {
+            println!("{}", render_json(&env, self.resolve)?);
+            return Ok(0);
+        }
+
+        if self.format == ShowFormat::Ndjson {
+            for line in render_ndjson(&env, self.resolve)? {
+                println!("{line}");
+            }
+            return Ok(0);
+        }
+
+        let mut digests = HashMap::new();
+        for layer in &env.layers {
+            let path = if self.resolve {
+                format!(
+                    "{} -> {}",
+                    layer.file_path.display(),
+                    cached_digest_of(&mut digests, &layer.file_path)?
+                )
+            } else {
+                layer.file_path.display().to_string()
+            };
+            match &layer.note {
+                Some(note) => println!("{path} ({note})"),
+                None => println!("{path}"),
+            }
+        }
+        let active_platform_keys = env.active_platform_keys();
+        if !active_platform_keys.is_empty() {
+            println!("active platform keys: {}", active_platform_keys.join(", "));
+        }
+        Ok(0)
+    }
+}
+
+/// Render `env` as a pretty-printed [`ShowJson`] document. `resolve`
+/// controls whether each layer's content digest is computed and
+/// included, since hashing every layer's file is wasted work unless
+/// `--resolve` was actually asked for.
+fn render_json(env: &ComposedEnvironment, resolve: bool) -> Result<String> {
+    let files: Vec<String> = env
+        .layers
+        .iter()
+        .map(|l| l.file_path.display().to_string())
+        .collect();
+    let contents = env
+        .layers
+        .iter()
+        .map(|l| std::fs::read_to_string(&l.file_path).into_diagnostic())
+        .collect::<Result<Vec<String>>>()?;
+    let digests = if resolve {
+        Some(contents.iter().map(|c| spk_env::hash_contents(c)).collect())
+    } else {
+        None
+    };
+    let show_json = ShowJson {
+        discovered_files: files.clone(),
+        layers: files,
+        environment: env.effective_ops().iter().map(format_op).collect(),
+        contents,
+        notes: env.layers.iter().map(|l| l.note.clone()).collect(),
+        total_files: EnvStats::compute(env).unique_layers,
+        total_layers: env.layers.len(),
+        active_platform_keys: env.active_platform_keys(),
+        digests,
+    };
+    Ok(serde_json::to_string_pretty(&show_json)
+        .expect("serializing the resolved environment to JSON should not fail"))
+}
+
+/// Render `env` as newline-delimited JSON, one file record and one
+/// layer record per line, so each line can be independently parsed.
+/// All lines are returned together once rendered. `resolve` controls whether
+/// each layer record carries a content digest.
+fn render_ndjson(env: &ComposedEnvironment, resolve: bool) -> Result<Vec<String>> {
+    let mut lines = Vec::with_capacity(env.layers.len() * 2);
+    for layer in &env.layers {
+        let path = layer.file_path.display().to_string();
+        lines.push(
+            serde_json::to_string(&ShowNdjsonLine::File { path: &path })
+                .expect("serializing a file record to JSON should not fail"),
+        );
+    }
+    // A layer reachable through more than one inherit/include edge
+    // appears more than once in `env.layers`, possibly under
+    // differently-spelled `file_path`s; cache each file's digest so
+    // it's hashed at most once per invocation instead of once per
+    // occurrence.
+    let mut digests = HashMap::new();
+    for layer in &env.layers {
+        let path = layer.file_path.display().to_string();
+        let digest = if resolve {
+            Some(cached_digest_of(&mut digests, &layer.file_path)?)
+        } else {
+            None
+        };
+        lines.push(
+            serde_json::to_string(&ShowNdjsonLine::Layer {
+                path: &path,
+                note: layer.note.as_deref(),
+                digest,
+            })
+            .expect("serializing a layer record to JSON should not fail"),
+        );
+    }
+    for key in env.active_platform_keys() {
+        lines.push(
+            serde_json::to_string(&ShowNdjsonLine::Platform { key: &key })
+                .expect("serializing a platform record to JSON should not fail"),
+        );
+    }
+    Ok(lines)
+}
+
+/// How one layer came to be part of the graph printed by
+/// `spenv show --graph`: either inherited from the parent directory
+/// that contributed the previous layer in the discovery chain, or
+/// pulled in by another layer's `includes`. A layer loaded as the
+/// system default, or as the first discovered layer in its chain, has
+/// no incoming edge and is a root of the graph.
+enum GraphEdgeKind {
+    Inherit,
+    Include,
+}
+
+/// One edge in the include/inherit graph: `from` contributed `to` the
+/// way `kind` describes.
+struct GraphEdge {
+    from: PathBuf,
+    to: PathBuf,
+    kind: GraphEdgeKind,
+}
+
+/// The include/inherit edges for `env`, built from its layers and
+/// [`ComposedEnvironment::provenance`]. A layer reachable through more
+/// than one mechanism contributes more than one edge.
+fn graph_edges(env: &ComposedEnvironment) -> Vec<GraphEdge> {
+    let mut edges = Vec::new();
+    let mut last_discovered: Option<PathBuf> = None;
+    for layer in &env.layers {
+        let canonical = layer
+            .file_path
+            .canonicalize()
+            .unwrap_or_else(|_| layer.file_path.clone());
+        let Some(sources) = env.provenance.get(&canonical) else {
+            continue;
+        };
+        for source in sources {
+            match source {
+                LayerSource::Discovered => {
+                    if let Some(parent) = &last_discovered {
+                        edges.push(GraphEdge {
+                            from: parent.clone(),
+                            to: layer.file_path.clone(),
+                            kind: GraphEdgeKind::Inherit,
+                        });
+                    }
+                    last_discovered = Some(layer.file_path.clone());
+                }
+                LayerSource::Included { from } => {
+                    edges.push(GraphEdge {
+                        from: from.clone(),
+                        to: layer.file_path.clone(),
+                        kind: GraphEdgeKind::Include,
+                    });
+                }
+                LayerSource::SystemDefault => {}
+            }
+        }
+    }
+    edges
+}
+
+/// Render `env`'s include/inherit graph as an indented text tree, one
+/// root per line with its descendants nested beneath it. A layer with
+/// no incoming edge (a discovery root, the system default, or an
+/// `inherit: false` spec) is printed as its own root.
+fn render_graph_tree(env: &ComposedEnvironment) -> String {
+    let edges = graph_edges(env);
+    let mut children: HashMap<&PathBuf, Vec<&GraphEdge>> = HashMap::new();
+    let mut has_parent: std::collections::HashSet<&PathBuf> = std::collections::HashSet::new();
+    for edge in &edges {
+        children.entry(&edge.from).or_default().push(edge);
+        has_parent.insert(&edge.to);
+    }
+
+    let mut out = String::new();
+    for layer in &env.layers {
+        if has_parent.contains(&layer.file_path) {
+            continue;
+        }
+        write_graph_tree_node(&mut out, &layer.file_path, &children, 0);
+    }
+    out
+}
+
+fn write_graph_tree_node(
+    out: &mut String,
+    path: &PathBuf,
+    children: &HashMap<&PathBuf, Vec<&GraphEdge>>,
+    depth: usize,
+) {
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(&path.display().to_string());
+    out.push('\n');
+    for edge in children.get(path).into_iter().flatten() {
+        let label = match edge.kind {
+            GraphEdgeKind::Inherit => "inherits",
+            GraphEdgeKind::Include => "includes",
+        };
+        out.push_str(&"  ".repeat(depth + 1));
+        out.push_str(label);
+        out.push('\n');
+        write_graph_tree_node(out, &edge.to, children, depth + 2);
+    }
+}
+
+/// Render `env`'s include/inherit graph as Graphviz DOT, pipeable into
+/// `dot -Tpng` or similar.
+fn render_graph_dot(env: &ComposedEnvironment) -> String {
+    let mut out = String::from("digraph spenv {\n");
+    for layer in &env.layers {
+        out.push_str(&format!("  {:?};\n", layer.file_path.display().to_string()));
+    }
+    for edge in graph_edges(env) {
+        let label = match edge.kind {
+            GraphEdgeKind::Inherit => "inherits",
+            GraphEdgeKind::Include => "includes",
+        };
+        out.push_str(&format!(
+            "  {:?} -> {:?} [label={label:?}];\n",
+            edge.from.display().to_string(),
+            edge.to.display().to_string()
+        ));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The content digest of the spec file at `path`, the same hash
+/// [`spk_env::EnvLock`] records for drift detection.
+fn digest_of(path: &std::path::Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path).into_diagnostic()?;
+    Ok(spk_env::hash_contents(&contents))
+}
+
+/// `digest_of`, but memoized in `cache` for the lifetime of one
+/// `spenv show` invocation. A layer reachable through more than one
+/// inherit/include edge is listed once per edge, each possibly
+/// spelled differently (e.g. `a/.spenv.yaml` vs `a/../.spenv.yaml`),
+/// so the cache key is the canonicalized path; without this, the same
+/// file would still be read and hashed once per occurrence
+fn cached_digest_of<'a>(
+    cache: &'a mut HashMap<PathBuf, String>,
+    path: &std::path::Path,
+) -> Result<&'a str> {
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !cache.contains_key(&key) {
+        cache.insert(key.clone(), digest_of(path)?);
+    }
+    Ok(cache.get(&key).expect("just inserted above").as_str())
+}
+
+/// `timings`, slowest include first.
+fn sorted_by_duration_desc(
+    mut timings: Vec<spk_env::IncludeTiming>,
+) -> Vec<spk_env::IncludeTiming> {
+    timings.sort_by_key(|t| std::cmp::Reverse(t.duration));
+    timings
+}
+
+/// Print `timings` to stderr, one line per include, in the order given.
+fn print_include_timings(timings: Vec<spk_env::IncludeTiming>) {
+    for timing in &timings {
+        eprintln!(
+            "{:>8.2?}  {} (included by {})",
+            timing.duration,
+            timing.path.display(),
+            timing.from.display()
+        );
+    }
+}
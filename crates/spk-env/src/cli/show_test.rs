@@ -0,0 +1,289 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use rstest::{fixture, rstest};
+
+use super::{
+    Show, ShowFormat, render_graph_dot, render_graph_tree, render_json, render_ndjson,
+    sorted_by_duration_desc,
+};
+use crate::cli::DiscoveryArgs;
+use spk_env::{ComposedEnvironment, IncludeTiming};
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn show(paths: Vec<PathBuf>) -> Show {
+    Show {
+        discovery: DiscoveryArgs {
+            path: None,
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        paths,
+        keep_going: false,
+        count_only: None,
+        json_schema_validate: false,
+        format: ShowFormat::Text,
+        verbose: 0,
+        profile_includes: false,
+        resolve: false,
+        graph: false,
+    }
+}
+
+#[test]
+fn test_two_start_paths_produce_two_labeled_sections() {
+    let show = show(vec![PathBuf::from("/one"), PathBuf::from("/two")]);
+
+    let start_paths = show.start_paths().unwrap();
+
+    assert_eq!(
+        start_paths,
+        vec![PathBuf::from("/one"), PathBuf::from("/two")]
+    );
+}
+
+#[test]
+fn test_no_positional_paths_falls_back_to_the_discovery_path() {
+    let show = show(Vec::new());
+
+    let start_paths = show.start_paths().unwrap();
+
+    assert_eq!(start_paths.len(), 1);
+}
+
+#[rstest]
+fn test_json_output_escapes_a_quote_in_a_layer_path(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let layer_dir = root.join("has\"quote");
+    std::fs::create_dir_all(&layer_dir).unwrap();
+    std::fs::write(
+        layer_dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(&layer_dir).unwrap();
+    let json = render_json(&env, false).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&json)
+        .expect("output containing a raw quote should still be valid JSON");
+    assert_eq!(
+        parsed["layers"][0],
+        layer_dir.join(".spenv.yaml").display().to_string()
+    );
+    assert_eq!(parsed["environment"][0], "set PATH=/bin");
+    assert_eq!(parsed["total_layers"], 1);
+}
+
+#[rstest]
+fn test_json_output_includes_a_layers_note(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "note: pinned for CVE fix\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let json = render_json(&env, false).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["notes"][0], "pinned for CVE fix");
+}
+
+#[rstest]
+fn test_json_output_reports_active_platform_keys(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        format!(
+            "platform:\n  {os}:\n    packages: [{os}-pkg]\n",
+            os = std::env::consts::OS
+        ),
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let json = render_json(&env, false).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed["active_platform_keys"][0], std::env::consts::OS);
+}
+
+#[rstest]
+fn test_ndjson_output_lines_each_parse_as_standalone_json(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("base.spenv.yaml"), "note: base layer\n").unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "includes: [base.spenv.yaml]\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let lines = render_ndjson(&env, false).unwrap();
+
+    assert_eq!(lines.len(), 4);
+    let parsed: Vec<serde_json::Value> = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).expect("each line should parse on its own"))
+        .collect();
+    assert_eq!(parsed[0]["kind"], "file");
+    assert_eq!(parsed[2]["kind"], "layer");
+    assert_eq!(parsed[2]["note"], "base layer");
+}
+
+#[rstest]
+fn test_resolve_flag_adds_a_content_digest_to_each_layer(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "note: pinned for CVE fix\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let json = render_json(&env, true).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let expected = spk_env::hash_contents("note: pinned for CVE fix\n");
+    assert_eq!(parsed["digests"][0], expected);
+}
+
+#[rstest]
+fn test_without_resolve_json_output_omits_digests(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "note: pinned for CVE fix\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let json = render_json(&env, false).unwrap();
+
+    let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert!(parsed["digests"].is_null());
+}
+
+#[rstest]
+fn test_resolving_a_layer_reachable_two_ways_reads_its_file_once(tmpdir: tempfile::TempDir) {
+    // `root/.spenv.yaml` is discovered directly as an ancestor of `a`
+    // via `inherit: true`, and is also pulled in by `a`'s `includes`,
+    // so it appears twice in `env.layers`, once per edge.
+    let root = tmpdir.path();
+    let root_spec = root.join(".spenv.yaml");
+    std::fs::write(&root_spec, "inherit: true\n").unwrap();
+    let a = root.join("a");
+    std::fs::create_dir(&a).unwrap();
+    std::fs::write(
+        a.join(".spenv.yaml"),
+        "inherit: true\nincludes: ['../.spenv.yaml']\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(&a).unwrap();
+    assert_eq!(env.cross_mechanism_duplicates().len(), 1);
+
+    let lines = render_ndjson(&env, true).unwrap();
+
+    // The two edges reach the same file via differently-spelled
+    // paths (`.spenv.yaml` directly, and `a/../.spenv.yaml` through
+    // the include), but both must still report the same digest.
+    let expected = spk_env::hash_contents("inherit: true\n");
+    let direct_path = root_spec.display().to_string();
+    let included_path = a.join("../.spenv.yaml").display().to_string();
+    let layer_digests: Vec<String> = lines
+        .iter()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|v| {
+            v["kind"] == "layer" && (v["path"] == direct_path || v["path"] == included_path)
+        })
+        .map(|v| v["digest"].as_str().unwrap().to_string())
+        .collect();
+
+    assert_eq!(layer_digests.len(), 2);
+    assert!(layer_digests.iter().all(|d| *d == expected));
+}
+
+#[rstest]
+fn test_resolve_flag_adds_a_digest_to_each_ndjson_layer_record(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "note: pinned for CVE fix\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let lines = render_ndjson(&env, true).unwrap();
+
+    let layer_line: serde_json::Value = lines
+        .iter()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .find(|v: &serde_json::Value| v["kind"] == "layer")
+        .unwrap();
+    let expected = spk_env::hash_contents("note: pinned for CVE fix\n");
+    assert_eq!(layer_line["digest"], expected);
+}
+
+#[rstest]
+fn test_graph_tree_nests_an_include_under_the_spec_that_declared_it(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("base.spenv.yaml"), "note: base layer\n").unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "includes: [base.spenv.yaml]\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let tree = render_graph_tree(&env);
+
+    let root_spec = root.join(".spenv.yaml").display().to_string();
+    let base_spec = root.join("base.spenv.yaml").display().to_string();
+    let root_line = tree.lines().position(|l| l == root_spec).unwrap();
+    let includes_line = tree.lines().position(|l| l.trim() == "includes").unwrap();
+    let base_line = tree.lines().position(|l| l.trim() == base_spec).unwrap();
+    assert!(root_line < includes_line);
+    assert!(includes_line < base_line);
+}
+
+#[rstest]
+fn test_graph_dot_emits_one_node_per_layer_and_one_edge_per_include(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("base.spenv.yaml"), "note: base layer\n").unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "includes: [base.spenv.yaml]\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let dot = render_graph_dot(&env);
+
+    assert!(dot.starts_with("digraph spenv {\n"));
+    assert!(dot.ends_with("}\n"));
+    let base_spec = root.join("base.spenv.yaml").display().to_string();
+    let root_spec = root.join(".spenv.yaml").display().to_string();
+    assert!(dot.contains(&format!("{root_spec:?} -> {base_spec:?}")));
+    assert!(dot.contains("label=\"includes\""));
+}
+
+#[rstest]
+fn test_graph_tree_treats_a_layer_with_no_incoming_edge_as_its_own_root(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "note: lone layer\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let tree = render_graph_tree(&env);
+
+    assert_eq!(tree.trim(), root.join(".spenv.yaml").display().to_string());
+}
+
+#[test]
+fn test_include_timings_are_sorted_slowest_first() {
+    let fast = IncludeTiming {
+        from: PathBuf::from(".spenv.yaml"),
+        path: PathBuf::from("fast.yaml"),
+        duration: std::time::Duration::from_millis(1),
+    };
+    let slow = IncludeTiming {
+        from: PathBuf::from(".spenv.yaml"),
+        path: PathBuf::from("slow.yaml"),
+        duration: std::time::Duration::from_millis(500),
+    };
+
+    let sorted = sorted_by_duration_desc(vec![fast.clone(), slow.clone()]);
+
+    assert_eq!(sorted, vec![slow, fast]);
+}
@@ -0,0 +1,54 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::ComposedEnvironment;
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "validate_test.rs"]
+mod validate_test;
+
+/// Lint a composed environment for common spec authoring mistakes
+#[derive(Debug, Args)]
+pub struct Validate {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+
+    /// Exit non-zero if any issue is found, including ones that are
+    /// normally reported as warnings
+    #[clap(long)]
+    warnings_as_errors: bool,
+}
+
+impl Validate {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let env = ComposedEnvironment::resolve_with_options(start, self.discovery.options())
+            .into_diagnostic()?;
+        let env = self.discovery.apply_overlay(env)?;
+
+        let issues = spk_env::validate(&env);
+        if issues.is_empty() {
+            println!("no issues found");
+            return Ok(0);
+        }
+
+        let mut failed = false;
+        for issue in &issues {
+            let label = if issue.is_warning() {
+                "warning"
+            } else {
+                "error"
+            };
+            println!("{label}: {issue}");
+            if !issue.is_warning() || self.warnings_as_errors {
+                failed = true;
+            }
+        }
+        Ok(i32::from(failed))
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::Validate;
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn validate(dir: &std::path::Path, warnings_as_errors: bool) -> Validate {
+    Validate {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+        warnings_as_errors,
+    }
+}
+
+#[rstest]
+fn test_a_clean_environment_exits_zero(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /spfs/bin\n",
+    )
+    .unwrap();
+
+    assert_eq!(validate(dir, false).run().unwrap(), 0);
+}
+
+#[rstest]
+fn test_an_error_level_issue_exits_non_zero(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(
+        dir.join(".spenv.yaml"),
+        "binds:\n  - source: /host/data\n    dest: /data\n",
+    )
+    .unwrap();
+
+    assert_eq!(validate(dir, false).run().unwrap(), 1);
+}
+
+#[rstest]
+fn test_a_warning_only_passes_unless_warnings_as_errors_is_set(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "pakages: [foo]\n").unwrap();
+
+    assert_eq!(validate(dir, false).run().unwrap(), 0);
+    assert_eq!(validate(dir, true).run().unwrap(), 1);
+}
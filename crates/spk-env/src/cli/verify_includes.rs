@@ -0,0 +1,51 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::{IntoDiagnostic, Result};
+use spk_env::IncludeStatus;
+
+use super::DiscoveryArgs;
+
+#[cfg(test)]
+#[path = "verify_includes_test.rs"]
+mod verify_includes_test;
+
+/// Check that every include declared by the discovered specs resolves
+/// to a real, loadable file, without composing the environment
+#[derive(Debug, Args)]
+pub struct VerifyIncludes {
+    #[clap(flatten)]
+    discovery: DiscoveryArgs,
+}
+
+impl VerifyIncludes {
+    pub fn run(self) -> Result<i32> {
+        let start = self.discovery.start_dir()?;
+        let checks = spk_env::verify_includes(start, self.discovery.options()).into_diagnostic()?;
+
+        if checks.is_empty() {
+            println!("no includes found");
+            return Ok(0);
+        }
+
+        let mut failed = false;
+        for check in &checks {
+            let label = match check.status {
+                IncludeStatus::Reachable => "ok",
+                IncludeStatus::Missing => "missing",
+                IncludeStatus::Circular => "circular",
+            };
+            println!(
+                "{label}: {} -> {}",
+                check.from.display(),
+                check.include.display()
+            );
+            if check.status != IncludeStatus::Reachable {
+                failed = true;
+            }
+        }
+        Ok(i32::from(failed))
+    }
+}
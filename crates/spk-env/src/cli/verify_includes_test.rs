@@ -0,0 +1,50 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::VerifyIncludes;
+use crate::cli::DiscoveryArgs;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+fn verify_includes(dir: &std::path::Path) -> VerifyIncludes {
+    VerifyIncludes {
+        discovery: DiscoveryArgs {
+            path: Some(dir.to_owned()),
+            max_depth: None,
+            stop_at: Vec::new(),
+            filename: None,
+            overlay: None,
+            system_defaults: false,
+            system_default_path: None,
+            trusted_only: false,
+            layers: Vec::new(),
+            layers_mode: None,
+        },
+    }
+}
+
+#[rstest]
+fn test_all_includes_reachable_exits_zero(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join("base.spenv.yaml"), "packages: [base-pkg]\n").unwrap();
+    std::fs::write(dir.join(".spenv.yaml"), "includes: [base.spenv.yaml]\n").unwrap();
+
+    assert_eq!(verify_includes(dir).run().unwrap(), 0);
+}
+
+#[rstest]
+fn test_a_missing_include_exits_non_zero(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    std::fs::write(dir.join(".spenv.yaml"), "includes: [missing.spenv.yaml]\n").unwrap();
+
+    assert_eq!(verify_includes(dir).run().unwrap(), 1);
+}
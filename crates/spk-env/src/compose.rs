@@ -0,0 +1,1193 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Composing multiple [`EnvSpec`] layers into a single environment.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::future::try_join_all;
+use tokio::runtime::Handle;
+
+use crate::discovery::{DiscoveryOptions, discover_in_tree, load_system_default};
+use crate::error::ComposeError;
+use crate::spec::{
+    ContentMount, EnvOp, EnvSpec, LayersMode, LockPolicy, matches_exclude,
+    matches_running_platform_key,
+};
+
+/// Template placeholders resolved elsewhere, at runtime, from a lock's
+/// metadata (see [`crate::runtime::resolve_env_vars_with_lock`]).
+/// Compose-time interpolation passes these through unchanged rather
+/// than treating them as unresolvable.
+const DEFERRED_PLACEHOLDERS: [&str; 2] = ["SPENV_FINGERPRINT", "SPENV_LOCKED_AT"];
+
+#[cfg(test)]
+#[path = "compose_test.rs"]
+mod compose_test;
+
+/// How a single composed layer was contributed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerSource {
+    /// Found by walking up the directory tree via `inherit: true`
+    Discovered,
+    /// Pulled in by another spec's `includes`
+    Included {
+        /// The spec that declared the include
+        from: PathBuf,
+    },
+    /// The machine-wide default spec, loaded because
+    /// [`DiscoveryOptions::system_defaults`] was set
+    SystemDefault,
+}
+
+/// A variable that two or more layers `set` or `default` to
+/// differing values, found by [`ComposedEnvironment::conflicts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvConflict {
+    /// The variable that is set to conflicting values
+    pub name: String,
+    /// Each distinct value contributed, in the order first seen
+    pub values: Vec<String>,
+    /// The spec file that contributed each entry in `values`, in the same order
+    pub sources: Vec<PathBuf>,
+}
+
+/// The result of composing one or more [`EnvSpec`] layers together.
+///
+/// Layers are kept in the order that they should be applied, from
+/// least to most specific, so that later layers take precedence.
+#[derive(Debug, Default, PartialEq)]
+pub struct ComposedEnvironment {
+    /// The ordered set of spec layers that make up this environment
+    pub layers: Vec<EnvSpec>,
+    /// Every source that contributed each layer, keyed by the layer's
+    /// canonicalized file path. A layer reachable through more than
+    /// one mechanism (e.g. both discovered and included) has more
+    /// than one entry here.
+    pub provenance: HashMap<PathBuf, Vec<LayerSource>>,
+}
+
+impl ComposedEnvironment {
+    /// The priority that governs this environment's startup-script
+    /// filename, taken from the last layer that sets one.
+    pub fn effective_priority(&self) -> Option<i32> {
+        self.layers.iter().rev().find_map(|l| l.priority)
+    }
+
+    /// The lock policy that governs `spenv check` and `spenv load`'s
+    /// default strictness, taken from the last layer that sets one.
+    /// Falls back to [`LockPolicy::default`] when no layer sets one.
+    pub fn effective_lock_policy(&self) -> LockPolicy {
+        self.layers
+            .iter()
+            .rev()
+            .find_map(|l| l.lock)
+            .unwrap_or_default()
+    }
+
+    /// Every layer's `note`, paired with the file it came from, in
+    /// composition order. Layers without a note are omitted. Useful
+    /// for surfacing why a layer is present without printing the
+    /// whole spec, e.g. the banner `spenv load` prints before running
+    /// a command, which uses the last entry as the top-level
+    /// description.
+    pub fn descriptions(&self) -> Vec<(PathBuf, String)> {
+        self.layers
+            .iter()
+            .filter_map(|l| l.note.clone().map(|note| (l.file_path.clone(), note)))
+            .collect()
+    }
+
+    /// Every distinct [`EnvSpec::platform`] key that matched the host
+    /// this process is actually running on, across every layer,
+    /// sorted and deduplicated. Reported by `spenv show` so it's
+    /// visible which platform sections actually took effect.
+    pub fn active_platform_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .layers
+            .iter()
+            .flat_map(|l| l.platform.keys())
+            .filter(|key| matches_running_platform_key(key))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Every package request across every layer, in composition
+    /// order. Layers contribute requests cumulatively; nothing is
+    /// deduplicated or overridden, since it's left to the solver to
+    /// reconcile duplicate or conflicting requests.
+    pub fn effective_packages(&self) -> Vec<String> {
+        self.layers
+            .iter()
+            .flat_map(|l| l.packages.iter().cloned())
+            .collect()
+    }
+
+    /// True if this environment has no layers at all, or every layer
+    /// it does have contributes no packages, ops, binds or contents.
+    /// Catches the common mistake of a spec with everything commented
+    /// out, which would otherwise silently resolve to a no-op
+    /// environment instead of failing loudly.
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(|l| {
+            l.packages.is_empty() && l.ops.is_empty() && l.binds.is_empty() && l.contents.is_empty()
+        })
+    }
+
+    /// The environment variable operations that remain after each
+    /// layer's `environment_exclude` has filtered out the operations
+    /// contributed by earlier layers.
+    pub fn effective_ops(&self) -> Vec<EnvOp> {
+        let mut ops: Vec<EnvOp> = Vec::new();
+        for layer in &self.layers {
+            if !layer.environment_exclude.is_empty() {
+                ops.retain(|op| {
+                    !layer
+                        .environment_exclude
+                        .iter()
+                        .any(|pattern| matches_exclude(op.var(), pattern))
+                });
+            }
+            ops.extend(layer.ops.iter().cloned());
+        }
+        ops
+    }
+
+    /// Like [`ComposedEnvironment::effective_ops`], but each op is
+    /// paired with the priority in effect when it was contributed:
+    /// the last priority set by that op's layer or an earlier one,
+    /// defaulting to 0 if no layer has set one yet.
+    pub fn effective_ops_with_priority(&self) -> Vec<(i32, EnvOp)> {
+        let mut ops: Vec<(i32, EnvOp)> = Vec::new();
+        let mut priority = 0;
+        for layer in &self.layers {
+            if let Some(p) = layer.priority {
+                priority = p;
+            }
+            if !layer.environment_exclude.is_empty() {
+                ops.retain(|(_, op)| {
+                    !layer
+                        .environment_exclude
+                        .iter()
+                        .any(|pattern| matches_exclude(op.var(), pattern))
+                });
+            }
+            ops.extend(layer.ops.iter().cloned().map(|op| (priority, op)));
+        }
+        ops
+    }
+
+    /// Like [`ComposedEnvironment::effective_ops_with_priority`], but
+    /// kept separate per contributing layer instead of merged into
+    /// one ordered list: each entry is a single layer's own surviving
+    /// ops (after every layer's `environment_exclude` has been
+    /// applied), paired with that layer's own `priority`, defaulting
+    /// to 50 if it didn't set one. Unlike `effective_ops_with_priority`,
+    /// a layer that sets no priority of its own does not inherit one
+    /// from an earlier layer.
+    pub fn effective_ops_by_layer(&self) -> Vec<(i32, Vec<EnvOp>)> {
+        let mut layer_ops: Vec<(i32, Vec<EnvOp>)> = self
+            .layers
+            .iter()
+            .map(|l| (l.priority.unwrap_or(50), Vec::new()))
+            .collect();
+        for (index, layer) in self.layers.iter().enumerate() {
+            if !layer.environment_exclude.is_empty() {
+                for (_, ops) in layer_ops.iter_mut() {
+                    ops.retain(|op: &EnvOp| {
+                        !layer
+                            .environment_exclude
+                            .iter()
+                            .any(|pattern| matches_exclude(op.var(), pattern))
+                    });
+                }
+            }
+            layer_ops[index].1.extend(layer.ops.iter().cloned());
+        }
+        layer_ops
+    }
+
+    /// Like [`ComposedEnvironment::effective_ops`], but collapsed into
+    /// a deterministic per-variable sequence: a `set` acts as a reset
+    /// point, discarding any earlier `set`/`prepend`/`append` for the
+    /// same variable, and only the `prepend`/`append` ops that follow
+    /// the most recent `set` survive. This resolves the otherwise
+    /// implicit ordering when one layer `set`s a variable that an
+    /// earlier layer had already `prepend`ed or `append`ed to.
+    ///
+    /// Other op kinds (`unset`, `default`, `path_remove`, `source`)
+    /// are left exactly where they fall, since they don't reset a
+    /// variable's accumulated prepend/append chain the way `set` does.
+    ///
+    /// This is opt-in: callers that want ops exactly as contributed by
+    /// each layer, implicit ordering and all, should keep using
+    /// [`ComposedEnvironment::effective_ops`].
+    pub fn normalize_env(&self) -> Vec<EnvOp> {
+        let mut normalized: Vec<EnvOp> = Vec::new();
+        for op in self.effective_ops() {
+            if let EnvOp::Set { var, .. } = &op {
+                normalized.retain(|existing| {
+                    !matches!(
+                        existing,
+                        EnvOp::Set { var: v, .. }
+                        | EnvOp::Prepend { var: v, .. }
+                        | EnvOp::Append { var: v, .. }
+                        if v == var
+                    )
+                });
+            }
+            normalized.push(op);
+        }
+        normalized
+    }
+
+    /// Find every variable that two or more layers `set` or
+    /// `default` to differing values, after `environment_exclude`
+    /// filtering. Unlike [`ComposedEnvironment::effective_ops`], the
+    /// last value isn't assumed to be what the author intended:
+    /// `prepend`/`append` are excluded since they're meant to stack,
+    /// but two conflicting `set`s (or a `set` and a `default`) most
+    /// often mean one layer is clobbering another by accident.
+    ///
+    /// A single layer repeating the same `set` is reported separately
+    /// by [`crate::validate::ValidationIssue::DuplicateSet`]; here,
+    /// only the layer's last value for a variable counts towards a
+    /// cross-layer conflict.
+    pub fn conflicts(&self) -> Vec<EnvConflict> {
+        let mut ops: Vec<(EnvOp, PathBuf)> = Vec::new();
+        for layer in &self.layers {
+            if !layer.environment_exclude.is_empty() {
+                ops.retain(|(op, _)| {
+                    !layer
+                        .environment_exclude
+                        .iter()
+                        .any(|pattern| matches_exclude(op.var(), pattern))
+                });
+            }
+            ops.extend(
+                layer
+                    .ops
+                    .iter()
+                    .cloned()
+                    .map(|op| (op, layer.file_path.clone())),
+            );
+        }
+
+        let mut by_var: HashMap<String, Vec<(String, PathBuf)>> = HashMap::new();
+        for (op, source) in ops {
+            if let EnvOp::Set { var, value } | EnvOp::Default { var, value } = op {
+                let contributions = by_var.entry(var).or_default();
+                match contributions.last_mut() {
+                    Some((last_value, last_source)) if *last_source == source => {
+                        *last_value = value;
+                    }
+                    _ => contributions.push((value, source)),
+                }
+            }
+        }
+
+        let mut conflicts: Vec<EnvConflict> = by_var
+            .into_iter()
+            .filter_map(|(name, contributions)| {
+                let mut values = Vec::new();
+                let mut sources = Vec::new();
+                for (value, source) in contributions {
+                    if !values.contains(&value) {
+                        values.push(value);
+                        sources.push(source);
+                    }
+                }
+                (values.len() > 1).then_some(EnvConflict {
+                    name,
+                    values,
+                    sources,
+                })
+            })
+            .collect();
+        conflicts.sort_by(|a, b| a.name.cmp(&b.name));
+        conflicts
+    }
+
+    /// Resolve the full cascade of specs starting from a directory.
+    ///
+    /// This discovers any inherited ancestor specs and recursively
+    /// resolves the `includes` declared by each, flattening the
+    /// result into a single ordered list of layers.
+    pub fn resolve<P: AsRef<Path>>(start: P) -> Result<Self, ComposeError> {
+        Self::resolve_with_options(start, DiscoveryOptions::default())
+    }
+
+    /// Resolve the full cascade of specs starting from a directory,
+    /// limiting ancestor discovery per `options`.
+    pub fn resolve_with_options<P: AsRef<Path>>(
+        start: P,
+        options: DiscoveryOptions,
+    ) -> Result<Self, ComposeError> {
+        Self::resolve_with_options_impl(start, options).map(|(env, _)| env)
+    }
+
+    /// Resolve the full cascade of specs starting from a directory,
+    /// like [`ComposedEnvironment::resolve_with_options`], additionally
+    /// reporting how long each individual `includes` entry took to
+    /// load, for `spenv show --profile-includes`.
+    pub fn resolve_with_profiling<P: AsRef<Path>>(
+        start: P,
+        options: DiscoveryOptions,
+    ) -> Result<(Self, Vec<IncludeTiming>), ComposeError> {
+        Self::resolve_with_options_impl(start, options)
+    }
+
+    /// Resolve the full cascade like
+    /// [`ComposedEnvironment::resolve_with_options`], but satisfy every
+    /// discovered spec's `includes` via `resolver` instead of reading
+    /// them directly from disk.
+    ///
+    /// Ancestor discovery via `inherit: true` is unaffected, since
+    /// it's inherently tied to walking real directories; only the
+    /// specs an `includes` entry names are looked up through
+    /// `resolver`. This lets a caller mix filesystem-discovered
+    /// layers with includes satisfied from its own store (a database,
+    /// a test double, ...), without switching to
+    /// [`compose_from_yaml`]'s fully in-memory composition. Pass
+    /// [`FilesystemResolver`] for behavior identical to
+    /// [`ComposedEnvironment::resolve_with_options`]'s own disk-backed
+    /// resolution, aside from not expanding glob include patterns,
+    /// which this entry point does not support.
+    pub fn resolve_with_resolver<P: AsRef<Path>>(
+        start: P,
+        options: DiscoveryOptions,
+        resolver: &dyn IncludeResolver,
+    ) -> Result<Self, ComposeError> {
+        let mut state = GatherState::default();
+        if options.system_defaults
+            && let Some(spec) = load_system_default(&options.system_default_path)?
+        {
+            gather_includes_from_resolver(spec, LayerSource::SystemDefault, &mut state, resolver)?;
+        }
+        for spec in discover_in_tree(start, options)? {
+            gather_includes_from_resolver(spec, LayerSource::Discovered, &mut state, resolver)?;
+        }
+        let mut layers = state.layers;
+        merge_platform_overlays(&mut layers);
+        interpolate_layers(&mut layers)?;
+        let layers = sort_by_weight(layers);
+        Ok(Self {
+            layers,
+            provenance: state.provenance,
+        })
+    }
+
+    fn resolve_with_options_impl<P: AsRef<Path>>(
+        start: P,
+        options: DiscoveryOptions,
+    ) -> Result<(Self, Vec<IncludeTiming>), ComposeError> {
+        // Sibling includes are independent, so each level fans out
+        // their loading to the blocking thread pool (see
+        // `resolve_all_includes`). A lone current-thread runtime is
+        // enough to drive that without requiring every caller of
+        // this otherwise-synchronous API to deal with `async`.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .expect("building the include-resolution runtime should not fail");
+
+        let mut state = GatherState::default();
+        if options.system_defaults
+            && let Some(spec) = load_system_default(&options.system_default_path)?
+        {
+            gather_includes(
+                spec,
+                LayerSource::SystemDefault,
+                &mut state,
+                runtime.handle(),
+            )?;
+        }
+        for spec in discover_in_tree(start, options)? {
+            gather_includes(spec, LayerSource::Discovered, &mut state, runtime.handle())?;
+        }
+        let mut layers = state.layers;
+        merge_platform_overlays(&mut layers);
+        interpolate_layers(&mut layers)?;
+        let layers = sort_by_weight(layers);
+        Ok((
+            Self {
+                layers,
+                provenance: state.provenance,
+            },
+            state.timings,
+        ))
+    }
+
+    /// Append `overlay` as the final layer, giving it the highest
+    /// precedence of any layer already present.
+    pub fn with_overlay(mut self, overlay: EnvSpec) -> Self {
+        self.layers.push(overlay);
+        self
+    }
+
+    /// Insert `layers` before every layer already present, keeping
+    /// their own relative order, as the lowest precedence of any layer.
+    pub fn with_layers_before(mut self, mut layers: Vec<EnvSpec>) -> Self {
+        layers.append(&mut self.layers);
+        self.layers = layers;
+        self
+    }
+
+    /// Append `layers` after every layer already present, keeping
+    /// their own relative order, as the highest precedence of any
+    /// layer short of a later [`ComposedEnvironment::with_overlay`].
+    pub fn with_layers_after(mut self, mut layers: Vec<EnvSpec>) -> Self {
+        self.layers.append(&mut layers);
+        self
+    }
+
+    /// Layers that were reached through more than one composition
+    /// mechanism, e.g. both discovered via `inherit: true` and pulled
+    /// in by an unrelated spec's `includes`. This is advisory: such a
+    /// layer is still only applied once, but the duplication usually
+    /// indicates a spec graph that could be simplified.
+    pub fn cross_mechanism_duplicates(&self) -> Vec<&PathBuf> {
+        self.provenance
+            .iter()
+            .filter(|(_, sources)| {
+                let discovered = sources
+                    .iter()
+                    .any(|source| matches!(source, LayerSource::Discovered));
+                let included = sources
+                    .iter()
+                    .any(|source| matches!(source, LayerSource::Included { .. }));
+                discovered && included
+            })
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// True if `self` and `other` resolve to the same layers, in the
+    /// same order, ignoring the file paths each layer was loaded from
+    /// and how it was discovered or included.
+    ///
+    /// This is what the diff and cache-invalidation logic should use
+    /// instead of [`PartialEq`], since two compositions found at
+    /// different starting directories are routinely expected to
+    /// differ in [`EnvSpec::file_path`] and `provenance` alone.
+    pub fn semantically_equal(&self, other: &Self) -> bool {
+        self.layers.len() == other.layers.len()
+            && self
+                .layers
+                .iter()
+                .zip(&other.layers)
+                .all(|(a, b)| a.semantically_equal(b))
+    }
+
+    /// Flatten every layer into a single-document YAML rendering of
+    /// the environment's effective state, for `spenv cat`.
+    ///
+    /// Unless `strip_comments` is set, the output is preceded by a
+    /// comment header listing the layers it was consolidated from.
+    pub fn to_yaml(&self, strip_comments: bool) -> String {
+        let flattened = FlattenedSpec {
+            ops: self.effective_ops(),
+            binds: self
+                .layers
+                .iter()
+                .flat_map(|l| l.binds.iter().cloned())
+                .collect(),
+            contents: self
+                .layers
+                .iter()
+                .flat_map(|l| l.contents.iter().cloned())
+                .collect(),
+            packages: self.effective_packages(),
+            priority: self.effective_priority(),
+        };
+        let body = serde_yaml::to_string(&flattened)
+            .expect("serializing a flattened spec to YAML should not fail");
+        if strip_comments {
+            return body;
+        }
+
+        let mut header = String::from("# Consolidated from:\n");
+        for layer in &self.layers {
+            header.push_str(&format!("#   {}\n", layer.file_path.display()));
+        }
+        header.push_str(&body);
+        header
+    }
+
+    /// Flatten every layer into a single, self-contained `.spenv.yaml`
+    /// document that doesn't depend on any other file, for `spenv
+    /// export --format spenv-yaml`.
+    ///
+    /// Unlike [`ComposedEnvironment::to_yaml`], which is meant to be a
+    /// readable summary of an environment that's still backed by its
+    /// original layers, this sets `includes: []` and `inherit: false`
+    /// explicitly and omits the provenance header, so the file can be
+    /// handed to someone without access to the include paths it was
+    /// composed from and still resolve to the same environment on its
+    /// own.
+    pub fn to_standalone_yaml(&self) -> String {
+        let standalone = StandaloneSpec {
+            includes: Vec::new(),
+            inherit: false,
+            ops: self.effective_ops(),
+            binds: self
+                .layers
+                .iter()
+                .flat_map(|l| l.binds.iter().cloned())
+                .collect(),
+            contents: self
+                .layers
+                .iter()
+                .flat_map(|l| l.contents.iter().cloned())
+                .collect(),
+            packages: self.effective_packages(),
+            priority: self.effective_priority(),
+        };
+        serde_yaml::to_string(&standalone)
+            .expect("serializing a standalone spec to YAML should not fail")
+    }
+
+    /// Export this composed environment as a portable [`EnvManifest`],
+    /// for bridging a resolved `spenv` environment into a container.
+    ///
+    /// This captures environment state, not a buildable image: the
+    /// bind destinations and package requests are listed for the
+    /// consuming tooling to act on, but nothing here describes how to
+    /// construct a root filesystem.
+    pub fn to_env_manifest(&self) -> EnvManifest {
+        let mut vars: HashMap<String, String> = HashMap::new();
+        for op in self.effective_ops() {
+            crate::runtime::apply_op(&mut vars, &op);
+        }
+        let env = vars.into_iter().collect();
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| ManifestLayer {
+                reference: layer.file_path.clone(),
+                digest: std::fs::read_to_string(&layer.file_path)
+                    .ok()
+                    .map(|contents| crate::lock::hash_contents(&contents)),
+            })
+            .collect();
+        let binds = self
+            .layers
+            .iter()
+            .flat_map(|layer| layer.binds.iter().map(|bind| bind.dest.clone()))
+            .collect();
+        let contents = self
+            .layers
+            .iter()
+            .flat_map(|layer| {
+                layer
+                    .contents
+                    .iter()
+                    .map(|content| content.dest().to_owned())
+            })
+            .collect();
+        EnvManifest {
+            env,
+            layers,
+            binds,
+            contents,
+        }
+    }
+}
+
+/// A portable export of a [`ComposedEnvironment`]'s resolved state,
+/// for `spenv export --format oci-env`. This is a snapshot of
+/// environment state meant to be applied inside a container, not a
+/// description of an image to build.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct EnvManifest {
+    /// The fully resolved environment variables, after applying every
+    /// layer's operations in order
+    pub env: std::collections::BTreeMap<String, String>,
+    /// Every layer that contributed to this environment, in
+    /// composition order
+    pub layers: Vec<ManifestLayer>,
+    /// Every bind mount destination declared by any layer
+    pub binds: Vec<PathBuf>,
+    /// Every [`crate::spec::ContentMount`] destination declared by any
+    /// layer, bind or tmpfs alike
+    pub contents: Vec<PathBuf>,
+}
+
+/// A single layer's reference within an [`EnvManifest`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ManifestLayer {
+    /// The spec file this layer was loaded from
+    pub reference: PathBuf,
+    /// A content digest of the layer at export time, `None` if its
+    /// file could no longer be read
+    pub digest: Option<String>,
+}
+
+/// A single-document rendering of a [`ComposedEnvironment`]'s
+/// effective state, used only to serialize [`ComposedEnvironment::to_yaml`]'s
+/// output. Unlike [`EnvSpec`], this has no `includes`/`inherit`, since
+/// it's already the flattened result of resolving those.
+#[derive(Debug, serde::Serialize)]
+struct FlattenedSpec {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ops: Vec<EnvOp>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    binds: Vec<crate::spec::BindMount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contents: Vec<ContentMount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    packages: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+}
+
+/// A single-document rendering of a [`ComposedEnvironment`], used
+/// only to serialize [`ComposedEnvironment::to_standalone_yaml`]'s
+/// output. Unlike [`FlattenedSpec`], `includes` and `inherit` are
+/// always written out explicitly, so the document is unambiguously
+/// self-contained rather than merely omitting fields that happen to
+/// be empty.
+#[derive(Debug, serde::Serialize)]
+struct StandaloneSpec {
+    includes: Vec<PathBuf>,
+    inherit: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    ops: Vec<EnvOp>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    binds: Vec<crate::spec::BindMount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    contents: Vec<ContentMount>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    packages: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<i32>,
+}
+
+/// How long a single `includes` entry took to load, for `spenv show
+/// --profile-includes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeTiming {
+    /// The spec that declared the include
+    pub from: PathBuf,
+    /// The include's resolved file path
+    pub path: PathBuf,
+    /// How long the include took to load from disk
+    pub duration: Duration,
+}
+
+/// Supplies the logical path and YAML text that an `includes` entry
+/// refers to, in place of reading it from disk.
+///
+/// Implement this to satisfy includes from whatever store a caller
+/// that embeds `spenv` keeps its specs in, e.g. a database or an API
+/// response cache. [`FilesystemResolver`] provides the default,
+/// disk-backed behavior that [`ComposedEnvironment::resolve`] and
+/// [`ComposedEnvironment::resolve_with_options`] always use.
+pub trait IncludeResolver {
+    /// Resolve `include`, the text of an `includes` entry exactly as
+    /// written (glob patterns are not expanded before it's passed in),
+    /// into the logical path and YAML text of the spec it names. The
+    /// returned path becomes the resolved spec's [`EnvSpec::file_path`],
+    /// so it should be stable and unique per spec: it's used to
+    /// detect circular includes and to report provenance.
+    ///
+    /// `base` is the directory of the spec that declared the include,
+    /// when one is known; it's `None` only for a spec composed
+    /// without a real file path of its own, e.g. one of
+    /// [`compose_from_yaml`]'s `sources` that names a bare logical
+    /// path with no parent component.
+    fn resolve(
+        &self,
+        include: &str,
+        base: Option<&Path>,
+    ) -> Result<(PathBuf, String), ComposeError>;
+}
+
+/// The default [`IncludeResolver`]: resolves an include exactly like
+/// [`ComposedEnvironment::resolve`] always has, reading a relative
+/// path against `base` and an absolute path as-is from disk, or
+/// fetching it over HTTP(S) when the `remote-includes` feature is
+/// enabled and `include` names a URL.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FilesystemResolver;
+
+impl IncludeResolver for FilesystemResolver {
+    fn resolve(
+        &self,
+        include: &str,
+        base: Option<&Path>,
+    ) -> Result<(PathBuf, String), ComposeError> {
+        #[cfg(feature = "remote-includes")]
+        if crate::remote::is_remote_include(include) {
+            let path = crate::remote::resolve_remote_include(include)?;
+            let contents = read_include_file(include, &path)?;
+            return Ok((path, contents));
+        }
+
+        let include_path = Path::new(include);
+        let resolved = if include_path.is_absolute() {
+            include_path.to_owned()
+        } else {
+            base.unwrap_or_else(|| Path::new("")).join(include_path)
+        };
+        let contents = read_include_file(include, &resolved)?;
+        Ok((resolved, contents))
+    }
+}
+
+/// Read `path` for [`FilesystemResolver`], reporting `include` (the
+/// entry as originally written) rather than the resolved path in the
+/// error, since that's what the author of the including spec will
+/// recognize.
+fn read_include_file(include: &str, path: &Path) -> Result<String, ComposeError> {
+    std::fs::read_to_string(path).map_err(|source| ComposeError::IncludeResolutionFailed {
+        include: include.to_string(),
+        reason: source.to_string(),
+    })
+}
+
+/// Compose an environment from in-memory spec sources, without any
+/// filesystem discovery.
+///
+/// Each entry in `sources` is parsed as YAML, with
+/// [`EnvSpec::file_path`] set to its paired logical path, and the
+/// specs are composed in the order given, as if they were the result
+/// of [`crate::discovery::discover_in_tree`] (least to most specific).
+/// Each spec's `includes` are then resolved via `resolver`, letting a
+/// caller that receives spec contents over its own API satisfy them
+/// from its own store instead of the filesystem lookups
+/// [`ComposedEnvironment::resolve`] performs.
+///
+/// `inherit` has no effect here, since there is no directory tree to
+/// walk up: every entry in `sources` is composed regardless of its
+/// value.
+pub fn compose_from_yaml(
+    sources: &[(PathBuf, String)],
+    resolver: &dyn IncludeResolver,
+) -> Result<ComposedEnvironment, ComposeError> {
+    let mut state = GatherState::default();
+    for (path, yaml) in sources {
+        let spec = EnvSpec::from_yaml_str(path.clone(), yaml)?;
+        gather_includes_from_resolver(spec, LayerSource::Discovered, &mut state, resolver)?;
+    }
+    let mut layers = state.layers;
+    merge_platform_overlays(&mut layers);
+    interpolate_layers(&mut layers)?;
+    let layers = sort_by_weight(layers);
+    Ok(ComposedEnvironment {
+        layers,
+        provenance: state.provenance,
+    })
+}
+
+/// Like [`gather_includes`], but resolves `includes` via a
+/// caller-supplied [`IncludeResolver`] instead of reading them
+/// directly from disk. Sibling includes are resolved sequentially
+/// here rather than concurrently, since a resolver has no obligation
+/// to be safe to call from multiple threads at once.
+fn gather_includes_from_resolver(
+    spec: EnvSpec,
+    source: LayerSource,
+    state: &mut GatherState,
+    resolver: &dyn IncludeResolver,
+) -> Result<(), ComposeError> {
+    if state.stack.contains(&spec.file_path) {
+        return Err(ComposeError::CircularInclude(spec.file_path.clone()));
+    }
+    state.stack.push(spec.file_path.clone());
+    state
+        .provenance
+        .entry(spec.file_path.clone())
+        .or_default()
+        .push(source);
+
+    if spec.layers_mode == LayersMode::Replace {
+        state.layers.clear();
+    }
+
+    let base_dir = spec.file_path.parent();
+    for include in spec.includes.clone() {
+        if !include.matches_host() {
+            continue;
+        }
+        let include_str = include.path().to_string_lossy();
+        let (include_path, yaml) = match resolver.resolve(&include_str, base_dir) {
+            Ok(resolved) => resolved,
+            Err(_) if include.is_optional() => {
+                tracing::debug!("optional include {include_str:?} not found, skipping");
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let mut included = EnvSpec::from_yaml_str(include_path, &yaml)?;
+        if let Some(weight) = include.weight() {
+            included.weight = Some(weight);
+        }
+        gather_includes_from_resolver(
+            included,
+            LayerSource::Included {
+                from: spec.file_path.clone(),
+            },
+            state,
+            resolver,
+        )?;
+    }
+    state.layers.push(spec);
+    state.stack.pop();
+    Ok(())
+}
+
+/// The mutable state threaded through [`gather_includes`]'s recursion.
+#[derive(Default)]
+struct GatherState {
+    layers: Vec<EnvSpec>,
+    stack: Vec<PathBuf>,
+    provenance: HashMap<PathBuf, Vec<LayerSource>>,
+    timings: Vec<IncludeTiming>,
+}
+
+/// Recursively resolve the `includes` of a spec into `state.layers`, depth-first.
+///
+/// `state.stack` tracks the specs currently being resolved along the
+/// current path, so that a spec including itself (directly or
+/// transitively) is caught as an error. A spec that is reachable more
+/// than once through unrelated branches of the include graph is not an
+/// error, and simply results in a duplicate entry in `state.layers`.
+///
+/// A single spec's sibling includes are independent of one another, so
+/// they are loaded concurrently via `handle`; the recursion into each
+/// loaded spec remains sequential, which is what keeps composition
+/// order and circular-include detection correct.
+///
+/// A spec with [`EnvSpec::layers_mode`] set to [`LayersMode::Replace`]
+/// clears `state.layers` before its own includes and itself are added,
+/// discarding every layer composed so far.
+fn gather_includes(
+    spec: EnvSpec,
+    source: LayerSource,
+    state: &mut GatherState,
+    handle: &Handle,
+) -> Result<(), ComposeError> {
+    let canonical = spec
+        .file_path
+        .canonicalize()
+        .unwrap_or_else(|_| spec.file_path.clone());
+    if state.stack.contains(&canonical) {
+        return Err(ComposeError::CircularInclude(spec.file_path.clone()));
+    }
+    state.stack.push(canonical.clone());
+    state.provenance.entry(canonical).or_default().push(source);
+
+    if spec.layers_mode == LayersMode::Replace {
+        state.layers.clear();
+    }
+
+    let base_dir = spec
+        .file_path
+        .parent()
+        .map(Path::to_owned)
+        .unwrap_or_default();
+    let mut include_paths = Vec::new();
+    for include in spec.includes.clone() {
+        if !include.matches_host() {
+            continue;
+        }
+        let optional = include.is_optional();
+        let weight = include.weight();
+        match resolve_include(&spec.file_path, &base_dir, include.path()) {
+            Ok(paths) => {
+                include_paths.extend(paths.into_iter().map(|path| (path, optional, weight)))
+            }
+            Err(ComposeError::IncludeGlobEmpty { pattern, .. }) if optional => {
+                tracing::debug!("optional include glob {pattern:?} matched no files, skipping");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let included_specs = handle.block_on(load_includes(&spec.file_path, include_paths))?;
+    for (included, duration) in included_specs {
+        state.timings.push(IncludeTiming {
+            from: spec.file_path.clone(),
+            path: included.file_path.clone(),
+            duration,
+        });
+        gather_includes(
+            included,
+            LayerSource::Included {
+                from: spec.file_path.clone(),
+            },
+            state,
+            handle,
+        )?;
+    }
+    state.layers.push(spec);
+    state.stack.pop();
+    Ok(())
+}
+
+/// Load every include in `paths` concurrently, preserving their
+/// original order in the result so composition order stays
+/// deterministic regardless of how the loads interleave. Each result
+/// is paired with how long its own load took, so callers can attribute
+/// a slow include to itself rather than the batch as a whole.
+///
+/// Each path is paired with whether its originating entry was marked
+/// `optional`: a missing optional include is logged at debug level
+/// and dropped from the result rather than failing the whole batch.
+/// Also paired with the originating entry's `weight` override, if
+/// any, applied to the loaded spec in place of its own declared
+/// [`EnvSpec::weight`].
+async fn load_includes(
+    from: &Path,
+    paths: Vec<(PathBuf, bool, Option<i32>)>,
+) -> Result<Vec<(EnvSpec, Duration)>, ComposeError> {
+    let from = from.to_owned();
+    let loads = paths.into_iter().map(|(include_path, optional, weight)| {
+        let from = from.clone();
+        async move {
+            tokio::task::spawn_blocking(move || {
+                let start = Instant::now();
+                match EnvSpec::load_file(&include_path) {
+                    Ok(mut spec) => {
+                        if let Some(weight) = weight {
+                            spec.weight = Some(weight);
+                        }
+                        Ok(Some((spec, start.elapsed())))
+                    }
+                    Err(_) if optional => {
+                        tracing::debug!("optional include {include_path:?} not found, skipping");
+                        Ok(None)
+                    }
+                    Err(_) => Err(ComposeError::IncludeNotFound {
+                        from,
+                        include: include_path,
+                    }),
+                }
+            })
+            .await
+            .expect("loading an include should not panic")
+        }
+    });
+    Ok(try_join_all(loads).await?.into_iter().flatten().collect())
+}
+
+/// Resolve a single `includes` entry against `base_dir` into the
+/// files it refers to.
+///
+/// An entry containing glob metacharacters (`*`, `?`, `[...]`) is
+/// expanded to every matching file, sorted lexicographically for a
+/// deterministic composition order. Any other entry resolves to
+/// exactly the one path it names, matching behavior from before glob
+/// support existed.
+pub(crate) fn resolve_include(
+    from: &Path,
+    base_dir: &Path,
+    include: &Path,
+) -> Result<Vec<PathBuf>, ComposeError> {
+    let include_str = include.to_string_lossy();
+
+    #[cfg(feature = "remote-includes")]
+    if crate::remote::is_remote_include(&include_str) {
+        return Ok(vec![crate::remote::resolve_remote_include(&include_str)?]);
+    }
+
+    if !is_glob_pattern(&include_str) {
+        let include_path = if include.is_absolute() {
+            include.to_owned()
+        } else {
+            base_dir.join(include)
+        };
+        return Ok(vec![include_path]);
+    }
+
+    let pattern = if include.is_absolute() {
+        include_str.into_owned()
+    } else {
+        base_dir.join(include).to_string_lossy().into_owned()
+    };
+    let paths = glob::glob(&pattern)
+        .map_err(|source| ComposeError::InvalidGlob {
+            from: from.to_owned(),
+            pattern: pattern.clone(),
+            source,
+        })?
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Err(ComposeError::IncludeGlobEmpty {
+            from: from.to_owned(),
+            pattern,
+        });
+    }
+
+    let mut paths = paths;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Returns true if `s` contains any glob metacharacters.
+fn is_glob_pattern(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+/// Stable-sort `layers` by [`EnvSpec::weight`], defaulting to 0 for a
+/// layer that doesn't set one, so that two layers with the same
+/// weight keep the relative order they were gathered in.
+///
+/// This runs after every layer has been gathered, including
+/// duplicates reached through more than one discovery or include
+/// path (see [`ComposedEnvironment::cross_mechanism_duplicates`]): a
+/// duplicate is sorted independently of its other occurrences, and
+/// since composition never deduplicates layers, both are still
+/// applied, just possibly no longer adjacent to one another.
+fn sort_by_weight(layers: Vec<EnvSpec>) -> Vec<EnvSpec> {
+    let mut indexed: Vec<(usize, EnvSpec)> = layers.into_iter().enumerate().collect();
+    indexed.sort_by_key(|(i, layer)| (layer.weight.unwrap_or(0), *i));
+    indexed.into_iter().map(|(_, layer)| layer).collect()
+}
+
+/// Merge each layer's matching [`EnvSpec::platform`] sections into its
+/// own `ops`, `binds` and `packages`, in sorted key order. A layer's
+/// `platform` map is left in place afterwards, so
+/// [`ComposedEnvironment::active_platform_keys`] can still report
+/// which sections applied.
+fn merge_platform_overlays(layers: &mut [EnvSpec]) {
+    for layer in layers.iter_mut() {
+        let mut keys: Vec<&String> = layer.platform.keys().collect();
+        keys.sort();
+        for key in keys {
+            if !matches_running_platform_key(key) {
+                continue;
+            }
+            let overlay = layer.platform[key].clone();
+            layer.ops.extend(overlay.ops);
+            layer.binds.extend(overlay.binds);
+            layer.packages.extend(overlay.packages);
+        }
+    }
+}
+
+/// Interpolate `${SPENV_SPEC_DIR}`, `${SPENV_PROJECT_ROOT}`, and
+/// `${ENV:NAME}` references into every layer's env op values and bind
+/// mounts, in place. A bind's `source` is also made absolute if it's
+/// still relative afterwards, against its own layer's directory —
+/// never against some other layer's, even one earlier in the cascade.
+///
+/// `SPENV_SPEC_DIR` resolves per-layer, to the directory containing
+/// that layer's own spec file. `SPENV_PROJECT_ROOT` is the same for
+/// every layer in a composition: the directory of `layers`' first,
+/// least-specific entry, which is the top of the discovered cascade.
+/// There is no precedence conflict between a built-in and a host
+/// variable of the same name, since built-ins are only ever named
+/// bare (`${SPENV_SPEC_DIR}`) while host variables are only ever
+/// reached through the explicit `ENV:` prefix (`${ENV:SPENV_SPEC_DIR}`
+/// would read a host variable literally named `SPENV_SPEC_DIR`).
+fn interpolate_layers(layers: &mut [EnvSpec]) -> Result<(), ComposeError> {
+    let project_root = layers
+        .first()
+        .and_then(|layer| layer.file_path.parent())
+        .unwrap_or_else(|| Path::new(""))
+        .to_owned();
+
+    for layer in layers.iter_mut() {
+        let file_path = layer.file_path.clone();
+        let spec_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+
+        for op in &mut layer.ops {
+            match op {
+                EnvOp::Set { var, value }
+                | EnvOp::Prepend { var, value }
+                | EnvOp::Append { var, value }
+                | EnvOp::Default { var, value }
+                | EnvOp::PathRemove { var, value, .. } => {
+                    *value = interpolate_value(value, spec_dir, &project_root, &file_path, var)?;
+                }
+                EnvOp::Source { source } => {
+                    *source =
+                        interpolate_value(source, spec_dir, &project_root, &file_path, "source")?;
+                }
+                EnvOp::Unset { .. } => {}
+            }
+        }
+        for bind in &mut layer.binds {
+            let source = interpolate_value(
+                &bind.source.to_string_lossy(),
+                spec_dir,
+                &project_root,
+                &file_path,
+                "bind source",
+            )?;
+            // A relative source is relative to the spec that declared
+            // it, not to whichever layer happens to be first in the
+            // cascade, so a child spec's relative bind doesn't get
+            // resolved against a parent spec's directory.
+            bind.source = match PathBuf::from(source) {
+                source if source.is_relative() => spec_dir.join(source),
+                source => source,
+            };
+            bind.dest = PathBuf::from(interpolate_value(
+                &bind.dest.to_string_lossy(),
+                spec_dir,
+                &project_root,
+                &file_path,
+                "bind dest",
+            )?);
+        }
+    }
+    Ok(())
+}
+
+/// Replace every `${...}` placeholder in `value`. `key` names the
+/// field `value` came from, for error reporting.
+fn interpolate_value(
+    value: &str,
+    spec_dir: &Path,
+    project_root: &Path,
+    layer: &Path,
+    key: &str,
+) -> Result<String, ComposeError> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            result.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let placeholder = &rest[start + 2..start + end];
+
+        if DEFERRED_PLACEHOLDERS.contains(&placeholder) {
+            result.push_str(&rest[start..start + end + 1]);
+        } else if let Some(env_name) = placeholder.strip_prefix("ENV:") {
+            let resolved = std::env::var(env_name).map_err(|_| ComposeError::ValidationFailed {
+                layer: layer.to_owned(),
+                key: key.to_owned(),
+                placeholder: placeholder.to_owned(),
+            })?;
+            result.push_str(&resolved);
+        } else {
+            let resolved = match placeholder {
+                "SPENV_SPEC_DIR" => spec_dir.to_string_lossy(),
+                "SPENV_PROJECT_ROOT" => project_root.to_string_lossy(),
+                _ => {
+                    return Err(ComposeError::ValidationFailed {
+                        layer: layer.to_owned(),
+                        key: key.to_owned(),
+                        placeholder: placeholder.to_owned(),
+                    });
+                }
+            };
+            result.push_str(&resolved);
+        }
+
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
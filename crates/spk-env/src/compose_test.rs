@@ -0,0 +1,1195 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use rstest::{fixture, rstest};
+
+use super::{ComposedEnvironment, FilesystemResolver, IncludeResolver, compose_from_yaml};
+use crate::discovery::DiscoveryOptions;
+use crate::error::ComposeError;
+use crate::spec::{EnvOp, EnvSpec};
+
+/// An [`IncludeResolver`] backed by an in-memory map, keyed by the
+/// logical path an include names, standing in for a caller's own
+/// spec store in tests.
+struct MapResolver(HashMap<PathBuf, String>);
+
+impl IncludeResolver for MapResolver {
+    fn resolve(
+        &self,
+        include: &str,
+        _base: Option<&Path>,
+    ) -> Result<(PathBuf, String), ComposeError> {
+        let path = PathBuf::from(include);
+        let yaml = self
+            .0
+            .get(&path)
+            .ok_or_else(|| ComposeError::IncludeResolutionFailed {
+                include: include.to_string(),
+                reason: "not present in the test's map".to_string(),
+            })?;
+        Ok((path.clone(), yaml.clone()))
+    }
+}
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_environment_exclude_filters_prefixed_ops(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: DEBUG_LEVEL\n    value: '2'\n  - op: set\n    var: PATH\n    value: /base/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nenvironment_exclude: ['DEBUG_*']\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let ops = env.effective_ops();
+
+    assert!(
+        !ops.iter().any(|op| op.var() == "DEBUG_LEVEL"),
+        "DEBUG_* ops should have been excluded"
+    );
+    assert!(
+        ops.contains(&EnvOp::Set {
+            var: "PATH".to_string(),
+            value: "/base/bin".to_string(),
+        }),
+        "unrelated ops should be kept"
+    );
+}
+
+#[rstest]
+fn test_normalize_env_keeps_a_prepend_that_follows_a_set(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /base/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: prepend\n    var: PATH\n    value: /project/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(
+        env.normalize_env(),
+        vec![
+            EnvOp::Set {
+                var: "PATH".to_string(),
+                value: "/base/bin".to_string(),
+            },
+            EnvOp::Prepend {
+                var: "PATH".to_string(),
+                value: "/project/bin".to_string(),
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_normalize_env_drops_a_prepend_superseded_by_a_later_set(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: prepend\n    var: PATH\n    value: /base/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: set\n    var: PATH\n    value: /project/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(
+        env.normalize_env(),
+        vec![EnvOp::Set {
+            var: "PATH".to_string(),
+            value: "/project/bin".to_string(),
+        }]
+    );
+}
+
+#[rstest]
+fn test_glob_include_expands_in_sorted_order(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let config_dir = root.join("config");
+    std::fs::create_dir(&config_dir).unwrap();
+    std::fs::write(config_dir.join("b.spenv.yaml"), "packages: [b-pkg]\n").unwrap();
+    std::fs::write(config_dir.join("a.spenv.yaml"), "packages: [a-pkg]\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: ['config/*.spenv.yaml']\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let packages: Vec<&str> = env
+        .layers
+        .iter()
+        .flat_map(|l| l.packages.iter().map(String::as_str))
+        .collect();
+    assert_eq!(packages, vec!["a-pkg", "b-pkg"]);
+}
+
+#[rstest]
+fn test_glob_include_matching_nothing_is_a_distinct_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "includes: ['missing/*.yaml']\n").unwrap();
+
+    let err = ComposedEnvironment::resolve(root).unwrap_err();
+    assert!(matches!(err, ComposeError::IncludeGlobEmpty { .. }));
+}
+
+#[rstest]
+fn test_optional_include_is_composed_when_present(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("override.spenv.yaml"),
+        "ops:\n  - op: set\n    var: FROM_OVERRIDE\n    value: loaded\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: override.spenv.yaml\n    optional: true\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(
+        env.effective_ops()
+            .iter()
+            .any(|op| op.var() == "FROM_OVERRIDE")
+    );
+}
+
+#[rstest]
+fn test_optional_include_missing_file_is_skipped_without_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: override.spenv.yaml\n    optional: true\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(env.layers.len(), 1);
+}
+
+#[rstest]
+fn test_optional_missing_glob_is_skipped_without_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: missing/*.yaml\n    optional: true\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(env.layers.len(), 1);
+}
+
+#[rstest]
+fn test_required_include_missing_file_is_still_an_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: override.spenv.yaml\n    optional: false\n",
+    )
+    .unwrap();
+
+    let err = ComposedEnvironment::resolve(root).unwrap_err();
+    assert!(matches!(err, ComposeError::IncludeNotFound { .. }));
+}
+
+#[rstest]
+fn test_with_overlay_lands_on_top_of_the_discovered_stack(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+
+    let overlay: EnvSpec = serde_yaml::from_str("priority: 99\n").unwrap();
+    let env = ComposedEnvironment::resolve(root)
+        .unwrap()
+        .with_overlay(overlay);
+
+    assert_eq!(env.layers.len(), 2);
+    assert_eq!(env.effective_priority(), Some(99));
+}
+
+#[rstest]
+fn test_system_defaults_are_composed_as_the_lowest_precedence_layer(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let system_dir = tempfile::Builder::new()
+        .prefix("spenv-test-system-")
+        .tempdir()
+        .expect("create a fake system config directory");
+    let system_default_path = system_dir.path().join("default.spenv.yaml");
+    std::fs::write(
+        &system_default_path,
+        "ops:\n  - op: set\n    var: PATH\n    value: /system/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /project/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve_with_options(
+        root,
+        DiscoveryOptions {
+            system_defaults: true,
+            system_default_path,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(env.layers.len(), 2);
+    let ops = env.effective_ops();
+    assert_eq!(
+        ops,
+        vec![
+            EnvOp::Set {
+                var: "PATH".to_string(),
+                value: "/system/bin".to_string(),
+            },
+            EnvOp::Set {
+                var: "PATH".to_string(),
+                value: "/project/bin".to_string(),
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_system_defaults_missing_file_is_not_an_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "inherit: false\n").unwrap();
+
+    let env = ComposedEnvironment::resolve_with_options(
+        root,
+        DiscoveryOptions {
+            system_defaults: true,
+            system_default_path: root.join("does-not-exist.yaml"),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert_eq!(env.layers.len(), 1);
+}
+
+#[rstest]
+fn test_sibling_includes_compose_in_declared_order(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    for (name, pkg) in [
+        ("one", "one-pkg"),
+        ("two", "two-pkg"),
+        ("three", "three-pkg"),
+    ] {
+        std::fs::write(
+            root.join(format!("{name}.spenv.yaml")),
+            format!("packages: [{pkg}]\n"),
+        )
+        .unwrap();
+    }
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [one.spenv.yaml, two.spenv.yaml, three.spenv.yaml]\n",
+    )
+    .unwrap();
+
+    // Sibling includes are loaded concurrently, but the composed
+    // order must still match the order they were declared in.
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let packages: Vec<&str> = env
+        .layers
+        .iter()
+        .flat_map(|l| l.packages.iter().map(String::as_str))
+        .collect();
+    assert_eq!(packages, vec!["one-pkg", "two-pkg", "three-pkg"]);
+}
+
+#[rstest]
+fn test_weight_sinks_a_late_declared_base_layer_to_the_bottom(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("early.spenv.yaml"), "packages: [early-pkg]\n").unwrap();
+    std::fs::write(
+        root.join("late_base.spenv.yaml"),
+        "weight: -100\npackages: [late-base-pkg]\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [early.spenv.yaml, late_base.spenv.yaml]\npackages: [root-pkg]\n",
+    )
+    .unwrap();
+
+    // Declared as the second include, `late_base.spenv.yaml` would
+    // naturally land between `early.spenv.yaml` and the root spec;
+    // its weight sinks it below both instead.
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(
+        env.layers
+            .iter()
+            .map(|l| l.file_path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["late_base.spenv.yaml", "early.spenv.yaml", ".spenv.yaml"]
+    );
+}
+
+#[rstest]
+fn test_equal_weights_preserve_declaration_order(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    for (name, pkg) in [("one", "one-pkg"), ("two", "two-pkg")] {
+        std::fs::write(
+            root.join(format!("{name}.spenv.yaml")),
+            format!("weight: 5\npackages: [{pkg}]\n"),
+        )
+        .unwrap();
+    }
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [one.spenv.yaml, two.spenv.yaml]\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let packages: Vec<&str> = env
+        .layers
+        .iter()
+        .flat_map(|l| l.packages.iter().map(String::as_str))
+        .collect();
+    assert_eq!(packages, vec!["one-pkg", "two-pkg"]);
+}
+
+#[rstest]
+fn test_include_weight_override_sinks_a_layer_without_editing_it(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("early.spenv.yaml"), "packages: [early-pkg]\n").unwrap();
+    // `base.spenv.yaml` declares no weight of its own; the root spec's
+    // object-form include pins it to the bottom anyway.
+    std::fs::write(root.join("base.spenv.yaml"), "packages: [base-pkg]\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - early.spenv.yaml\n  - path: base.spenv.yaml\n    weight: -100\npackages: [root-pkg]\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(
+        env.layers
+            .iter()
+            .map(|l| l.file_path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["base.spenv.yaml", "early.spenv.yaml", ".spenv.yaml"]
+    );
+}
+
+#[rstest]
+fn test_include_weight_override_beats_the_included_specs_own_weight(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    // The included spec declares its own weight, but the reference to
+    // it overrides that instead of combining with it.
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "weight: 5\npackages: [base-pkg]\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: base.spenv.yaml\n    weight: -100\npackages: [root-pkg]\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert_eq!(
+        env.layers
+            .iter()
+            .map(|l| l.file_path.file_name().unwrap().to_str().unwrap())
+            .collect::<Vec<_>>(),
+        vec!["base.spenv.yaml", ".spenv.yaml"]
+    );
+}
+
+#[test]
+fn test_is_empty_is_true_with_no_layers() {
+    let env = ComposedEnvironment::default();
+    assert!(env.layers.is_empty());
+    assert!(env.is_empty());
+}
+
+#[rstest]
+fn test_is_empty_is_true_when_every_layer_contributes_nothing(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(!env.layers.is_empty());
+    assert!(env.is_empty());
+}
+
+#[rstest]
+fn test_is_empty_is_false_with_a_package_request(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "packages: [some-pkg]\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(!env.is_empty());
+}
+
+#[rstest]
+fn test_with_layers_before_gives_injected_layers_the_lowest_precedence(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "packages: [root-pkg]\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let injected = EnvSpec {
+        packages: vec!["injected-pkg".to_string()],
+        ..Default::default()
+    };
+    let env = env.with_layers_before(vec![injected]);
+
+    assert_eq!(env.effective_packages(), vec!["injected-pkg", "root-pkg"]);
+}
+
+#[rstest]
+fn test_with_layers_after_gives_injected_layers_the_highest_precedence(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "packages: [root-pkg]\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let injected = EnvSpec {
+        packages: vec!["injected-pkg".to_string()],
+        ..Default::default()
+    };
+    let env = env.with_layers_after(vec![injected]);
+
+    assert_eq!(env.effective_packages(), vec!["root-pkg", "injected-pkg"]);
+}
+
+#[rstest]
+fn test_resolve_with_profiling_reports_one_timing_per_include(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("one.spenv.yaml"), "packages: [one-pkg]\n").unwrap();
+    std::fs::write(root.join("two.spenv.yaml"), "packages: [two-pkg]\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [one.spenv.yaml, two.spenv.yaml]\n",
+    )
+    .unwrap();
+
+    let (_, timings) =
+        ComposedEnvironment::resolve_with_profiling(root, DiscoveryOptions::default()).unwrap();
+
+    let names: Vec<&str> = timings
+        .iter()
+        .map(|t| t.path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, vec!["one.spenv.yaml", "two.spenv.yaml"]);
+    for timing in &timings {
+        assert_eq!(timing.from, root.join(".spenv.yaml"));
+    }
+}
+
+#[rstest]
+fn test_conditional_include_is_composed_when_the_host_predicate_matches(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PLATFORM_BASE\n    value: loaded\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        format!(
+            "includes:\n  - path: base.spenv.yaml\n    when:\n      os: {}\n      arch: {}\n",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        ),
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(
+        env.effective_ops()
+            .iter()
+            .any(|op| op.var() == "PLATFORM_BASE")
+    );
+}
+
+#[rstest]
+fn test_conditional_include_is_skipped_when_the_host_predicate_does_not_match(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PLATFORM_BASE\n    value: loaded\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: base.spenv.yaml\n    when:\n      os: not-a-real-os\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(
+        !env.effective_ops()
+            .iter()
+            .any(|op| op.var() == "PLATFORM_BASE")
+    );
+}
+
+#[rstest]
+fn test_include_with_unknown_predicate_key_is_an_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("base.spenv.yaml"), "ops: []\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes:\n  - path: base.spenv.yaml\n    when:\n      platform: linux\n",
+    )
+    .unwrap();
+
+    ComposedEnvironment::resolve(root).expect_err("unknown predicate key should be rejected");
+}
+
+#[rstest]
+fn test_cross_mechanism_duplicate_is_flagged(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let root_spec = root.join(".spenv.yaml");
+    std::fs::write(&root_spec, "inherit: true\n").unwrap();
+
+    let a = root.join("a");
+    std::fs::create_dir(&a).unwrap();
+    std::fs::write(
+        a.join(".spenv.yaml"),
+        "inherit: true\nincludes: ['../.spenv.yaml']\n",
+    )
+    .unwrap();
+
+    // `root/.spenv.yaml` is discovered directly as an ancestor of `a`
+    // via `inherit: true`, and is also pulled in by `a`'s `includes`.
+    let env = ComposedEnvironment::resolve(&a).unwrap();
+    let duplicates = env.cross_mechanism_duplicates();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0], &root_spec.canonicalize().unwrap());
+}
+
+#[test]
+fn test_semantically_equal_ignores_where_the_layers_were_loaded_from() {
+    let one = tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files");
+    let two = tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files");
+
+    let contents = "ops:\n  - op: set\n    var: PATH\n    value: /bin\npackages: [a-pkg]\n";
+    std::fs::write(one.path().join(".spenv.yaml"), contents).unwrap();
+    std::fs::write(two.path().join(".spenv.yaml"), contents).unwrap();
+
+    let env_one = ComposedEnvironment::resolve(one.path()).unwrap();
+    let env_two = ComposedEnvironment::resolve(two.path()).unwrap();
+
+    assert_ne!(
+        env_one, env_two,
+        "file paths differ, so the compositions are not strictly equal"
+    );
+    assert!(env_one.semantically_equal(&env_two));
+}
+
+#[rstest]
+fn test_interpolates_spec_dir_project_root_and_host_env(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let pkg_dir = root.join("pkg");
+    std::fs::create_dir(&pkg_dir).unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "inherit: true\n").unwrap();
+    std::fs::write(
+        pkg_dir.join(".spenv.yaml"),
+        "inherit: true\nops:\n  - op: set\n    var: PKG_BIN\n    value: ${SPENV_SPEC_DIR}/bin\n  - op: set\n    var: PROJECT\n    value: ${SPENV_PROJECT_ROOT}\n  - op: set\n    var: FROM_HOST\n    value: ${ENV:SPENV_TEST_INTERPOLATE_VAR}\nbinds:\n  - source: ${SPENV_SPEC_DIR}/data\n    dest: /data\n",
+    )
+    .unwrap();
+
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::set_var("SPENV_TEST_INTERPOLATE_VAR", "host-value");
+    }
+    let env = ComposedEnvironment::resolve(&pkg_dir).unwrap();
+    unsafe {
+        std::env::remove_var("SPENV_TEST_INTERPOLATE_VAR");
+    }
+
+    let ops = env.effective_ops();
+    assert_eq!(
+        ops.iter().find(|op| op.var() == "PKG_BIN"),
+        Some(&EnvOp::Set {
+            var: "PKG_BIN".to_string(),
+            value: format!("{}/bin", pkg_dir.display()),
+        })
+    );
+    assert_eq!(
+        ops.iter().find(|op| op.var() == "PROJECT"),
+        Some(&EnvOp::Set {
+            var: "PROJECT".to_string(),
+            value: root.display().to_string(),
+        })
+    );
+    assert_eq!(
+        ops.iter().find(|op| op.var() == "FROM_HOST"),
+        Some(&EnvOp::Set {
+            var: "FROM_HOST".to_string(),
+            value: "host-value".to_string(),
+        })
+    );
+    let layer = env.layers.last().unwrap();
+    assert_eq!(layer.binds[0].source, pkg_dir.join("data"));
+}
+
+#[rstest]
+fn test_unresolvable_placeholder_is_a_validation_error(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: FOO\n    value: ${NOT_A_REAL_PLACEHOLDER}\n",
+    )
+    .unwrap();
+
+    let err = ComposedEnvironment::resolve(root).unwrap_err();
+    assert!(matches!(
+        err,
+        ComposeError::ValidationFailed { placeholder, .. } if placeholder == "NOT_A_REAL_PLACEHOLDER"
+    ));
+}
+
+#[rstest]
+fn test_relative_binds_resolve_against_their_own_layer_not_the_first_layer(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    let child_dir = root.join("child");
+    std::fs::create_dir(&child_dir).unwrap();
+
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "binds:\n  - source: parent-data\n    dest: /parent-data\n",
+    )
+    .unwrap();
+    std::fs::write(
+        child_dir.join(".spenv.yaml"),
+        "inherit: true\nbinds:\n  - source: child-data\n    dest: /child-data\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(&child_dir).unwrap();
+
+    let parent_bind = env.layers[0].binds[0].source.clone();
+    let child_bind = env.layers[1].binds[0].source.clone();
+    assert_eq!(parent_bind, root.join("parent-data"));
+    assert_eq!(child_bind, child_dir.join("child-data"));
+}
+
+#[rstest]
+fn test_layers_mode_replace_discards_the_inherited_parent_layer(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let child_dir = root.join("child");
+    std::fs::create_dir(&child_dir).unwrap();
+
+    std::fs::write(root.join(".spenv.yaml"), "packages: ['parent-platform']\n").unwrap();
+    std::fs::write(
+        child_dir.join(".spenv.yaml"),
+        "inherit: true\nlayers_mode: replace\npackages: ['child-platform']\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(&child_dir).unwrap();
+
+    assert_eq!(env.layers.len(), 1, "the parent layer should be dropped");
+    assert_eq!(env.effective_packages(), vec!["child-platform"]);
+}
+
+#[rstest]
+fn test_layers_mode_append_is_the_default(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let child_dir = root.join("child");
+    std::fs::create_dir(&child_dir).unwrap();
+
+    std::fs::write(root.join(".spenv.yaml"), "packages: ['parent-platform']\n").unwrap();
+    std::fs::write(
+        child_dir.join(".spenv.yaml"),
+        "inherit: true\npackages: ['child-platform']\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(&child_dir).unwrap();
+
+    assert_eq!(
+        env.effective_packages(),
+        vec!["parent-platform", "child-platform"]
+    );
+}
+
+#[rstest]
+fn test_effective_lock_policy_is_last_layer_wins(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "lock:\n  enabled: false\n  strict: true\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nlock:\n  enabled: true\n  strict: false\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let policy = env.effective_lock_policy();
+
+    assert!(policy.enabled);
+    assert!(!policy.strict);
+}
+
+#[rstest]
+fn test_effective_lock_policy_defaults_when_no_layer_sets_one(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let policy = env.effective_lock_policy();
+
+    assert!(policy.enabled);
+    assert!(!policy.strict);
+}
+
+#[rstest]
+fn test_conflicts_reports_two_layers_setting_the_same_variable_differently(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /base\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /override\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let conflicts = env.conflicts();
+
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].name, "PROJECT_ROOT");
+    assert_eq!(conflicts[0].values, vec!["/base", "/override"]);
+}
+
+#[rstest]
+fn test_conflicts_treats_set_and_default_as_the_same_kind_of_assignment(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: default\n    var: EDITOR\n    value: vi\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let conflicts = env.conflicts();
+
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].name, "EDITOR");
+}
+
+#[rstest]
+fn test_conflicts_ignores_repeated_identical_values(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /base\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /base\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    assert!(env.conflicts().is_empty());
+}
+
+#[rstest]
+fn test_conflicts_ignores_prepend_and_append(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: prepend\n    var: PATH\n    value: /base/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: append\n    var: PATH\n    value: /override/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    assert!(env.conflicts().is_empty());
+}
+
+#[rstest]
+fn test_to_yaml_with_comments_includes_a_provenance_header(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(
+        &spec_path,
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let yaml = env.to_yaml(false);
+
+    assert!(yaml.contains(&spec_path.display().to_string()));
+    let reparsed: EnvSpec = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(reparsed.ops, env.effective_ops());
+}
+
+#[rstest]
+fn test_to_yaml_stripped_has_no_comments_and_still_reparses(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /bin\npackages: [foo]\npriority: 5\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let yaml = env.to_yaml(true);
+
+    assert!(
+        !yaml.lines().any(|line| line.trim_start().starts_with('#')),
+        "stripped output should contain no comment lines: {yaml}"
+    );
+    let reparsed: EnvSpec = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(reparsed.ops, env.effective_ops());
+    assert_eq!(reparsed.packages, vec!["foo".to_string()]);
+    assert_eq!(reparsed.priority, Some(5));
+}
+
+#[rstest]
+fn test_to_env_manifest_reflects_a_set_and_prepend_composition(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "binds:\n  - source: /host/tools\n    dest: /spfs/tools\nops:\n  \
+         - op: set\n    var: PATH\n    value: /usr/bin\n  \
+         - op: prepend\n    var: PATH\n    value: /opt/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let manifest = env.to_env_manifest();
+
+    assert_eq!(
+        manifest.env.get("PATH").map(String::as_str),
+        Some("/opt/bin:/usr/bin")
+    );
+    assert_eq!(manifest.layers.len(), 1);
+    assert!(manifest.layers[0].digest.is_some());
+    assert_eq!(manifest.binds, vec![PathBuf::from("/spfs/tools")]);
+}
+
+#[rstest]
+fn test_to_env_manifest_reports_tmpfs_content_destinations(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "contents:\n  - tmpfs: /spfs/scratch\n    size: 256m\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let manifest = env.to_env_manifest();
+
+    assert_eq!(manifest.contents, vec![PathBuf::from("/spfs/scratch")]);
+}
+
+#[rstest]
+fn test_fingerprint_placeholder_is_left_for_runtime_to_expand(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: BUILD_TAG\n    value: build-${SPENV_FINGERPRINT}\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let ops = env.effective_ops();
+    assert_eq!(
+        ops.iter().find(|op| op.var() == "BUILD_TAG"),
+        Some(&EnvOp::Set {
+            var: "BUILD_TAG".to_string(),
+            value: "build-${SPENV_FINGERPRINT}".to_string(),
+        })
+    );
+}
+
+#[rstest]
+fn test_descriptions_are_collected_in_composition_order(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "note: base platform\npackages: [base-pkg]\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nnote: project override\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let entries = env.descriptions();
+    let descriptions: Vec<&str> = entries.iter().map(|(_, note)| note.as_str()).collect();
+
+    assert_eq!(descriptions, vec!["base platform", "project override"]);
+}
+
+#[rstest]
+fn test_a_matching_platform_section_is_merged_in_and_a_non_matching_one_is_ignored(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        format!(
+            "packages: [base-pkg]\nplatform:\n  {os}:\n    packages: [{os}-pkg]\n  bogus-os:\n    packages: [bogus-pkg]\n",
+            os = std::env::consts::OS
+        ),
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    assert_eq!(
+        env.effective_packages(),
+        vec![
+            "base-pkg".to_string(),
+            format!("{}-pkg", std::env::consts::OS)
+        ]
+    );
+    assert_eq!(
+        env.active_platform_keys(),
+        vec![std::env::consts::OS.to_string()]
+    );
+}
+
+#[rstest]
+fn test_effective_ops_by_layer_uses_each_layers_own_priority_and_respects_later_excludes(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "priority: 10\nops:\n  - op: set\n    var: BASE\n    value: base\n  - op: set\n    var: KEEP\n    value: keep\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nenvironment_exclude: ['BASE']\nops:\n  - op: set\n    var: PROJECT\n    value: project\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let by_layer = env.effective_ops_by_layer();
+
+    assert_eq!(by_layer.len(), 2);
+    assert_eq!(by_layer[0].0, 10);
+    assert_eq!(
+        by_layer[0].1,
+        vec![EnvOp::Set {
+            var: "KEEP".to_string(),
+            value: "keep".to_string(),
+        }]
+    );
+    // The project layer didn't set its own priority, so it falls back to 50.
+    assert_eq!(by_layer[1].0, 50);
+    assert_eq!(
+        by_layer[1].1,
+        vec![EnvOp::Set {
+            var: "PROJECT".to_string(),
+            value: "project".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_compose_from_yaml_composes_sources_in_order_without_touching_disk() {
+    let sources = vec![
+        (
+            PathBuf::from("base"),
+            "ops:\n  - op: set\n    var: BASE\n    value: base\n".to_string(),
+        ),
+        (
+            PathBuf::from("project"),
+            "ops:\n  - op: set\n    var: PROJECT\n    value: project\n".to_string(),
+        ),
+    ];
+
+    let env = compose_from_yaml(&sources, &MapResolver(HashMap::new())).unwrap();
+
+    assert_eq!(env.layers.len(), 2);
+    assert_eq!(env.layers[0].file_path, PathBuf::from("base"));
+    assert_eq!(env.layers[1].file_path, PathBuf::from("project"));
+    assert_eq!(
+        env.effective_ops(),
+        vec![
+            EnvOp::Set {
+                var: "BASE".to_string(),
+                value: "base".to_string(),
+            },
+            EnvOp::Set {
+                var: "PROJECT".to_string(),
+                value: "project".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_compose_from_yaml_resolves_includes_through_the_resolver() {
+    let sources = vec![(
+        PathBuf::from("project"),
+        "includes: [base]\nops:\n  - op: set\n    var: PROJECT\n    value: project\n".to_string(),
+    )];
+    let resolver = MapResolver(HashMap::from([(
+        PathBuf::from("base"),
+        "ops:\n  - op: set\n    var: BASE\n    value: base\n".to_string(),
+    )]));
+
+    let env = compose_from_yaml(&sources, &resolver).unwrap();
+
+    assert_eq!(env.layers.len(), 2);
+    assert_eq!(env.layers[0].file_path, PathBuf::from("base"));
+    assert_eq!(env.layers[1].file_path, PathBuf::from("project"));
+}
+
+#[test]
+fn test_compose_from_yaml_reports_an_include_the_resolver_cannot_satisfy() {
+    let sources = vec![(
+        PathBuf::from("project"),
+        "includes: [missing]\n".to_string(),
+    )];
+
+    let err = compose_from_yaml(&sources, &MapResolver(HashMap::new())).unwrap_err();
+
+    assert!(matches!(err, ComposeError::IncludeResolutionFailed { .. }));
+}
+
+#[test]
+fn test_compose_from_yaml_skips_an_optional_include_the_resolver_cannot_satisfy() {
+    let sources = vec![(
+        PathBuf::from("project"),
+        "includes:\n  - path: missing\n    optional: true\nops:\n  - op: set\n    var: PROJECT\n    value: project\n".to_string(),
+    )];
+
+    let env = compose_from_yaml(&sources, &MapResolver(HashMap::new())).unwrap();
+
+    assert_eq!(env.layers.len(), 1);
+    assert_eq!(env.layers[0].file_path, PathBuf::from("project"));
+}
+
+#[test]
+fn test_compose_from_yaml_detects_a_circular_include() {
+    let sources = vec![(
+        PathBuf::from("project"),
+        "includes: [project]\n".to_string(),
+    )];
+    let resolver = MapResolver(HashMap::from([(
+        PathBuf::from("project"),
+        "includes: [project]\n".to_string(),
+    )]));
+
+    let err = compose_from_yaml(&sources, &resolver).unwrap_err();
+
+    assert!(matches!(err, ComposeError::CircularInclude(_)));
+}
+
+#[rstest]
+fn test_filesystem_resolver_matches_the_default_resolve_path(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("base.spenv.yaml"), "packages: [base-pkg]\n").unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "includes: [base.spenv.yaml]\n").unwrap();
+
+    let via_default = ComposedEnvironment::resolve(root).unwrap();
+    let via_resolver = ComposedEnvironment::resolve_with_resolver(
+        root,
+        DiscoveryOptions::default(),
+        &FilesystemResolver,
+    )
+    .unwrap();
+
+    assert!(via_default.semantically_equal(&via_resolver));
+}
+
+#[rstest]
+fn test_resolve_with_resolver_satisfies_includes_from_a_mock_store(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [mock-base]\nops:\n  - op: set\n    var: PROJECT\n    value: project\n",
+    )
+    .unwrap();
+    let resolver = MapResolver(HashMap::from([(
+        PathBuf::from("mock-base"),
+        "ops:\n  - op: set\n    var: BASE\n    value: base\n".to_string(),
+    )]));
+
+    let env =
+        ComposedEnvironment::resolve_with_resolver(root, DiscoveryOptions::default(), &resolver)
+            .unwrap();
+
+    assert_eq!(
+        env.effective_ops(),
+        vec![
+            EnvOp::Set {
+                var: "BASE".to_string(),
+                value: "base".to_string(),
+            },
+            EnvOp::Set {
+                var: "PROJECT".to_string(),
+                value: "project".to_string(),
+            },
+        ]
+    );
+}
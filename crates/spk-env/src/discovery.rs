@@ -0,0 +1,431 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Locating `.spenv.yaml` spec files on disk.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::error::LoadSpecError;
+use crate::spec::{EnvOp, EnvSpec};
+
+#[cfg(test)]
+#[path = "discovery_test.rs"]
+mod discovery_test;
+
+/// The environment variable consulted for [`DiscoveryOptions::max_depth`]
+/// when a caller doesn't explicitly set one (e.g. a bare CLI invocation).
+pub const MAX_DEPTH_ENV_VAR: &str = "SPENV_MAX_DEPTH";
+
+/// The environment variable consulted for [`DiscoveryOptions::root_markers`]
+/// when a caller doesn't explicitly set any, as a colon-separated list.
+pub const STOP_AT_ENV_VAR: &str = "SPENV_STOP_AT";
+
+/// The environment variable consulted for [`DiscoveryOptions::filename`]
+/// when a caller doesn't explicitly set one.
+pub const FILENAME_ENV_VAR: &str = "SPENV_FILENAME";
+
+/// The environment variable consulted for
+/// [`DiscoveryOptions::system_defaults`] when a caller doesn't
+/// explicitly set one.
+pub const SYSTEM_DEFAULTS_ENV_VAR: &str = "SPENV_SYSTEM_DEFAULTS";
+
+/// The environment variable consulted for
+/// [`DiscoveryOptions::system_default_path`] when a caller doesn't
+/// explicitly set one.
+pub const SYSTEM_DEFAULT_PATH_ENV_VAR: &str = "SPENV_SYSTEM_DEFAULT_PATH";
+
+/// The environment variable consulted for
+/// [`DiscoveryOptions::trusted_only`] when a caller doesn't
+/// explicitly set one.
+pub const TRUSTED_ONLY_ENV_VAR: &str = "SPENV_TRUSTED_ONLY";
+
+/// The machine-wide default spec consulted when
+/// [`DiscoveryOptions::system_defaults`] is set: the default value of
+/// [`DiscoveryOptions::system_default_path`].
+pub const SYSTEM_DEFAULT_PATH: &str = "/etc/spenv/default.spenv.yaml";
+
+/// Options controlling how [`discover_in_tree`] walks the directory tree.
+#[derive(Debug, Clone)]
+pub struct DiscoveryOptions {
+    /// The maximum number of parent directories to visit, counted
+    /// from the start directory. `None` means no limit. `Some(0)`
+    /// means only the start directory is considered, equivalent to
+    /// treating every spec as if it had `inherit: false`.
+    pub max_depth: Option<usize>,
+    /// File or directory names that mark the top of a project. Once a
+    /// directory containing any of these is visited, the walk stops
+    /// ascending, even if the spec found there has `inherit: true`.
+    pub root_markers: Vec<String>,
+    /// The file name to look for in each directory, in place of
+    /// [`EnvSpec::FILE_NAME`].
+    pub filename: String,
+    /// When true, compose a machine-wide default spec (see
+    /// [`DiscoveryOptions::system_default_path`]) as the
+    /// lowest-precedence layer, before any discovered or overlay
+    /// layers. A missing file at that path is not an error.
+    pub system_defaults: bool,
+    /// The path consulted for the system default spec when
+    /// [`DiscoveryOptions::system_defaults`] is set, in place of
+    /// [`SYSTEM_DEFAULT_PATH`].
+    pub system_default_path: PathBuf,
+    /// When true, an inherited spec (one found in an ancestor
+    /// directory while walking up under `inherit: true`) that is
+    /// world-writable or owned by a UID other than the current user
+    /// or root fails discovery with [`LoadSpecError::Untrusted`]
+    /// instead of only being warned about. Unix-specific; a no-op
+    /// elsewhere.
+    pub trusted_only: bool,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            root_markers: Vec::new(),
+            filename: EnvSpec::FILE_NAME.to_string(),
+            system_defaults: false,
+            system_default_path: PathBuf::from(SYSTEM_DEFAULT_PATH),
+            trusted_only: false,
+        }
+    }
+}
+
+impl DiscoveryOptions {
+    /// Build options from the `SPENV_MAX_DEPTH`, `SPENV_STOP_AT`,
+    /// `SPENV_FILENAME`, `SPENV_SYSTEM_DEFAULTS`,
+    /// `SPENV_SYSTEM_DEFAULT_PATH` and `SPENV_TRUSTED_ONLY`
+    /// environment variables, if set.
+    pub fn from_env() -> Self {
+        let max_depth = std::env::var(MAX_DEPTH_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok());
+        let root_markers = std::env::var(STOP_AT_ENV_VAR)
+            .ok()
+            .map(|v| v.split(':').map(str::to_owned).collect())
+            .unwrap_or_default();
+        let filename =
+            std::env::var(FILENAME_ENV_VAR).unwrap_or_else(|_| EnvSpec::FILE_NAME.to_string());
+        let system_defaults = std::env::var(SYSTEM_DEFAULTS_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+        let system_default_path = std::env::var(SYSTEM_DEFAULT_PATH_ENV_VAR)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(SYSTEM_DEFAULT_PATH));
+        let trusted_only = std::env::var(TRUSTED_ONLY_ENV_VAR)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(false);
+        Self {
+            max_depth,
+            root_markers,
+            filename,
+            system_defaults,
+            system_default_path,
+            trusted_only,
+        }
+    }
+}
+
+/// Load the machine-wide default spec from `path`, if present.
+///
+/// Unlike [`EnvSpec::load_file`], a missing file is not an error: it
+/// just means no system defaults are installed, so `None` is returned.
+pub fn load_system_default(path: &Path) -> Result<Option<EnvSpec>, LoadSpecError> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    EnvSpec::load_file(path).map(Some)
+}
+
+/// The sibling "local override" filename for a discovered spec named
+/// `filename`, by inserting `.local` immediately before its final
+/// extension, e.g. `.spenv.yaml` -> `.spenv.local.yaml`. When present
+/// next to a discovered spec, the local override is composed in
+/// immediately after it, for machine- or developer-specific tweaks
+/// that shouldn't be checked in.
+fn local_filename_for(filename: &str) -> String {
+    match filename.rfind('.') {
+        Some(idx) if idx > 0 => format!("{}.local{}", &filename[..idx], &filename[idx..]),
+        _ => format!("{filename}.local"),
+    }
+}
+
+/// Returns the first marker from `markers` that is present in `dir`, if any.
+fn find_root_marker(dir: &Path, markers: &[String]) -> Option<String> {
+    markers
+        .iter()
+        .find(|marker| dir.join(marker).exists())
+        .cloned()
+}
+
+/// Check an inherited spec file's ownership and permissions, warning
+/// on stderr when it is world-writable or owned by a UID other than
+/// the current user or root, and refusing it outright with
+/// [`LoadSpecError::Untrusted`] when `trusted_only` is set.
+///
+/// A parent `.spenv.yaml` composed in purely because `inherit: true`
+/// was set is otherwise trusted implicitly, which lets anyone who can
+/// write to an ancestor directory inject layers into every
+/// subdirectory's environment. Unix-specific: a no-op on other
+/// platforms, since ownership and mode bits aren't meaningful there.
+///
+/// Callers should pass `trusted_only: true` even when the caller
+/// didn't ask for it, for any spec whose contribution can run
+/// arbitrary code on its own, such as one declaring an
+/// [`EnvOp::Source`] or [`EnvOp::PathRemove`] op; see
+/// [`discover_specs_traced`]'s call site.
+#[cfg(unix)]
+fn check_inherited_spec_trust(path: &Path, trusted_only: bool) -> Result<(), LoadSpecError> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return Ok(());
+    };
+    let world_writable = metadata.mode() & 0o002 != 0;
+    let owner_uid = metadata.uid();
+    let current_uid = nix::unistd::getuid().as_raw();
+    let foreign_owner = owner_uid != current_uid && owner_uid != 0;
+
+    let reason = if world_writable && foreign_owner {
+        Some(format!("world-writable and owned by uid {owner_uid}"))
+    } else if world_writable {
+        Some("world-writable".to_string())
+    } else if foreign_owner {
+        Some(format!("owned by uid {owner_uid}"))
+    } else {
+        None
+    };
+
+    let Some(reason) = reason else {
+        return Ok(());
+    };
+    if trusted_only {
+        return Err(LoadSpecError::Untrusted {
+            path: path.to_owned(),
+            reason,
+        });
+    }
+    eprintln!("warning: inherited spec {path:?} is {reason}, treating it as trusted anyway");
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_inherited_spec_trust(_path: &Path, _trusted_only: bool) -> Result<(), LoadSpecError> {
+    Ok(())
+}
+
+/// A single directory visited during a [`discover_specs_traced`] walk.
+#[derive(Debug, Clone)]
+pub struct DiscoveryVisit {
+    /// The directory that was visited
+    pub dir: PathBuf,
+    /// Whether a spec file was found there
+    pub found_spec: bool,
+}
+
+/// Why a [`discover_specs_traced`] walk stopped ascending.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The last visited spec did not set `inherit: true`.
+    NotInherited,
+    /// The last visited directory contained this root marker.
+    RootMarker(String),
+    /// `options.max_depth` parent directories were visited.
+    MaxDepth,
+    /// The walk reached the top of the filesystem, or the next
+    /// parent directory has no spec file.
+    TreeTop,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotInherited => write!(f, "spec did not set inherit: true"),
+            Self::RootMarker(marker) => write!(f, "found root marker {marker:?}"),
+            Self::MaxDepth => write!(f, "reached max_depth"),
+            Self::TreeTop => write!(f, "reached the top of the tree"),
+        }
+    }
+}
+
+/// A record of a [`discover_specs_traced`] walk, useful for debugging
+/// why a particular spec was or wasn't picked up.
+#[derive(Debug, Clone)]
+pub struct DiscoveryTrace {
+    /// Every directory visited, in walk order (innermost first)
+    pub visits: Vec<DiscoveryVisit>,
+    /// Why the walk stopped ascending
+    pub stop_reason: StopReason,
+}
+
+impl std::fmt::Display for DiscoveryTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for visit in &self.visits {
+            writeln!(
+                f,
+                "{}: {}",
+                visit.dir.display(),
+                if visit.found_spec {
+                    "spec found"
+                } else {
+                    "no spec"
+                }
+            )?;
+        }
+        write!(f, "stopped: {}", self.stop_reason)
+    }
+}
+
+/// Find the directory containing a spec file named `filename`, starting
+/// at `cwd` and walking up through parent directories until one is found.
+pub fn resolve_start_path<P: AsRef<Path>>(
+    cwd: P,
+    filename: &str,
+) -> Result<PathBuf, LoadSpecError> {
+    let mut candidate = cwd.as_ref().to_owned();
+    loop {
+        if candidate.join(filename).is_file() {
+            return Ok(candidate);
+        }
+        if !candidate.pop() {
+            break;
+        }
+    }
+    Err(LoadSpecError::NotFound(cwd.as_ref().to_owned()))
+}
+
+/// Discover the chain of specs that apply to a starting directory.
+///
+/// Loads the spec found directly in `start`, and if it (or any of
+/// its ancestors) sets `inherit: true`, continues walking up parent
+/// directories loading additional specs. The returned specs are
+/// ordered from the outermost ancestor to the innermost, so that
+/// later entries take precedence when composed.
+///
+/// At every level visited, if a sibling local override file is also
+/// present (see [`local_filename_for`]), it's inserted into the
+/// returned chain immediately after that level's own spec, so it
+/// takes precedence over everything from that level but is still
+/// overridden by any level closer to `start`.
+///
+/// The walk stops after visiting `options.max_depth` directories
+/// beyond the start directory, if set, returning whatever specs were
+/// found up to that point rather than erroring. It also stops as soon
+/// as it visits a directory containing one of `options.root_markers`,
+/// regardless of that directory's spec having `inherit: true`.
+///
+/// Each visited directory is canonicalized and tracked for the
+/// duration of the walk, so a symlink loop that leads back to a
+/// directory already visited is reported as
+/// [`LoadSpecError::SymlinkCycle`] rather than looping forever. This
+/// is separate from the include-cycle detection in
+/// [`crate::compose`], which tracks spec files, not tree-walk
+/// directories.
+pub fn discover_in_tree<P: AsRef<Path>>(
+    start: P,
+    options: DiscoveryOptions,
+) -> Result<Vec<EnvSpec>, LoadSpecError> {
+    discover_specs_traced(start, options).map(|(chain, _trace)| chain)
+}
+
+/// The result of [`discover_specs_detailed`]: the discovered chain of
+/// specs, plus the root of the inheritance cascade they were found in.
+#[derive(Debug, Clone)]
+pub struct DiscoveryResult {
+    /// The discovered specs, outermost ancestor first
+    pub specs: Vec<EnvSpec>,
+    /// The directory of the outermost discovered spec: the top of the
+    /// inheritance cascade. `None` if discovery found no specs at all.
+    pub root_source: Option<PathBuf>,
+}
+
+/// Discover the chain of specs that apply to a starting directory,
+/// same as [`discover_in_tree`], but also report [`DiscoveryResult::root_source`]:
+/// the directory of the outermost spec reached. This reports the
+/// "top-most discovered spec directory" without the full
+/// [`DiscoveryTrace`] that [`discover_specs_traced`] produces.
+pub fn discover_specs_detailed<P: AsRef<Path>>(
+    start: P,
+    options: DiscoveryOptions,
+) -> Result<DiscoveryResult, LoadSpecError> {
+    let specs = discover_in_tree(start, options)?;
+    let root_source = specs
+        .first()
+        .map(|spec| spec.file_path.parent().unwrap_or(Path::new("")).to_owned());
+    Ok(DiscoveryResult { specs, root_source })
+}
+
+/// Discover the chain of specs that apply to a starting directory,
+/// same as [`discover_in_tree`], but also return a [`DiscoveryTrace`]
+/// recording every directory visited and why the walk stopped.
+pub fn discover_specs_traced<P: AsRef<Path>>(
+    start: P,
+    options: DiscoveryOptions,
+) -> Result<(Vec<EnvSpec>, DiscoveryTrace), LoadSpecError> {
+    let mut chain = Vec::new();
+    let mut visits = Vec::new();
+    let mut dir = start.as_ref().to_owned();
+    let mut depth = 0;
+    let mut seen = HashSet::new();
+    let stop_reason = loop {
+        let canonical = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        if !seen.insert(canonical) {
+            return Err(LoadSpecError::SymlinkCycle(dir.clone()));
+        }
+
+        let spec = EnvSpec::load_dir_named(&dir, &options.filename)?;
+        if depth > 0 {
+            // `Source` runs an arbitrary file when the rendered
+            // startup script executes it, and `PathRemove` is
+            // rendered as a `sed` pipeline run under command
+            // substitution, so both can execute code of the
+            // inherited spec's choosing and warrant the same
+            // escalation as an explicit `--trusted-only`.
+            let runs_untrusted_code = spec
+                .ops
+                .iter()
+                .any(|op| matches!(op, EnvOp::Source { .. } | EnvOp::PathRemove { .. }));
+            check_inherited_spec_trust(
+                &spec.file_path,
+                options.trusted_only || runs_untrusted_code,
+            )?;
+        }
+        visits.push(DiscoveryVisit {
+            dir: dir.clone(),
+            found_spec: true,
+        });
+        let inherit = spec.inherit;
+        let local_filename = local_filename_for(&options.filename);
+        if dir.join(&local_filename).is_file() {
+            chain.push(EnvSpec::load_dir_named(&dir, &local_filename)?);
+        }
+        chain.push(spec);
+        if let Some(marker) = find_root_marker(&dir, &options.root_markers) {
+            break StopReason::RootMarker(marker);
+        }
+        if !inherit {
+            break StopReason::NotInherited;
+        }
+        if options.max_depth.is_some_and(|max| depth >= max) {
+            break StopReason::MaxDepth;
+        }
+        if !dir.pop() {
+            break StopReason::TreeTop;
+        }
+        if !dir.join(&options.filename).is_file() {
+            break StopReason::TreeTop;
+        }
+        depth += 1;
+    };
+    chain.reverse();
+    Ok((
+        chain,
+        DiscoveryTrace {
+            visits,
+            stop_reason,
+        },
+    ))
+}
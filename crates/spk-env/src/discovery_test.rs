@@ -0,0 +1,364 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{
+    DiscoveryOptions, StopReason, discover_in_tree, discover_specs_detailed, discover_specs_traced,
+    load_system_default,
+};
+use crate::error::LoadSpecError;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+/// Builds `root/a/b/c`, each with an inheriting spec, so discovery
+/// from `c` would normally find four layers.
+fn make_deep_tree(root: &std::path::Path) -> std::path::PathBuf {
+    let leaf = root.join("a").join("b").join("c");
+    std::fs::create_dir_all(&leaf).unwrap();
+    for dir in [
+        root.to_path_buf(),
+        root.join("a"),
+        root.join("a/b"),
+        leaf.clone(),
+    ] {
+        std::fs::write(dir.join(".spenv.yaml"), "inherit: true\n").unwrap();
+    }
+    leaf
+}
+
+#[rstest]
+fn test_discover_in_tree_unbounded(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+    assert_eq!(chain.len(), 4);
+}
+
+#[rstest]
+fn test_discover_in_tree_max_depth_zero_is_start_only(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    let chain = discover_in_tree(
+        &leaf,
+        DiscoveryOptions {
+            max_depth: Some(0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.len(), 1);
+}
+
+#[rstest]
+fn test_discover_in_tree_max_depth_caps_without_erroring(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    let chain = discover_in_tree(
+        &leaf,
+        DiscoveryOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.len(), 3);
+}
+
+/// A `.git` marker in `root/a` should stop the walk there, even
+/// though that directory's spec has `inherit: true`.
+#[rstest]
+fn test_discover_in_tree_stops_at_root_marker(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    std::fs::create_dir(tmpdir.path().join("a").join(".git")).unwrap();
+    let chain = discover_in_tree(
+        &leaf,
+        DiscoveryOptions {
+            root_markers: vec![".git".to_string()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.len(), 3);
+}
+
+/// `root/a/loop` is a symlink back to `root`, so ascending from it
+/// eventually revisits `root`'s canonical path a second time.
+#[rstest]
+fn test_discover_in_tree_detects_symlink_cycle(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let a = root.join("a");
+    std::fs::create_dir(&a).unwrap();
+    std::fs::write(root.join(".spenv.yaml"), "inherit: true\n").unwrap();
+    std::fs::write(a.join(".spenv.yaml"), "inherit: true\n").unwrap();
+    std::os::unix::fs::symlink("..", a.join("loop")).unwrap();
+
+    let err = discover_in_tree(a.join("loop"), DiscoveryOptions::default()).unwrap_err();
+    assert!(matches!(err, LoadSpecError::SymlinkCycle(_)));
+}
+
+/// `discover_in_tree`'s cycle detection is a local `HashSet` built
+/// fresh on every call, not shared process-wide state, so two
+/// discoveries running concurrently can't clear or pollute each
+/// other's visited set. Two independent symlink-cycle trees, walked
+/// from separate threads at the same time, should each report their
+/// own cycle every time.
+#[rstest]
+fn test_concurrent_discoveries_do_not_interfere(tmpdir: tempfile::TempDir) {
+    fn make_cyclic_tree(root: &std::path::Path) -> std::path::PathBuf {
+        let a = root.join("a");
+        std::fs::create_dir(&a).unwrap();
+        std::fs::write(root.join(".spenv.yaml"), "inherit: true\n").unwrap();
+        std::fs::write(a.join(".spenv.yaml"), "inherit: true\n").unwrap();
+        std::os::unix::fs::symlink("..", a.join("loop")).unwrap();
+        a.join("loop")
+    }
+
+    let one = make_cyclic_tree(tmpdir.path());
+    let other_dir = tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .unwrap();
+    let two = make_cyclic_tree(other_dir.path());
+
+    let handles: Vec<_> = [one, two]
+        .into_iter()
+        .map(|start| {
+            std::thread::spawn(move || discover_in_tree(start, DiscoveryOptions::default()))
+        })
+        .collect();
+
+    for handle in handles {
+        let err = handle.join().unwrap().unwrap_err();
+        assert!(matches!(err, LoadSpecError::SymlinkCycle(_)));
+    }
+}
+
+#[rstest]
+fn test_discover_in_tree_uses_configured_filename(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".myenv.yaml"), "inherit: false\n").unwrap();
+
+    let chain = discover_in_tree(
+        root,
+        DiscoveryOptions {
+            filename: ".myenv.yaml".to_string(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.len(), 1);
+
+    let err = discover_in_tree(root, DiscoveryOptions::default()).unwrap_err();
+    assert!(matches!(err, LoadSpecError::NotFound(_)));
+}
+
+#[rstest]
+fn test_discover_specs_traced_records_visits_and_stop_reason(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    let (chain, trace) = discover_specs_traced(&leaf, DiscoveryOptions::default()).unwrap();
+
+    assert_eq!(chain.len(), 4);
+    assert_eq!(trace.visits.len(), 4);
+    assert!(trace.visits.iter().all(|visit| visit.found_spec));
+    assert_eq!(trace.stop_reason, StopReason::TreeTop);
+}
+
+#[rstest]
+fn test_discover_specs_detailed_reports_the_outermost_ancestor_as_root(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let leaf = make_deep_tree(root);
+
+    let result = discover_specs_detailed(&leaf, DiscoveryOptions::default()).unwrap();
+
+    assert_eq!(result.specs.len(), 4);
+    assert_eq!(result.root_source, Some(root.to_path_buf()));
+}
+
+#[rstest]
+fn test_load_system_default_returns_none_when_missing(tmpdir: tempfile::TempDir) {
+    let path = tmpdir.path().join("default.spenv.yaml");
+    assert!(load_system_default(&path).unwrap().is_none());
+}
+
+#[rstest]
+fn test_load_system_default_loads_the_spec_when_present(tmpdir: tempfile::TempDir) {
+    let path = tmpdir.path().join("default.spenv.yaml");
+    std::fs::write(
+        &path,
+        "ops:\n  - op: set\n    var: SYSTEM\n    value: \"1\"\n",
+    )
+    .unwrap();
+
+    let spec = load_system_default(&path).unwrap().unwrap();
+    assert_eq!(spec.ops.len(), 1);
+}
+
+#[rstest]
+fn test_a_local_override_is_inserted_right_after_its_own_level(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    std::fs::write(
+        leaf.join(".spenv.local.yaml"),
+        "ops:\n  - op: set\n    var: LOCAL\n    value: leaf\n",
+    )
+    .unwrap();
+
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+
+    // outermost..innermost, with the leaf's own local override
+    // composed in right after the leaf's own spec, last overall
+    assert_eq!(chain.len(), 5);
+    assert_eq!(chain.last().unwrap().ops.len(), 1);
+}
+
+#[rstest]
+fn test_local_overrides_compose_at_every_inherited_level(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let leaf = make_deep_tree(root);
+    std::fs::write(
+        root.join(".spenv.local.yaml"),
+        "ops:\n  - op: set\n    var: LOCAL\n    value: root\n",
+    )
+    .unwrap();
+    std::fs::write(
+        leaf.join(".spenv.local.yaml"),
+        "ops:\n  - op: set\n    var: LOCAL\n    value: leaf\n",
+    )
+    .unwrap();
+
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+
+    // root, root.local, a, a/b, leaf, leaf.local: 6 specs total
+    assert_eq!(chain.len(), 6);
+    assert_eq!(
+        chain[1].ops.first().unwrap().var(),
+        "LOCAL",
+        "root's local override should sit right after root's own spec"
+    );
+    assert_eq!(chain.last().unwrap().ops.first().unwrap().var(), "LOCAL");
+}
+
+#[rstest]
+fn test_a_missing_local_override_is_not_an_error(tmpdir: tempfile::TempDir) {
+    let leaf = make_deep_tree(tmpdir.path());
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+    assert_eq!(chain.len(), 4);
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_a_world_writable_inherited_spec_is_allowed_with_only_a_warning(tmpdir: tempfile::TempDir) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    let parent_spec = tmpdir.path().join(".spenv.yaml");
+    std::fs::set_permissions(&parent_spec, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+    assert_eq!(chain.len(), 4);
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_trusted_only_refuses_a_world_writable_inherited_spec(tmpdir: tempfile::TempDir) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    let parent_spec = tmpdir.path().join(".spenv.yaml");
+    std::fs::set_permissions(&parent_spec, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+    let err = discover_in_tree(
+        &leaf,
+        DiscoveryOptions {
+            trusted_only: true,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, LoadSpecError::Untrusted { .. }));
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_a_source_op_in_an_untrusted_inherited_spec_is_refused_even_without_trusted_only(
+    tmpdir: tempfile::TempDir,
+) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    let parent_spec = tmpdir.path().join(".spenv.yaml");
+    std::fs::write(
+        &parent_spec,
+        "inherit: true\nops:\n  - op: source\n    source: /opt/tool/env.sh\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&parent_spec, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+    let err = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap_err();
+    assert!(matches!(err, LoadSpecError::Untrusted { .. }));
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_a_path_remove_op_in_an_untrusted_inherited_spec_is_refused_even_without_trusted_only(
+    tmpdir: tempfile::TempDir,
+) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    let parent_spec = tmpdir.path().join(".spenv.yaml");
+    std::fs::write(
+        &parent_spec,
+        "inherit: true\nops:\n  - op: path_remove\n    var: PATH\n    value: /opt/tool/bin\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&parent_spec, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+    let err = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap_err();
+    assert!(matches!(err, LoadSpecError::Untrusted { .. }));
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_a_source_op_in_the_start_directorys_own_spec_is_always_allowed(tmpdir: tempfile::TempDir) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    let own_spec = leaf.join(".spenv.yaml");
+    std::fs::write(
+        &own_spec,
+        "inherit: true\nops:\n  - op: source\n    source: /opt/tool/env.sh\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&own_spec, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+    let chain = discover_in_tree(&leaf, DiscoveryOptions::default()).unwrap();
+    assert_eq!(chain.len(), 4);
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_trusted_only_does_not_check_the_start_directorys_own_spec(tmpdir: tempfile::TempDir) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let leaf = make_deep_tree(tmpdir.path());
+    std::fs::set_permissions(
+        leaf.join(".spenv.yaml"),
+        std::fs::Permissions::from_mode(0o666),
+    )
+    .unwrap();
+
+    let chain = discover_in_tree(
+        &leaf,
+        DiscoveryOptions {
+            trusted_only: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(chain.len(), 4);
+}
@@ -0,0 +1,405 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Errors reported by this crate.
+
+use std::path::PathBuf;
+
+use crate::runtime::AsUser;
+
+/// Wraps a [`serde_yaml::Error`] so it can be turned into a
+/// [`format_serde_error::SerdeError`], which `serde_yaml` 0.9 does not
+/// support directly.
+pub(crate) struct SerdeYamlError(pub serde_yaml::Error);
+
+impl From<SerdeYamlError> for format_serde_error::ErrorTypes {
+    fn from(err: SerdeYamlError) -> Self {
+        let location = err.0.location();
+        Self::Custom {
+            error: Box::new(err.0),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column().saturating_sub(1)),
+        }
+    }
+}
+
+/// Errors that can occur when loading an environment spec file.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum LoadSpecError {
+    /// No spec file was found at the given path.
+    #[error("no spec file found at {0:?}")]
+    #[diagnostic(code(spenv::spec_not_found))]
+    NotFound(PathBuf),
+    /// Error reading the spec file from disk.
+    #[error("failed to read spec file {path:?}")]
+    ReadFailed {
+        /// The file that could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error deserializing the spec file contents.
+    #[error("failed to parse spec file {path:?}")]
+    InvalidYaml {
+        /// The file that failed to parse
+        path: PathBuf,
+        /// The underlying error
+        source: Box<format_serde_error::SerdeError>,
+    },
+    /// A symlink loop caused the same directory to be visited twice
+    /// while walking up the tree during discovery.
+    #[error("symlink cycle detected: {0:?} was already visited during this walk")]
+    SymlinkCycle(PathBuf),
+    /// An inherited spec file failed a `--trusted-only` ownership or
+    /// permission check.
+    #[error("refusing to inherit untrusted spec {path:?}: {reason}")]
+    #[diagnostic(code(spenv::untrusted_spec))]
+    Untrusted {
+        /// The inherited spec file that failed the check
+        path: PathBuf,
+        /// Why the spec was considered untrusted
+        reason: String,
+    },
+}
+
+/// Errors that can occur while composing a set of specs into
+/// a single [`crate::ComposedEnvironment`].
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum ComposeError {
+    /// Error loading one of the specs taking part in composition.
+    #[error(transparent)]
+    #[diagnostic(forward(0))]
+    LoadSpecError(#[from] LoadSpecError),
+    /// An include referenced a spec that could not be found.
+    #[error("spec {from:?} includes {include:?}, which could not be found")]
+    IncludeNotFound {
+        /// The spec that declared the include
+        from: PathBuf,
+        /// The include path that could not be resolved
+        include: PathBuf,
+    },
+    /// An include glob pattern did not match any files.
+    #[error("spec {from:?} includes glob {pattern:?}, which matched no files")]
+    IncludeGlobEmpty {
+        /// The spec that declared the include
+        from: PathBuf,
+        /// The glob pattern that matched nothing
+        pattern: String,
+    },
+    /// An include glob pattern was not valid glob syntax.
+    #[error("spec {from:?} includes {pattern:?}, which is not a valid glob pattern")]
+    InvalidGlob {
+        /// The spec that declared the include
+        from: PathBuf,
+        /// The invalid glob pattern
+        pattern: String,
+        /// The underlying error
+        source: glob::PatternError,
+    },
+    /// Two or more specs, directly or transitively, include each other.
+    #[error("circular include detected: {0:?}")]
+    CircularInclude(PathBuf),
+    /// A `remote-includes` URL could not be fetched, and no
+    /// previously cached copy was available to fall back to.
+    #[cfg(feature = "remote-includes")]
+    #[error("failed to fetch remote include {url:?}: {error}")]
+    IncludeFetchFailed {
+        /// The URL that could not be fetched
+        url: String,
+        /// A human-readable description of what went wrong
+        error: String,
+    },
+    /// An [`crate::compose::IncludeResolver`] failed to resolve an
+    /// `includes` entry into a spec, e.g. [`crate::compose::FilesystemResolver`]
+    /// failing to read it from disk.
+    #[error("could not resolve include {include:?}: {reason}")]
+    IncludeResolutionFailed {
+        /// The include entry, as originally written, that could not
+        /// be resolved
+        include: String,
+        /// A human-readable description of what went wrong
+        reason: String,
+    },
+    /// A layer's value referenced a `${...}` template variable that
+    /// is neither a known spenv built-in nor a set `ENV:` host variable.
+    #[error("{layer:?}: could not resolve {placeholder:?} in {key}")]
+    ValidationFailed {
+        /// The spec that declared the unresolvable value
+        layer: PathBuf,
+        /// The field the unresolvable value came from
+        key: String,
+        /// The placeholder name that could not be resolved
+        placeholder: String,
+    },
+}
+
+/// Errors that can occur when loading a lock file.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum LoadLockError {
+    /// Error reading the lock file from disk.
+    #[error("failed to read lock file {path:?}")]
+    ReadFailed {
+        /// The file that could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error deserializing the lock file contents.
+    #[error("failed to parse lock file {path:?}")]
+    InvalidYaml {
+        /// The file that failed to parse
+        path: PathBuf,
+        /// The underlying error
+        source: serde_yaml::Error,
+    },
+}
+
+/// Errors that can occur when writing a lock file.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum SaveLockError {
+    /// Error serializing the lock to yaml.
+    #[error("failed to serialize lock file")]
+    Serialize(serde_yaml::Error),
+    /// Error writing the lock file to disk.
+    #[error("failed to write lock file {path:?}")]
+    WriteFailed {
+        /// The file that could not be written
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+}
+
+/// Errors that can occur when serializing a spec back to YAML.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum SaveSpecError {
+    /// Error serializing the spec to yaml.
+    #[error("failed to serialize spec to yaml")]
+    Serialize(#[from] serde_yaml::Error),
+}
+
+/// Errors that can occur when migrating a lock file between
+/// [`crate::lock::LockApiVersion`]s.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum MigrateLockError {
+    /// Error loading the lock file to migrate.
+    #[error(transparent)]
+    #[diagnostic(forward(0))]
+    LoadLockError(#[from] LoadLockError),
+    /// The lock file declares an `api_version` that this build of
+    /// `spenv` does not know how to migrate.
+    #[error("unsupported lock api_version {0:?}, cannot migrate")]
+    UnsupportedVersion(String),
+}
+
+/// A way a lock file can be internally inconsistent, found by
+/// [`crate::lock::EnvLock::validate`] without needing a repository or
+/// a composed environment to check it against.
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum LockValidationError {
+    /// A recorded source's content hash is empty.
+    #[error("source {path:?} has an empty content hash")]
+    EmptySourceHash {
+        /// The source whose hash is empty
+        path: PathBuf,
+    },
+    /// A recorded source's content hash isn't a well-formed
+    /// [`crate::lock::hash_contents`] digest (16 lowercase hex digits).
+    #[error("source {path:?} has a malformed content hash {hash:?}")]
+    MalformedSourceHash {
+        /// The source whose hash is malformed
+        path: PathBuf,
+        /// The malformed hash value
+        hash: String,
+    },
+    /// A recorded source has an empty path.
+    #[error("a recorded source has an empty path")]
+    EmptySourcePath,
+    /// `generated_at` is further in the future than the lock's load
+    /// time, which is impossible for a lock that was actually
+    /// generated in the past.
+    #[error("generated_at {generated_at} is in the future (now is {now})")]
+    TimestampInFuture {
+        /// The lock's recorded generation time
+        generated_at: u64,
+        /// The time the lock was validated
+        now: u64,
+    },
+}
+
+/// Errors that can occur when reading or writing the solution cache.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum CacheError {
+    /// Error creating the cache directory.
+    #[error("failed to create cache directory {path:?}")]
+    CreateDirFailed {
+        /// The directory that could not be created
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error reading a cache entry from disk.
+    #[error("failed to read cache entry {path:?}")]
+    ReadFailed {
+        /// The file that could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error writing a cache entry to disk.
+    #[error("failed to write cache entry {path:?}")]
+    WriteFailed {
+        /// The file that could not be written
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error serializing a cache entry.
+    #[error("failed to serialize cache entry")]
+    Serialize(serde_yaml::Error),
+    /// Error removing an expired cache entry from disk.
+    #[error("failed to remove cache entry {path:?}")]
+    RemoveFailed {
+        /// The file that could not be removed
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// No XDG cache directory could be determined for this platform.
+    #[error("could not determine a cache directory for this platform")]
+    NoCacheDir,
+}
+
+/// Errors returned when computing stats over a composed environment.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum StatsError {
+    /// The requested count dimension is not recognized.
+    #[error(
+        "unknown --count-only dimension {0:?}, expected one of: layers, unique-layers, files, env-ops, binds, packages"
+    )]
+    UnknownDimension(String),
+}
+
+/// Errors that can occur when resolving a named `--overlay` spec.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum OverlayError {
+    /// No home directory could be determined to locate the default
+    /// overlays directory.
+    #[error("could not determine a home directory to locate overlays")]
+    NoHomeDir,
+    /// The requested overlay name has no matching file in the overlays directory.
+    #[error(
+        "unknown overlay {name:?}, available overlays: {}{}",
+        available.join(", "),
+        suggestion_message(suggestions)
+    )]
+    NotFound {
+        /// The overlay name that was requested
+        name: String,
+        /// The overlay names that do exist
+        available: Vec<String>,
+        /// The names in `available` closest to `name` by edit distance,
+        /// most similar first, for a "did you mean" hint
+        suggestions: Vec<String>,
+    },
+    /// The overlay file exists but failed to load.
+    #[error("failed to load overlay {name:?}")]
+    LoadFailed {
+        /// The overlay name that was requested
+        name: String,
+        /// The underlying error
+        source: LoadSpecError,
+    },
+}
+
+/// Render a short "did you mean" hint from the closest matches to an
+/// unresolved name, or an empty string if there are none.
+fn suggestion_message(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        return String::new();
+    }
+    let names = suggestions
+        .iter()
+        .map(|name| format!("{name:?}"))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    format!(" (did you mean {names}?)")
+}
+
+/// Errors that can occur when preparing to run a command inside a
+/// composed environment.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum RuntimeError {
+    /// The requested `--cwd` does not exist, or is not a directory.
+    #[error("--cwd {0:?} does not exist, or is not a directory")]
+    CwdNotFound(PathBuf),
+    /// `--as-user` was not a valid `uid[:gid]`.
+    #[error("invalid --as-user {0:?}, expected a uid or uid:gid, e.g. 1000 or 1000:1000")]
+    InvalidAsUser(String),
+    /// `--as-user` was given, but the current process does not have
+    /// enough privilege to change the spawned command's user.
+    #[error("--as-user requires root privilege to change user, refusing to run as {0}")]
+    AsUserNotPermitted(AsUser),
+    /// The composed environment contributes no layers, or none of its
+    /// layers contribute any packages, ops, binds or contents, which
+    /// is almost always a mistake rather than an intentional no-op.
+    #[error(
+        "the composed environment is empty: no layers, packages, ops, binds or contents were found; pass --allow-empty if this is intentional"
+    )]
+    #[diagnostic(code(spenv::empty_environment))]
+    EmptyEnvironment,
+}
+
+/// Errors that can occur when rewriting a spec's `includes:` list.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum PruneIncludesError {
+    /// Error loading or composing the spec whose includes are being pruned.
+    #[error(transparent)]
+    #[diagnostic(forward(0))]
+    ComposeError(#[from] ComposeError),
+    /// Error reading the spec file to rewrite.
+    #[error("failed to read spec file {path:?}")]
+    ReadFailed {
+        /// The file that could not be read
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+    /// Error parsing the spec file as YAML to rewrite its `includes:` list.
+    #[error("failed to parse spec file {path:?} as yaml")]
+    InvalidYaml {
+        /// The file that failed to parse
+        path: PathBuf,
+        /// The underlying error
+        source: serde_yaml::Error,
+    },
+    /// Error serializing the rewritten spec back to YAML.
+    #[error("failed to serialize rewritten spec file {path:?}")]
+    Serialize {
+        /// The file that could not be serialized
+        path: PathBuf,
+        /// The underlying error
+        source: serde_yaml::Error,
+    },
+    /// Error writing the rewritten spec back to disk.
+    #[error("failed to write spec file {path:?}")]
+    WriteFailed {
+        /// The file that could not be written
+        path: PathBuf,
+        /// The underlying error
+        source: std::io::Error,
+    },
+}
+
+/// Errors that can occur when validating a spec against its JSON Schema.
+#[derive(thiserror::Error, miette::Diagnostic, Debug)]
+pub enum SchemaError {
+    /// The spec contents could not be parsed as YAML at all, so no
+    /// schema validation could be attempted.
+    #[error("failed to parse spec as yaml")]
+    InvalidYaml(serde_yaml::Error),
+    /// The generated JSON Schema itself failed to compile.
+    #[error("generated schema failed to compile: {0}")]
+    InvalidSchema(String),
+}
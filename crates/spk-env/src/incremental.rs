@@ -0,0 +1,113 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Re-resolving a composed environment in response to individual file
+//! changes, for long-running callers (daemons, IDE servers) that
+//! watch `.spenv.yaml` files rather than re-discovering from scratch
+//! on every access.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::compose::ComposedEnvironment;
+use crate::discovery::DiscoveryOptions;
+use crate::error::ComposeError;
+
+#[cfg(test)]
+#[path = "incremental_test.rs"]
+mod incremental_test;
+
+/// A [`ComposedEnvironment`] kept up to date as the spec files it was
+/// built from change on disk, without re-resolving on every change.
+///
+/// Composition itself is not split apart and cached per file: the
+/// include graph can restructure in ways that are cheaper to just
+/// re-walk than to patch incrementally. What this does save is the
+/// work entirely when a watcher reports changes to files that had no
+/// bearing on this environment, which is the common case for a daemon
+/// watching a whole project tree.
+pub struct IncrementalDiscovery {
+    start: PathBuf,
+    options: DiscoveryOptions,
+    composed: ComposedEnvironment,
+    mtimes: HashMap<PathBuf, SystemTime>,
+}
+
+impl IncrementalDiscovery {
+    /// Resolve the environment rooted at `start`, recording the
+    /// modification time of every layer that contributed to it.
+    pub fn resolve<P: AsRef<Path>>(
+        start: P,
+        options: DiscoveryOptions,
+    ) -> Result<Self, ComposeError> {
+        let start = start.as_ref().to_owned();
+        let composed = ComposedEnvironment::resolve_with_options(&start, options.clone())?;
+        let mtimes = layer_mtimes(&composed);
+        Ok(Self {
+            start,
+            options,
+            composed,
+            mtimes,
+        })
+    }
+
+    /// The most recently resolved composition.
+    pub fn composed(&self) -> &ComposedEnvironment {
+        &self.composed
+    }
+
+    /// Re-resolve the environment if any of `changed_paths` could
+    /// plausibly affect it, returning whether the composition actually
+    /// changed as a result.
+    ///
+    /// A path that isn't one of this environment's known layers, or
+    /// whose modification time hasn't moved since it was last read,
+    /// is assumed irrelevant and skipped without re-resolving, so a
+    /// daemon watching an entire project tree doesn't pay for edits
+    /// elsewhere in it or duplicate change notifications.
+    /// [`IncrementalDiscovery::composed`] is left untouched when this
+    /// returns `false`.
+    pub fn refresh(&mut self, changed_paths: &[PathBuf]) -> Result<bool, ComposeError> {
+        let relevant = changed_paths.iter().any(|path| {
+            let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+            let Some(cached_mtime) = self.mtimes.get(&canonical) else {
+                return false;
+            };
+            std::fs::metadata(&canonical)
+                .and_then(|meta| meta.modified())
+                .is_ok_and(|current_mtime| current_mtime != *cached_mtime)
+        });
+        if !relevant {
+            return Ok(false);
+        }
+
+        let next = ComposedEnvironment::resolve_with_options(&self.start, self.options.clone())?;
+        let changed = !self.composed.semantically_equal(&next);
+        self.mtimes = layer_mtimes(&next);
+        self.composed = next;
+        Ok(changed)
+    }
+}
+
+/// The modification time of each layer in `composed`, keyed by its
+/// canonicalized path. A layer whose mtime can't be read (e.g. it was
+/// deleted between composing and now) is left out, so a later change
+/// reported against it is treated as irrelevant rather than panicking.
+fn layer_mtimes(composed: &ComposedEnvironment) -> HashMap<PathBuf, SystemTime> {
+    composed
+        .layers
+        .iter()
+        .filter_map(|layer| {
+            let canonical = layer
+                .file_path
+                .canonicalize()
+                .unwrap_or_else(|_| layer.file_path.clone());
+            let mtime = std::fs::metadata(&canonical)
+                .and_then(|meta| meta.modified())
+                .ok()?;
+            Some((canonical, mtime))
+        })
+        .collect()
+}
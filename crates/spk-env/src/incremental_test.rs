@@ -0,0 +1,76 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::time::{Duration, SystemTime};
+
+use rstest::{fixture, rstest};
+
+use super::IncrementalDiscovery;
+use crate::discovery::DiscoveryOptions;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+/// Rewrite `path` and bump its modification time forward, so the
+/// change is visible even on filesystems with coarse mtime resolution.
+fn touch_with_new_contents(path: &std::path::Path, contents: &str) {
+    std::fs::write(path, contents).unwrap();
+    let file = std::fs::File::options().write(true).open(path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5))
+        .unwrap();
+}
+
+#[rstest]
+fn test_refresh_ignores_changes_to_unrelated_files(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+
+    let mut discovery = IncrementalDiscovery::resolve(root, DiscoveryOptions::default()).unwrap();
+    let before = discovery.composed().effective_priority();
+
+    let unrelated = root.join("notes.txt");
+    std::fs::write(&unrelated, "hello").unwrap();
+    let changed = discovery.refresh(&[unrelated]).unwrap();
+
+    assert!(!changed);
+    assert_eq!(discovery.composed().effective_priority(), before);
+}
+
+#[rstest]
+fn test_refresh_re_resolves_after_a_known_layer_changes_and_reports_a_changed_composition(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    let base_path = root.join("base.spenv.yaml");
+    std::fs::write(&base_path, "priority: 1\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\npriority: 2\n",
+    )
+    .unwrap();
+
+    let mut discovery = IncrementalDiscovery::resolve(root, DiscoveryOptions::default()).unwrap();
+    assert_eq!(discovery.composed().effective_priority(), Some(2));
+
+    touch_with_new_contents(&base_path, "priority: 5\n");
+    let changed = discovery.refresh(&[base_path]).unwrap();
+
+    assert!(changed);
+    // The innermost spec's priority still wins, but it was only able
+    // to change because the included layer was reloaded.
+    assert_eq!(discovery.composed().effective_priority(), Some(2));
+    assert!(
+        discovery
+            .composed()
+            .layers
+            .iter()
+            .any(|layer| layer.priority == Some(5)),
+        "the changed layer's new contents should be reflected after refresh"
+    );
+}
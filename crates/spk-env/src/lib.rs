@@ -0,0 +1,54 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! `spenv` composes scripting environments from `.spenv.yaml` specs.
+//!
+//! A spec describes environment variable operations, bind mounts and
+//! package requests for a single layer. Specs can include one
+//! another, and can be discovered up a directory tree via
+//! `inherit: true`, so that a [`ComposedEnvironment`] is typically
+//! built up from several specs layered together.
+
+pub mod cache;
+pub mod compose;
+pub mod discovery;
+pub mod error;
+pub mod incremental;
+pub mod lock;
+pub mod overlay;
+pub mod prune;
+#[cfg(feature = "remote-includes")]
+pub mod remote;
+pub mod runtime;
+pub mod schema;
+pub mod spec;
+pub mod stats;
+pub mod validate;
+pub mod verify_includes;
+
+pub use cache::{CachedSolution, SolutionCache, resolve_with_cache};
+pub use compose::{
+    ComposedEnvironment, EnvConflict, EnvManifest, FilesystemResolver, IncludeResolver,
+    IncludeTiming, LayerSource, ManifestLayer, compose_from_yaml,
+};
+pub use discovery::{
+    DiscoveryOptions, DiscoveryResult, DiscoveryTrace, discover_specs_detailed,
+    discover_specs_traced,
+};
+pub use incremental::IncrementalDiscovery;
+pub use lock::{
+    EnvLock, GenerateLockOptions, GenerationMetadata, LockApiVersion, LockChange, SourceHash,
+    check_compatibility, format_changes, hash_contents, migrate_lock_file, verify_layers,
+    verify_lock, verify_sources,
+};
+pub use overlay::{
+    LayerInsertMode, default_overlays_dir, layer_names_from_env, layers_mode_from_env,
+    resolve_overlay,
+};
+pub use prune::{redundant_includes, remove_includes};
+pub use schema::{spec_json_schema, validate_spec_yaml};
+pub use spec::{BindMount, ContentMount, EnvOp, EnvSpec, LockPolicy, TmpfsMount};
+pub use stats::{CountDimension, EnvStats};
+pub use validate::{ValidationIssue, validate};
+pub use verify_includes::{IncludeCheck, IncludeStatus, verify_includes};
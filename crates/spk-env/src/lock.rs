@@ -0,0 +1,505 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Locking the resolved shape of a composed environment so that
+//! drift can be detected and reported later with `spenv check`.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ComposedEnvironment;
+use crate::error::{LoadLockError, LockValidationError, MigrateLockError, SaveLockError};
+
+#[cfg(test)]
+#[path = "lock_test.rs"]
+mod lock_test;
+
+/// The schema version of an [`EnvLock`], bumped whenever the lock
+/// format gains or changes fields in an incompatible way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockApiVersion {
+    /// The initial lock schema
+    #[default]
+    V0,
+}
+
+/// Metadata recorded about how a lock was generated, separate from
+/// the resolved layers themselves.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenerationMetadata {
+    /// The schema version this lock was written with
+    pub api_version: LockApiVersion,
+    /// The `CARGO_PKG_VERSION` of the spenv binary that generated this
+    /// lock. Empty for locks written before this field existed, which
+    /// [`check_compatibility`] treats as nothing to compare against.
+    #[serde(default)]
+    pub spenv_version: String,
+    /// The effective startup-script priority at the time of generation
+    #[serde(default)]
+    pub priority: Option<i32>,
+    /// A content hash of every layer's spec file, at the time of
+    /// generation. This covers every layer in [`ComposedEnvironment::layers`],
+    /// not just the directly discovered specs: a layer pulled in
+    /// through `includes` or a `--overlay` is hashed the same as one
+    /// found by walking the directory tree, so drift in any of them
+    /// is caught by [`verify_sources`].
+    #[serde(default)]
+    pub source_hashes: Vec<SourceHash>,
+    /// A content fingerprint of this lock's resolved state, exposed
+    /// to ops via the `${SPENV_FINGERPRINT}` template placeholder
+    #[serde(default)]
+    pub fingerprint: String,
+    /// A hash of the effective, ordered package requests across
+    /// every layer, at the time of generation. `None` if no layer
+    /// requested any packages.
+    #[serde(default)]
+    pub solution_hash: Option<String>,
+    /// The unix timestamp, in seconds, when this lock was generated,
+    /// exposed to ops via the `${SPENV_LOCKED_AT}` template placeholder
+    #[serde(default)]
+    pub generated_at: u64,
+    /// The hostname of the machine the lock was generated on, if
+    /// recording one wasn't disabled via
+    /// [`GenerateLockOptions::record_hostname`]. Not a drift signal:
+    /// [`verify_lock`] never compares it.
+    #[serde(default)]
+    pub hostname: Option<String>,
+}
+
+/// The recorded content hash of a single layer's spec file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SourceHash {
+    /// The spec file this hash was computed from
+    pub path: PathBuf,
+    /// A content hash of the file, as it was when the lock was generated
+    pub hash: String,
+    /// The layer's [`crate::spec::EnvSpec::note`] at the time the lock
+    /// was generated, if it set one, carried through for auditing
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// A simple, non-cryptographic content hash used to detect when a
+/// spec file's contents have changed since a lock was generated.
+pub fn hash_contents(contents: &str) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    contents.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// A hash of the effective, ordered package requests, used to detect
+/// when the same requests would no longer resolve to the same
+/// packages. `None` when there are no package requests to hash.
+fn hash_packages(packages: &[String]) -> Option<String> {
+    if packages.is_empty() {
+        return None;
+    }
+    Some(hash_contents(&packages.join("\n")))
+}
+
+/// A fingerprint of a lock's resolved state, derived from the same
+/// inputs as the lock itself so that it changes exactly when the
+/// lock's own drift detection would fire.
+fn compute_fingerprint(priority: Option<i32>, source_hashes: &[SourceHash]) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    priority.hash(&mut hasher);
+    source_hashes.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A snapshot of a [`ComposedEnvironment`]'s resolved shape, used to
+/// detect drift between when it was generated and when it is checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvLock {
+    /// Metadata about how this lock was produced
+    #[serde(flatten)]
+    pub metadata: GenerationMetadata,
+}
+
+/// Controls how [`EnvLock::generate_with_options`] records the
+/// generating machine's hostname and generation timestamp, so CI can
+/// pin stable values rather than leaking a machine name or wall-clock
+/// time into a lock that's shared and diffed across machines, and so
+/// that two locks generated from the same inputs at different times
+/// are byte-for-byte identical.
+#[derive(Debug, Clone)]
+pub struct GenerateLockOptions {
+    /// A hostname to record instead of the local machine's own one.
+    /// Ignored if `record_hostname` is `false`.
+    pub hostname: Option<String>,
+    /// Whether to record a hostname at all. Defaults to `true`,
+    /// preserving the previous unconditional behavior.
+    pub record_hostname: bool,
+    /// A unix timestamp, in seconds, to record instead of the current
+    /// time. Falls back to the `SOURCE_DATE_EPOCH` environment
+    /// variable, then to the current time, if unset.
+    pub timestamp: Option<u64>,
+}
+
+impl Default for GenerateLockOptions {
+    fn default() -> Self {
+        Self {
+            hostname: None,
+            record_hostname: true,
+            timestamp: None,
+        }
+    }
+}
+
+impl GenerateLockOptions {
+    fn resolve_hostname(&self) -> Option<String> {
+        if !self.record_hostname {
+            return None;
+        }
+        self.hostname.clone().or_else(|| {
+            hostname::get()
+                .ok()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+    }
+
+    fn resolve_timestamp(&self) -> u64 {
+        self.timestamp
+            .or_else(|| {
+                std::env::var("SOURCE_DATE_EPOCH")
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+            })
+            .unwrap_or_else(unix_now)
+    }
+}
+
+impl EnvLock {
+    /// The expected file name for a lock file
+    pub const FILE_NAME: &str = ".spenv.lock.yaml";
+
+    /// Generate a lock capturing the current resolved state of `env`,
+    /// recording the local machine's hostname. Use
+    /// [`EnvLock::generate_with_options`] to override or omit it.
+    pub fn generate(env: &ComposedEnvironment) -> Self {
+        Self::generate_with_options(env, GenerateLockOptions::default())
+    }
+
+    /// Generate a lock capturing the current resolved state of `env`,
+    /// as [`EnvLock::generate`], with control over the hostname and
+    /// generation timestamp recorded via `options`.
+    pub fn generate_with_options(env: &ComposedEnvironment, options: GenerateLockOptions) -> Self {
+        let source_hashes: Vec<SourceHash> = env
+            .layers
+            .iter()
+            .filter_map(|layer| {
+                let contents = std::fs::read_to_string(&layer.file_path).ok()?;
+                Some(SourceHash {
+                    path: layer.file_path.clone(),
+                    hash: hash_contents(&contents),
+                    note: layer.note.clone(),
+                })
+            })
+            .collect();
+        let priority = env.effective_priority();
+        let fingerprint = compute_fingerprint(priority, &source_hashes);
+        let solution_hash = hash_packages(&env.effective_packages());
+        let hostname = options.resolve_hostname();
+        let generated_at = options.resolve_timestamp();
+        Self {
+            metadata: GenerationMetadata {
+                api_version: LockApiVersion::V0,
+                spenv_version: env!("CARGO_PKG_VERSION").to_string(),
+                priority,
+                source_hashes,
+                fingerprint,
+                solution_hash,
+                generated_at,
+                hostname,
+            },
+        }
+    }
+
+    /// Load a lock from a file on disk, in its current schema.
+    ///
+    /// This does not attempt to upgrade locks written by an older
+    /// [`LockApiVersion`]; use [`migrate_lock_file`] for that.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadLockError> {
+        let path = path.as_ref();
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| LoadLockError::ReadFailed {
+                path: path.to_owned(),
+                source,
+            })?;
+        serde_yaml::from_str(&contents).map_err(|source| LoadLockError::InvalidYaml {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Write this lock to a file on disk, in the current schema.
+    pub fn save_file<P: AsRef<Path>>(&self, path: P) -> Result<(), SaveLockError> {
+        let path = path.as_ref();
+        let yaml = serde_yaml::to_string(self).map_err(SaveLockError::Serialize)?;
+        std::fs::write(path, yaml).map_err(|source| SaveLockError::WriteFailed {
+            path: path.to_owned(),
+            source,
+        })
+    }
+
+    /// Check this lock for internal inconsistencies that would make
+    /// it untrustworthy, without needing to compose an environment or
+    /// reach a repository: every source has a non-empty, well-formed
+    /// content hash, every source has a path, and `generated_at`
+    /// isn't in the future. Catches hand-edited or corrupted locks
+    /// before [`verify_lock`] tries to use them against a real
+    /// environment.
+    pub fn validate(&self) -> Result<(), LockValidationError> {
+        for source in &self.metadata.source_hashes {
+            if source.path.as_os_str().is_empty() {
+                return Err(LockValidationError::EmptySourcePath);
+            }
+            if source.hash.is_empty() {
+                return Err(LockValidationError::EmptySourceHash {
+                    path: source.path.clone(),
+                });
+            }
+            if source.hash.len() != 16
+                || !source.hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+            {
+                return Err(LockValidationError::MalformedSourceHash {
+                    path: source.path.clone(),
+                    hash: source.hash.clone(),
+                });
+            }
+        }
+
+        let now = unix_now();
+        if self.metadata.generated_at > now {
+            return Err(LockValidationError::TimestampInFuture {
+                generated_at: self.metadata.generated_at,
+                now,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a lock file of any supported [`LockApiVersion`] and return it
+/// upgraded to the current schema, filling any new fields with
+/// their defaults. Unsupported/unknown future versions are rejected.
+pub fn migrate_lock_file<P: AsRef<Path>>(path: P) -> Result<EnvLock, MigrateLockError> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path).map_err(|source| LoadLockError::ReadFailed {
+        path: path.to_owned(),
+        source,
+    })?;
+    let raw: serde_yaml::Value =
+        serde_yaml::from_str(&contents).map_err(|source| LoadLockError::InvalidYaml {
+            path: path.to_owned(),
+            source,
+        })?;
+    let version = raw
+        .get("api_version")
+        .and_then(serde_yaml::Value::as_str)
+        .map(str::to_owned);
+
+    // Every known version is parsed directly into the current schema,
+    // since `LockApiVersion::V0` is still the latest. As new versions
+    // are introduced, each one gains an explicit transformation here
+    // before falling through to the current representation.
+    match version.as_deref() {
+        Some("v0") => {
+            Ok(
+                serde_yaml::from_value(raw).map_err(|source| LoadLockError::InvalidYaml {
+                    path: path.to_owned(),
+                    source,
+                })?,
+            )
+        }
+        Some(other) => Err(MigrateLockError::UnsupportedVersion(other.to_owned())),
+        None => Err(MigrateLockError::UnsupportedVersion(String::from(
+            "<missing api_version>",
+        ))),
+    }
+}
+
+/// A single difference found between a lock and the environment it
+/// was generated from, reported by [`verify_lock`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LockChange {
+    /// The effective startup-script priority has changed since the
+    /// lock was generated.
+    PriorityChanged {
+        /// The priority recorded in the lock
+        locked: Option<i32>,
+        /// The priority that would be resolved now
+        resolved: Option<i32>,
+    },
+    /// A layer's spec file has changed on disk since the lock was generated.
+    SourceChanged {
+        /// The spec file whose contents no longer match the lock
+        path: PathBuf,
+    },
+    /// The effective, ordered package requests have changed since the
+    /// lock was generated, so the same requests would no longer
+    /// resolve to the same packages.
+    PackagesChanged {
+        /// The package requests hash recorded in the lock
+        locked: Option<String>,
+        /// The package requests hash that would be resolved now
+        resolved: Option<String>,
+    },
+    /// The lock was generated by a different minor version of spenv
+    /// than the one currently running. Non-fatal: the lock format or
+    /// resolution behavior may have changed, so this is a heads-up
+    /// rather than drift in the environment itself.
+    VersionSkew {
+        /// The spenv version that generated the lock
+        locked: String,
+        /// The spenv version currently running
+        running: String,
+    },
+}
+
+/// Render each of `changes` on its own line, in the shared format used
+/// wherever a change list is reported to a user.
+pub fn format_changes(changes: &[LockChange]) -> String {
+    changes
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl std::fmt::Display for LockChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockChange::PriorityChanged { locked, resolved } => {
+                write!(f, "priority changed: {locked:?} -> {resolved:?}")
+            }
+            LockChange::SourceChanged { path } => {
+                write!(f, "source changed: {}", path.display())
+            }
+            LockChange::PackagesChanged { locked, resolved } => {
+                write!(f, "packages changed: {locked:?} -> {resolved:?}")
+            }
+            LockChange::VersionSkew { locked, running } => {
+                write!(
+                    f,
+                    "spenv version skew: lock generated by {locked}, running {running}"
+                )
+            }
+        }
+    }
+}
+
+/// Parse the leading `major.minor` components of a version string like
+/// `CARGO_PKG_VERSION`, ignoring the patch segment and anything after it.
+fn major_minor(version: &str) -> Option<(u64, u64)> {
+    let mut parts = version.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+/// Compare the spenv version recorded in `lock` against the version
+/// currently running, returning a non-fatal [`LockChange::VersionSkew`]
+/// when they differ across a minor version boundary.
+///
+/// Locks written before [`GenerationMetadata::spenv_version`] existed
+/// record an empty string and are treated as compatible, since there's
+/// nothing to compare against.
+pub fn check_compatibility(lock: &EnvLock) -> Vec<LockChange> {
+    let locked = &lock.metadata.spenv_version;
+    if locked.is_empty() {
+        return Vec::new();
+    }
+    let running = env!("CARGO_PKG_VERSION");
+    match (major_minor(locked), major_minor(running)) {
+        (Some(locked_mm), Some(running_mm)) if locked_mm != running_mm => {
+            vec![LockChange::VersionSkew {
+                locked: locked.clone(),
+                running: running.to_string(),
+            }]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Compare the spec files recorded in `lock` against their current
+/// contents on disk, without resolving or discovering any layers.
+///
+/// This only reads the exact files the lock already knows about, so
+/// it works offline and does not require walking the directory tree
+/// or re-composing the environment. Each recorded hash is matched to
+/// its current contents by `path`, not by position, so reordering an
+/// unrelated layer (e.g. two independent `includes` entries swapping
+/// places) never produces a spurious change here.
+pub fn verify_sources(lock: &EnvLock) -> Vec<LockChange> {
+    lock.metadata
+        .source_hashes
+        .iter()
+        .filter_map(|recorded| {
+            let current = std::fs::read_to_string(&recorded.path)
+                .ok()
+                .map(|contents| hash_contents(&contents));
+            if current.as_deref() == Some(recorded.hash.as_str()) {
+                None
+            } else {
+                Some(LockChange::SourceChanged {
+                    path: recorded.path.clone(),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Recompute `env`'s resolved layer state and compare it against
+/// `lock`, without re-checking the spec files themselves.
+pub fn verify_layers(env: &ComposedEnvironment, lock: &EnvLock) -> Vec<LockChange> {
+    let mut changes = Vec::new();
+
+    let resolved_priority = env.effective_priority();
+    if resolved_priority != lock.metadata.priority {
+        changes.push(LockChange::PriorityChanged {
+            locked: lock.metadata.priority,
+            resolved: resolved_priority,
+        });
+    }
+
+    let resolved_solution_hash = hash_packages(&env.effective_packages());
+    if resolved_solution_hash != lock.metadata.solution_hash {
+        changes.push(LockChange::PackagesChanged {
+            locked: lock.metadata.solution_hash.clone(),
+            resolved: resolved_solution_hash,
+        });
+    }
+
+    changes
+}
+
+/// Recompute `env`'s resolved state and compare it against `lock`,
+/// returning every change that has drifted since the lock was made.
+///
+/// This runs [`verify_sources`], [`verify_layers`], and
+/// [`check_compatibility`]; use [`verify_sources`] alone for a faster,
+/// offline-only check.
+pub fn verify_lock(env: &ComposedEnvironment, lock: &EnvLock) -> Vec<LockChange> {
+    let mut changes = verify_sources(lock);
+    changes.extend(verify_layers(env, lock));
+    changes.extend(check_compatibility(lock));
+    changes
+}
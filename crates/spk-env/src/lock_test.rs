@@ -0,0 +1,421 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{
+    EnvLock, GenerateLockOptions, LockChange, SourceHash, check_compatibility, migrate_lock_file,
+    verify_lock, verify_sources,
+};
+use crate::ComposedEnvironment;
+use crate::error::LockValidationError;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_verify_lock_reports_changed_priority(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+
+    std::fs::write(&spec_path, "priority: 50\n").unwrap();
+    let original = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&original);
+    assert_eq!(lock.metadata.priority, Some(50));
+
+    // A no-op recheck should report no drift
+    assert!(verify_lock(&original, &lock).is_empty());
+
+    // Changing the priority op should be caught as drift, alongside
+    // the resulting change to the spec file's own contents
+    std::fs::write(&spec_path, "priority: 75\n").unwrap();
+    let changed = ComposedEnvironment::resolve(root).unwrap();
+    let changes = verify_lock(&changed, &lock);
+    assert_eq!(
+        changes,
+        vec![
+            LockChange::SourceChanged {
+                path: spec_path.clone(),
+            },
+            LockChange::PriorityChanged {
+                locked: Some(50),
+                resolved: Some(75),
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_verify_lock_reports_changed_packages(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+
+    std::fs::write(&spec_path, "packages: [foo/1.0.0]\n").unwrap();
+    let original = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&original);
+    assert!(lock.metadata.solution_hash.is_some());
+    assert!(verify_lock(&original, &lock).is_empty());
+
+    // Pinning the request to a specific build changes the effective
+    // package requests, which is caught as drift alongside the change
+    // to the spec file's own contents.
+    std::fs::write(&spec_path, "packages: [foo/1.0.0/CU7ZWOIF]\n").unwrap();
+    let changed = ComposedEnvironment::resolve(root).unwrap();
+    let changes = verify_lock(&changed, &lock);
+    assert_eq!(
+        changes,
+        vec![
+            LockChange::SourceChanged {
+                path: spec_path.clone(),
+            },
+            LockChange::PackagesChanged {
+                locked: lock.metadata.solution_hash.clone(),
+                resolved: EnvLock::generate(&changed).metadata.solution_hash,
+            },
+        ]
+    );
+}
+
+#[rstest]
+fn test_lock_tracks_an_included_file_alongside_the_spec_that_includes_it(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    let included_path = root.join("team.yaml");
+
+    std::fs::write(&spec_path, "includes: [team.yaml]\n").unwrap();
+    std::fs::write(
+        &included_path,
+        "ops:\n  - op: set\n    var: TEAM\n    value: infra\n",
+    )
+    .unwrap();
+    let original = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&original);
+    assert!(
+        lock.metadata
+            .source_hashes
+            .iter()
+            .any(|hash| hash.path == included_path)
+    );
+    assert!(verify_lock(&original, &lock).is_empty());
+
+    // The included file changing, with the spec that pulled it in
+    // left untouched, should still be caught as drift.
+    std::fs::write(
+        &included_path,
+        "ops:\n  - op: set\n    var: TEAM\n    value: platform\n",
+    )
+    .unwrap();
+    let changes = verify_sources(&lock);
+    assert_eq!(
+        changes,
+        vec![LockChange::SourceChanged {
+            path: included_path,
+        }]
+    );
+}
+
+#[rstest]
+fn test_verify_sources_does_not_flag_unrelated_includes_reordering(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    let a_path = root.join("a.yaml");
+    let b_path = root.join("b.yaml");
+
+    std::fs::write(&spec_path, "includes: [a.yaml, b.yaml]\n").unwrap();
+    std::fs::write(&a_path, "ops:\n  - op: set\n    var: A\n    value: a\n").unwrap();
+    std::fs::write(&b_path, "ops:\n  - op: set\n    var: B\n    value: b\n").unwrap();
+    let original = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&original);
+    assert!(verify_sources(&lock).is_empty());
+
+    // Swapping the order of two unrelated includes changes the
+    // parent spec's own contents, which is still reported, but must
+    // not also flag the untouched included files as changed.
+    std::fs::write(&spec_path, "includes: [b.yaml, a.yaml]\n").unwrap();
+    let changes = verify_sources(&lock);
+    assert_eq!(
+        changes,
+        vec![LockChange::SourceChanged {
+            path: spec_path.clone(),
+        }]
+    );
+}
+
+#[rstest]
+fn test_verify_sources_detects_changed_spec_without_resolving_layers(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+
+    std::fs::write(
+        &spec_path,
+        "ops:\n  - op: set\n    var: FOO\n    value: bar\n",
+    )
+    .unwrap();
+    let original = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&original);
+    assert!(verify_sources(&lock).is_empty());
+
+    std::fs::write(
+        &spec_path,
+        "ops:\n  - op: set\n    var: FOO\n    value: baz\n",
+    )
+    .unwrap();
+    let changes = verify_sources(&lock);
+    assert_eq!(
+        changes,
+        vec![LockChange::SourceChanged {
+            path: spec_path.clone(),
+        }]
+    );
+}
+
+#[rstest]
+fn test_generate_carries_a_layers_note_into_its_source_hash(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(&spec_path, "note: pinned for CVE fix\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&env);
+
+    let source_hash = lock
+        .metadata
+        .source_hashes
+        .iter()
+        .find(|hash| hash.path == spec_path)
+        .expect("the layer's source hash should be recorded");
+    assert_eq!(source_hash.note, Some("pinned for CVE fix".to_string()));
+}
+
+#[rstest]
+fn test_migrate_lock_from_minimal_v0(tmpdir: tempfile::TempDir) {
+    let lock_file = tmpdir.path().join(EnvLock::FILE_NAME);
+    // A minimal, hand-written V0 lock with no optional fields set.
+    std::fs::write(&lock_file, "api_version: v0\n").unwrap();
+
+    let migrated = migrate_lock_file(&lock_file).expect("minimal v0 lock should migrate");
+    assert_eq!(migrated.metadata.priority, None);
+
+    migrated.save_file(&lock_file).unwrap();
+    let reparsed = EnvLock::load_file(&lock_file)
+        .expect("migrated lock should re-parse under the current loader");
+    assert_eq!(reparsed, migrated);
+}
+
+#[rstest]
+fn test_migrate_lock_rejects_unknown_version(tmpdir: tempfile::TempDir) {
+    let lock_file = tmpdir.path().join(EnvLock::FILE_NAME);
+    std::fs::write(&lock_file, "api_version: v99\n").unwrap();
+    migrate_lock_file(&lock_file).expect_err("unknown future version should be rejected");
+}
+
+#[rstest]
+fn test_generate_records_the_local_hostname_by_default(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let lock = EnvLock::generate(&env);
+
+    assert!(lock.metadata.hostname.is_some());
+}
+
+#[rstest]
+fn test_generate_with_options_can_override_or_omit_the_hostname(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let stable = EnvLock::generate_with_options(
+        &env,
+        GenerateLockOptions {
+            hostname: Some("ci-runner".to_string()),
+            record_hostname: true,
+            timestamp: None,
+        },
+    );
+    assert_eq!(stable.metadata.hostname, Some("ci-runner".to_string()));
+
+    let elided = EnvLock::generate_with_options(
+        &env,
+        GenerateLockOptions {
+            hostname: None,
+            record_hostname: false,
+            timestamp: None,
+        },
+    );
+    assert_eq!(elided.metadata.hostname, None);
+
+    // Disabling hostname recording is not drift: a lock generated
+    // without one should still verify cleanly against the same env.
+    assert!(verify_lock(&env, &elided).is_empty());
+}
+
+#[rstest]
+fn test_generate_with_options_can_override_the_timestamp(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let lock = EnvLock::generate_with_options(
+        &env,
+        GenerateLockOptions {
+            hostname: None,
+            record_hostname: false,
+            timestamp: Some(1_700_000_000),
+        },
+    );
+
+    assert_eq!(lock.metadata.generated_at, 1_700_000_000);
+}
+
+#[rstest]
+fn test_pinned_options_produce_byte_for_byte_identical_locks(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let options = || GenerateLockOptions {
+        hostname: Some("ci-runner".to_string()),
+        record_hostname: true,
+        timestamp: Some(1_700_000_000),
+    };
+
+    let first = EnvLock::generate_with_options(&env, options());
+    let second = EnvLock::generate_with_options(&env, options());
+
+    assert_eq!(first, second);
+}
+
+#[rstest]
+fn test_validate_accepts_a_well_formed_lock(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+
+    let lock = EnvLock::generate(&env);
+
+    assert_eq!(lock.validate(), Ok(()));
+}
+
+#[rstest]
+fn test_validate_rejects_an_empty_source_hash(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.source_hashes[0].hash = String::new();
+
+    assert_eq!(
+        lock.validate(),
+        Err(LockValidationError::EmptySourceHash {
+            path: lock.metadata.source_hashes[0].path.clone(),
+        })
+    );
+}
+
+#[rstest]
+fn test_validate_rejects_a_malformed_source_hash(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.source_hashes[0].hash = "not-hex".to_string();
+
+    assert_eq!(
+        lock.validate(),
+        Err(LockValidationError::MalformedSourceHash {
+            path: lock.metadata.source_hashes[0].path.clone(),
+            hash: "not-hex".to_string(),
+        })
+    );
+}
+
+#[rstest]
+fn test_validate_rejects_an_empty_source_path(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.source_hashes.push(SourceHash::default());
+
+    assert_eq!(lock.validate(), Err(LockValidationError::EmptySourcePath));
+}
+
+#[rstest]
+fn test_validate_rejects_a_timestamp_in_the_future(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.generated_at = u64::MAX;
+
+    assert!(matches!(
+        lock.validate(),
+        Err(LockValidationError::TimestampInFuture { .. })
+    ));
+}
+
+#[rstest]
+fn test_generate_records_the_running_spenv_version(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let lock = EnvLock::generate(&env);
+
+    assert_eq!(lock.metadata.spenv_version, env!("CARGO_PKG_VERSION"));
+    assert!(check_compatibility(&lock).is_empty());
+}
+
+#[rstest]
+fn test_check_compatibility_warns_on_a_minor_version_mismatch(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.spenv_version = "0.1.0".to_string();
+
+    assert_eq!(
+        check_compatibility(&lock),
+        vec![LockChange::VersionSkew {
+            locked: "0.1.0".to_string(),
+            running: env!("CARGO_PKG_VERSION").to_string(),
+        }]
+    );
+}
+
+#[rstest]
+fn test_check_compatibility_ignores_a_patch_only_difference(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    let (major, minor) = {
+        let running = env!("CARGO_PKG_VERSION");
+        let mut parts = running.split('.');
+        (parts.next().unwrap(), parts.next().unwrap())
+    };
+    lock.metadata.spenv_version = format!("{major}.{minor}.999999");
+
+    assert!(check_compatibility(&lock).is_empty());
+}
+
+#[rstest]
+fn test_check_compatibility_ignores_a_lock_predating_the_version_field(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "priority: 1\n").unwrap();
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let mut lock = EnvLock::generate(&env);
+    lock.metadata.spenv_version = String::new();
+
+    assert!(check_compatibility(&lock).is_empty());
+    assert!(verify_lock(&env, &lock).is_empty());
+}
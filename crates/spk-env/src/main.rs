@@ -0,0 +1,26 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::process::ExitCode;
+
+use clap::Parser;
+use colored::Colorize;
+
+mod cli;
+
+fn main() -> ExitCode {
+    let opt = cli::Opt::parse();
+    let error_format = opt.error_format;
+    match opt.run() {
+        Ok(code) => ExitCode::from(code as u8),
+        Err(err) => {
+            let rendered = cli::render_error(&err, error_format);
+            match error_format {
+                cli::ErrorFormat::Human => eprintln!("{}", rendered.red()),
+                cli::ErrorFormat::Json => eprintln!("{rendered}"),
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
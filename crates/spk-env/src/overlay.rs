@@ -0,0 +1,160 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Resolving named overlay specs applied on top of a composed environment.
+//!
+//! An overlay is a single spec file living in a configured directory
+//! (by default `~/.spenv/overlays`), selected by name at runtime via
+//! `--overlay` and composed in last, giving it the highest precedence
+//! of any layer.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::OverlayError;
+use crate::spec::EnvSpec;
+
+#[cfg(test)]
+#[path = "overlay_test.rs"]
+mod overlay_test;
+
+/// The environment variable naming one or more overlay specs to
+/// compose in via `--layer`, colon- or comma-separated, when a caller
+/// doesn't pass `--layer` explicitly.
+pub const LAYERS_ENV_VAR: &str = "SPENV_LAYERS";
+
+/// The environment variable selecting [`LayerInsertMode`] for
+/// `--layer`/`SPENV_LAYERS`, when a caller doesn't pass
+/// `--layers-mode` explicitly.
+pub const LAYERS_MODE_ENV_VAR: &str = "SPENV_LAYERS_MODE";
+
+/// The default overlays directory, `~/.spenv/overlays`.
+pub fn default_overlays_dir() -> Result<PathBuf, OverlayError> {
+    dirs::home_dir()
+        .map(|home| home.join(".spenv").join("overlays"))
+        .ok_or(OverlayError::NoHomeDir)
+}
+
+/// Where `--layer`/`SPENV_LAYERS` entries are composed relative to
+/// the discovered layers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum LayerInsertMode {
+    /// Composed before every discovered layer, as the lowest
+    /// precedence of any layer
+    Before,
+    /// Composed after every discovered layer, as the highest
+    /// precedence of any layer except a later `--overlay`
+    #[default]
+    After,
+}
+
+/// Parse `SPENV_LAYERS` into the overlay names it names, splitting on
+/// both `:` and `,` so either convention works.
+pub fn layer_names_from_env() -> Vec<String> {
+    std::env::var(LAYERS_ENV_VAR)
+        .ok()
+        .map(|value| {
+            value
+                .split([':', ','])
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Parse `SPENV_LAYERS_MODE`, falling back to
+/// [`LayerInsertMode::default`] if unset or unrecognized.
+pub fn layers_mode_from_env() -> LayerInsertMode {
+    match std::env::var(LAYERS_MODE_ENV_VAR).as_deref() {
+        Ok("before") => LayerInsertMode::Before,
+        Ok("after") => LayerInsertMode::After,
+        _ => LayerInsertMode::default(),
+    }
+}
+
+/// Load the overlay spec named `name` from `overlays_dir`.
+///
+/// An unknown name errors with the overlay names that are actually
+/// available in the directory, along with the closest ones by name, as
+/// a "did you mean" hint.
+pub fn resolve_overlay(overlays_dir: &Path, name: &str) -> Result<EnvSpec, OverlayError> {
+    let path = overlays_dir.join(format!("{name}.yaml"));
+    if !path.is_file() {
+        let available = list_overlays(overlays_dir);
+        let suggestions = closest_overlay_names(name, &available);
+        return Err(OverlayError::NotFound {
+            name: name.to_string(),
+            available,
+            suggestions,
+        });
+    }
+    EnvSpec::load_file(&path).map_err(|source| OverlayError::LoadFailed {
+        name: name.to_string(),
+        source,
+    })
+}
+
+/// The overlay names available in `overlays_dir`, sorted.
+fn list_overlays(overlays_dir: &Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(overlays_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                return None;
+            }
+            path.file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(str::to_owned)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The names in `candidates` within a small edit distance of `name`,
+/// closest first, for use as a "did you mean" hint. Picks up typos and
+/// near-misses without flooding the suggestion list for a name that
+/// isn't close to anything.
+fn closest_overlay_names(name: &str, candidates: &[String]) -> Vec<String> {
+    const MAX_DISTANCE: usize = 3;
+    const MAX_SUGGESTIONS: usize = 3;
+
+    let mut scored: Vec<(usize, &String)> = candidates
+        .iter()
+        .map(|candidate| (levenshtein_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= MAX_DISTANCE)
+        .collect();
+    scored.sort_by_key(|(distance, _)| *distance);
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// The Levenshtein (single-character insert/delete/substitute) edit
+/// distance between `a` and `b`.
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut prev_diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let above = row[j + 1];
+            let cost = usize::from(a_char != b_char);
+            let new_value = (above + 1).min(row[j] + 1).min(prev_diagonal + cost);
+            prev_diagonal = above;
+            row[j + 1] = new_value;
+        }
+    }
+    row[b.len()]
+}
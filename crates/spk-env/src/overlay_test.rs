@@ -0,0 +1,66 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::resolve_overlay;
+use crate::error::OverlayError;
+
+#[fixture]
+fn overlays_dir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_resolve_overlay_loads_the_named_spec(overlays_dir: tempfile::TempDir) {
+    std::fs::write(
+        overlays_dir.path().join("foo.yaml"),
+        "priority: 5\ninherit: false\n",
+    )
+    .unwrap();
+
+    let overlay = resolve_overlay(overlays_dir.path(), "foo").unwrap();
+    assert_eq!(overlay.priority, Some(5));
+}
+
+#[rstest]
+fn test_resolve_overlay_lists_available_names_when_unknown(overlays_dir: tempfile::TempDir) {
+    std::fs::write(overlays_dir.path().join("bar.yaml"), "inherit: false\n").unwrap();
+    std::fs::write(overlays_dir.path().join("baz.yaml"), "inherit: false\n").unwrap();
+
+    let err = resolve_overlay(overlays_dir.path(), "missing").unwrap_err();
+    match err {
+        OverlayError::NotFound {
+            name,
+            available,
+            suggestions,
+        } => {
+            assert_eq!(name, "missing");
+            assert_eq!(available, vec!["bar".to_string(), "baz".to_string()]);
+            assert!(suggestions.is_empty());
+        }
+        other => panic!("expected OverlayError::NotFound, got {other:?}"),
+    }
+}
+
+#[rstest]
+fn test_resolve_overlay_suggests_a_close_typo(overlays_dir: tempfile::TempDir) {
+    std::fs::write(overlays_dir.path().join("staging.yaml"), "inherit: false\n").unwrap();
+    std::fs::write(
+        overlays_dir.path().join("production.yaml"),
+        "inherit: false\n",
+    )
+    .unwrap();
+
+    let err = resolve_overlay(overlays_dir.path(), "stagin").unwrap_err();
+    match err {
+        OverlayError::NotFound { suggestions, .. } => {
+            assert_eq!(suggestions, vec!["staging".to_string()]);
+        }
+        other => panic!("expected OverlayError::NotFound, got {other:?}"),
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Detecting and removing includes from a spec whose entire
+//! contribution is already shadowed by a layer composed after them.
+
+use std::path::{Path, PathBuf};
+
+use crate::compose::ComposedEnvironment;
+use crate::discovery::DiscoveryOptions;
+use crate::error::{ComposeError, PruneIncludesError};
+use crate::spec::{EnvOp, EnvSpec, IncludeEntry};
+
+#[cfg(test)]
+#[path = "prune_test.rs"]
+mod prune_test;
+
+/// Find the entries in `spec_path`'s own `includes:` list that are
+/// safe to remove because every variable they set is unconditionally
+/// overwritten by a layer composed after them, within the same
+/// spec's own include subtree.
+///
+/// This is deliberately conservative, and only proves an include dead
+/// when all of the following hold:
+/// - it has no `when` predicate, so dropping it can't change anything
+///   for a host it wasn't even active on
+/// - it contributes nothing but `set` operations: no includes or
+///   `inherit` of its own, no binds, contents, packages, priority,
+///   lock policy, `environment_exclude`, or note
+/// - every variable it sets also has a `set` in a layer composed
+///   after it
+///
+/// An include that only ever `prepend`s or `append`s, or sets a
+/// variable nothing later touches, is left alone, since removing it
+/// could change the resulting environment.
+pub fn redundant_includes(spec_path: &Path) -> Result<Vec<PathBuf>, ComposeError> {
+    let spec = EnvSpec::load_file(spec_path)?;
+    let base_dir = spec_path.parent().unwrap_or_else(|| Path::new("."));
+    let resolved = ComposedEnvironment::resolve_with_options(
+        base_dir,
+        DiscoveryOptions {
+            max_depth: Some(0),
+            ..Default::default()
+        },
+    )?;
+
+    let mut dead = Vec::new();
+    for include in &spec.includes {
+        let IncludeEntry::Path(declared) = include else {
+            continue;
+        };
+        let candidate = canonical(&base_dir.join(declared));
+        let Some(index) = resolved
+            .layers
+            .iter()
+            .position(|layer| canonical(&layer.file_path) == candidate)
+        else {
+            continue;
+        };
+
+        let layer = &resolved.layers[index];
+        if !is_prunable_leaf(layer) {
+            continue;
+        }
+
+        let set_vars: Vec<&str> = layer
+            .ops
+            .iter()
+            .filter_map(|op| match op {
+                EnvOp::Set { var, .. } => Some(var.as_str()),
+                _ => None,
+            })
+            .collect();
+        let later_layers = &resolved.layers[index + 1..];
+        let fully_shadowed = !set_vars.is_empty()
+            && set_vars
+                .iter()
+                .all(|var| later_layers.iter().any(|later| sets(later, var)));
+        if fully_shadowed {
+            dead.push(declared.clone());
+        }
+    }
+    Ok(dead)
+}
+
+/// True if `layer` contributes nothing beyond plain `set` operations:
+/// no includes or `inherit` of its own, no binds, contents, packages,
+/// priority, lock policy, `environment_exclude`, or note.
+fn is_prunable_leaf(layer: &EnvSpec) -> bool {
+    layer.includes.is_empty()
+        && !layer.inherit
+        && layer.binds.is_empty()
+        && layer.contents.is_empty()
+        && layer.packages.is_empty()
+        && layer.priority.is_none()
+        && layer.environment_exclude.is_empty()
+        && layer.lock.is_none()
+        && layer.note.is_none()
+        && layer.ops.iter().all(|op| matches!(op, EnvOp::Set { .. }))
+}
+
+fn sets(layer: &EnvSpec, var: &str) -> bool {
+    layer
+        .ops
+        .iter()
+        .any(|op| matches!(op, EnvOp::Set { var: v, .. } if v == var))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+/// Remove the entries in `dead` from `spec_path`'s `includes:` list,
+/// rewriting the file in place. `dead` must contain paths exactly as
+/// declared in the file, e.g. as returned by [`redundant_includes`].
+/// Every other top-level key, and the order of any remaining
+/// includes, is preserved; comments and formatting are not, since the
+/// file is rewritten through a generic YAML value rather than edited
+/// textually.
+pub fn remove_includes(spec_path: &Path, dead: &[PathBuf]) -> Result<(), PruneIncludesError> {
+    if dead.is_empty() {
+        return Ok(());
+    }
+
+    let contents =
+        std::fs::read_to_string(spec_path).map_err(|source| PruneIncludesError::ReadFailed {
+            path: spec_path.to_owned(),
+            source,
+        })?;
+    let mut doc: serde_yaml::Value =
+        serde_yaml::from_str(&contents).map_err(|source| PruneIncludesError::InvalidYaml {
+            path: spec_path.to_owned(),
+            source,
+        })?;
+
+    if let serde_yaml::Value::Mapping(map) = &mut doc
+        && let Some(serde_yaml::Value::Sequence(includes)) =
+            map.get_mut(serde_yaml::Value::String("includes".to_string()))
+    {
+        includes.retain(|entry| !entry_path(entry).is_some_and(|path| dead.contains(&path)));
+    }
+
+    let rewritten =
+        serde_yaml::to_string(&doc).map_err(|source| PruneIncludesError::Serialize {
+            path: spec_path.to_owned(),
+            source,
+        })?;
+    std::fs::write(spec_path, rewritten).map_err(|source| PruneIncludesError::WriteFailed {
+        path: spec_path.to_owned(),
+        source,
+    })
+}
+
+/// The path an `includes:` entry refers to, whether it's a bare
+/// string or a `{ path: ..., when: {...} }` mapping.
+fn entry_path(entry: &serde_yaml::Value) -> Option<PathBuf> {
+    match entry {
+        serde_yaml::Value::String(s) => Some(PathBuf::from(s)),
+        serde_yaml::Value::Mapping(m) => m
+            .get(serde_yaml::Value::String("path".to_string()))
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from),
+        _ => None,
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{redundant_includes, remove_includes};
+use crate::ComposedEnvironment;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_an_include_fully_overwritten_by_a_later_one_is_reported_as_redundant(
+    tmpdir: tempfile::TempDir,
+) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(&spec_path, "includes: [base.yaml, override.yaml]\n").unwrap();
+    std::fs::write(
+        root.join("base.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: vi\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("override.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    let dead = redundant_includes(&spec_path).unwrap();
+
+    assert_eq!(dead, vec![std::path::PathBuf::from("base.yaml")]);
+}
+
+#[rstest]
+fn test_an_include_with_a_variable_nothing_later_sets_is_not_redundant(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(&spec_path, "includes: [base.yaml, override.yaml]\n").unwrap();
+    std::fs::write(
+        root.join("base.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: vi\n  - op: set\n    var: PAGER\n    value: less\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("override.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    assert!(redundant_includes(&spec_path).unwrap().is_empty());
+}
+
+#[rstest]
+fn test_an_include_that_also_prepends_is_never_pruned(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(&spec_path, "includes: [base.yaml, override.yaml]\n").unwrap();
+    std::fs::write(
+        root.join("base.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: vi\n  - op: prepend\n    var: PATH\n    value: /base/bin\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("override.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    assert!(redundant_includes(&spec_path).unwrap().is_empty());
+}
+
+#[rstest]
+fn test_pruning_a_redundant_include_still_composes_identically(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(&spec_path, "includes: [base.yaml, override.yaml]\n").unwrap();
+    std::fs::write(
+        root.join("base.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: vi\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("override.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    let before = ComposedEnvironment::resolve(root).unwrap();
+    let dead = redundant_includes(&spec_path).unwrap();
+    remove_includes(&spec_path, &dead).unwrap();
+    let after = ComposedEnvironment::resolve(root).unwrap();
+
+    // The layer list itself shrinks by the pruned include, but the
+    // resulting environment variables are unchanged, since the
+    // pruned include's whole contribution was already shadowed.
+    assert_eq!(before.to_env_manifest().env, after.to_env_manifest().env);
+    assert!(
+        !std::fs::read_to_string(&spec_path)
+            .unwrap()
+            .contains("base.yaml")
+    );
+}
+
+#[rstest]
+fn test_remove_includes_preserves_the_order_of_remaining_includes(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    let spec_path = root.join(".spenv.yaml");
+    std::fs::write(
+        &spec_path,
+        "includes: [a.yaml, dead.yaml, b.yaml]\npriority: 1\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("a.yaml"),
+        "ops:\n  - op: set\n    var: A\n    value: a\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("dead.yaml"),
+        "ops:\n  - op: set\n    var: A\n    value: shadowed\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("b.yaml"),
+        "ops:\n  - op: set\n    var: B\n    value: b\n",
+    )
+    .unwrap();
+
+    remove_includes(&spec_path, &[std::path::PathBuf::from("dead.yaml")]).unwrap();
+
+    let spec = crate::EnvSpec::load_file(&spec_path).unwrap();
+    let paths: Vec<_> = spec.includes.iter().map(|i| i.path().to_owned()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            std::path::PathBuf::from("a.yaml"),
+            std::path::PathBuf::from("b.yaml"),
+        ]
+    );
+    assert_eq!(spec.priority, Some(1));
+}
@@ -0,0 +1,81 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Fetching `http://`/`https://` includes and caching them on disk,
+//! behind the `remote-includes` feature.
+
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::ComposeError;
+
+#[cfg(test)]
+#[path = "remote_test.rs"]
+mod remote_test;
+
+/// The subdirectory created under the platform cache directory.
+const CACHE_SUBDIR: &str = "spenv/includes";
+
+/// Returns true if `include` names a remote spec, fetched over
+/// HTTP(S), rather than a local file.
+pub fn is_remote_include(include: &str) -> bool {
+    include.starts_with("http://") || include.starts_with("https://")
+}
+
+/// Resolve a `http://`/`https://` include to a local file, fetching
+/// it into the on-disk cache if necessary.
+pub fn resolve_remote_include(url: &str) -> Result<PathBuf, ComposeError> {
+    let cache_dir = dirs::cache_dir()
+        .map(|dir| dir.join(CACHE_SUBDIR))
+        .ok_or_else(|| ComposeError::IncludeFetchFailed {
+            url: url.to_string(),
+            error: "could not determine a cache directory for this platform".to_string(),
+        })?;
+    fetch_and_cache(&cache_dir, url)
+}
+
+/// Resolve `url` against `cache_dir`.
+///
+/// A successful fetch always refreshes the cached copy. A failed
+/// fetch falls back to a previously cached copy, if one exists,
+/// printing a warning; an unreachable or malformed URL with no
+/// cached copy is a hard error.
+fn fetch_and_cache(cache_dir: &Path, url: &str) -> Result<PathBuf, ComposeError> {
+    let cache_path = cache_path_for(cache_dir, url);
+    match reqwest::blocking::get(url).and_then(|response| response.error_for_status()) {
+        Ok(response) => {
+            let contents = response.text().map_err(|error| fetch_failed(url, &error))?;
+            std::fs::create_dir_all(cache_dir)
+                .and_then(|()| std::fs::write(&cache_path, contents))
+                .map_err(|error| fetch_failed(url, &error))?;
+        }
+        Err(error) if cache_path.is_file() => {
+            eprintln!("warning: failed to fetch {url}, using cached copy: {error}");
+        }
+        Err(error) => return Err(fetch_failed(url, &error)),
+    }
+    Ok(cache_path)
+}
+
+fn fetch_failed(url: &str, error: &impl std::fmt::Display) -> ComposeError {
+    ComposeError::IncludeFetchFailed {
+        url: url.to_string(),
+        error: error.to_string(),
+    }
+}
+
+/// The cache file a given URL resolves to.
+///
+/// Normalizing the URL first means trivially different spellings of
+/// the same address (a trailing slash, differing case) share the same
+/// cache entry, and therefore the same circular-include detection key.
+fn cache_path_for(cache_dir: &Path, url: &str) -> PathBuf {
+    let digest = Sha256::digest(normalize_url(url).as_bytes());
+    cache_dir.join(format!("{digest:x}.yaml"))
+}
+
+fn normalize_url(url: &str) -> String {
+    url.trim_end_matches('/').to_ascii_lowercase()
+}
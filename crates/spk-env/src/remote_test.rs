@@ -0,0 +1,73 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+use super::{cache_path_for, fetch_and_cache, is_remote_include};
+
+#[test]
+fn test_is_remote_include_recognizes_http_and_https() {
+    assert!(is_remote_include("https://example.com/a.yaml"));
+    assert!(is_remote_include("http://example.com/a.yaml"));
+    assert!(!is_remote_include("relative/a.yaml"));
+}
+
+#[test]
+fn test_cache_path_for_is_stable_across_trivial_url_variants() {
+    let cache_dir = std::path::Path::new("/cache");
+    assert_eq!(
+        cache_path_for(cache_dir, "https://Example.com/a.yaml/"),
+        cache_path_for(cache_dir, "https://example.com/a.yaml"),
+    );
+}
+
+#[test]
+fn test_fetch_and_cache_writes_the_fetched_contents() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let url = serve_once("packages: [remote-pkg]\n");
+
+    let cached = fetch_and_cache(cache_dir.path(), &url).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(cached).unwrap(),
+        "packages: [remote-pkg]\n"
+    );
+}
+
+#[test]
+fn test_fetch_and_cache_falls_back_to_a_stale_cache_entry_when_unreachable() {
+    let cache_dir = tempfile::tempdir().unwrap();
+    let url = "http://127.0.0.1:1/unreachable.yaml";
+    let cache_path = cache_path_for(cache_dir.path(), url);
+    std::fs::write(&cache_path, "packages: [cached-pkg]\n").unwrap();
+
+    let resolved = fetch_and_cache(cache_dir.path(), url).unwrap();
+
+    assert_eq!(resolved, cache_path);
+    assert_eq!(
+        std::fs::read_to_string(resolved).unwrap(),
+        "packages: [cached-pkg]\n"
+    );
+}
+
+/// Serve `body` once to the first connection received, on a random
+/// local port, returning a URL that points at it.
+fn serve_once(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut buf = [0u8; 1024];
+            let _ = stream.read(&mut buf);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    format!("http://{addr}/spec.yaml")
+}
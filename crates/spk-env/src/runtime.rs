@@ -0,0 +1,618 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Applying a [`ComposedEnvironment`]'s operations to a real process
+//! environment, for `spenv load` and `spenv shell`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::ComposedEnvironment;
+use crate::error::RuntimeError;
+use crate::lock::EnvLock;
+use crate::spec::EnvOp;
+
+#[cfg(test)]
+#[path = "runtime_test.rs"]
+mod runtime_test;
+
+/// The environment variable a spenv runtime sets in its child
+/// process, recording that the process is running inside an active
+/// spenv runtime. `spenv load` consults this to guard against
+/// accidentally nesting one runtime inside another.
+pub const SPENV_ACTIVE_VAR: &str = "SPENV_ACTIVE";
+
+/// The marker left by an already-active spenv runtime in the current
+/// process environment, if any.
+pub fn active_runtime() -> Option<String> {
+    std::env::var(SPENV_ACTIVE_VAR).ok()
+}
+
+/// Apply every environment variable operation in `env`, in layer
+/// order, starting from the current process environment.
+pub fn resolve_env_vars(env: &ComposedEnvironment) -> HashMap<String, String> {
+    resolve_env_vars_with_lock(env, None)
+}
+
+/// Apply every environment variable operation in `env`, in layer
+/// order, starting from the current process environment.
+///
+/// When `lock` is given, any op value containing the
+/// `${SPENV_FINGERPRINT}` or `${SPENV_LOCKED_AT}` placeholders has
+/// them expanded to that lock's recorded fingerprint and generation
+/// time. Absent a lock, both placeholders expand to the empty string.
+pub fn resolve_env_vars_with_lock(
+    env: &ComposedEnvironment,
+    lock: Option<&EnvLock>,
+) -> HashMap<String, String> {
+    let mut vars: HashMap<String, String> = std::env::vars().collect();
+    for op in env.effective_ops() {
+        apply_op(&mut vars, &expand_placeholders(op, lock));
+    }
+    vars
+}
+
+/// Expand the `${SPENV_FINGERPRINT}` and `${SPENV_LOCKED_AT}`
+/// placeholders in an op's value(s), using metadata from `lock`.
+fn expand_placeholders(op: EnvOp, lock: Option<&EnvLock>) -> EnvOp {
+    let fingerprint = lock.map(|l| l.metadata.fingerprint.as_str()).unwrap_or("");
+    let locked_at = lock
+        .map(|l| l.metadata.generated_at.to_string())
+        .unwrap_or_default();
+    let expand = |value: String| {
+        value
+            .replace("${SPENV_FINGERPRINT}", fingerprint)
+            .replace("${SPENV_LOCKED_AT}", &locked_at)
+    };
+    match op {
+        EnvOp::Set { var, value } => EnvOp::Set {
+            var,
+            value: expand(value),
+        },
+        EnvOp::Prepend { var, value } => EnvOp::Prepend {
+            var,
+            value: expand(value),
+        },
+        EnvOp::Append { var, value } => EnvOp::Append {
+            var,
+            value: expand(value),
+        },
+        EnvOp::Default { var, value } => EnvOp::Default {
+            var,
+            value: expand(value),
+        },
+        EnvOp::PathRemove {
+            var,
+            value,
+            separator,
+        } => EnvOp::PathRemove {
+            var,
+            value: expand(value),
+            separator,
+        },
+        EnvOp::Unset { var } => EnvOp::Unset { var },
+        EnvOp::Source { source } => EnvOp::Source {
+            source: expand(source),
+        },
+    }
+}
+
+/// Which shell dialect a generated startup script should target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[clap(rename_all = "lower")]
+pub enum ShellKind {
+    /// POSIX-compatible shells (bash, zsh, dash, ...), using `export`
+    Sh,
+    /// `csh`/`tcsh`, using `setenv`
+    Csh,
+    /// `fish`, using `set -gx`
+    Fish,
+}
+
+impl ShellKind {
+    /// Guess a shell dialect from a `$SHELL`-style path, falling back
+    /// to [`ShellKind::Sh`] for anything unrecognized.
+    pub fn from_shell_path(shell: &str) -> Self {
+        match Path::new(shell).file_name().and_then(|n| n.to_str()) {
+            Some("csh") | Some("tcsh") => ShellKind::Csh,
+            Some("fish") => ShellKind::Fish,
+            _ => ShellKind::Sh,
+        }
+    }
+}
+
+/// Render every environment variable operation in `env`, in layer
+/// order, as a startup script a user can `eval`/`source` in their
+/// shell of choice.
+///
+/// `lock` is used the same way as in [`resolve_env_vars_with_lock`],
+/// expanding any `${SPENV_FINGERPRINT}`/`${SPENV_LOCKED_AT}`
+/// placeholders before the value is rendered.
+pub fn generate_startup_script(
+    env: &ComposedEnvironment,
+    lock: Option<&EnvLock>,
+    shell: ShellKind,
+) -> String {
+    let mut script = String::new();
+    for op in env.effective_ops() {
+        let line = render_op_line(shell, expand_placeholders(op, lock));
+        script.push_str(&line);
+        script.push('\n');
+    }
+    script
+}
+
+/// One startup script for a single priority group, named following
+/// the `startup.d` convention of ordering scripts by a numeric prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityScript {
+    /// The priority this script's ops were contributed at
+    pub priority: i32,
+    /// The `startup.d`-style filename for this script
+    pub filename: String,
+    /// The rendered script contents
+    pub script: String,
+    /// A content hash of `script`, the same kind of digest
+    /// [`crate::lock::hash_contents`] uses elsewhere to detect
+    /// unchanged content. Two groups whose ops render to
+    /// byte-identical text always share this digest
+    pub digest: String,
+}
+
+/// Options for [`generate_startup_scripts_by_priority_with_options`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuntimeOptions {
+    /// Added to each group's priority before it's rendered into a
+    /// `startup.d` filename, so spenv's scripts can be placed in a
+    /// band reserved for it (e.g. a base of `80` turns priority `5`
+    /// into `85_spenv.sh`) rather than colliding or mis-ordering with
+    /// other tools writing into the same `startup.d` directory at the
+    /// raw priority values spenv computes on its own.
+    pub priority_base: i32,
+    /// Emit one startup script per contributing spec, numbered by
+    /// that spec's own `priority` (defaulting to 50), instead of one
+    /// script per distinct priority value shared by consecutive
+    /// layers. Useful when ordering between individual specs needs to
+    /// be explicit even if two of them happen to share a priority.
+    pub split_startup_scripts: bool,
+}
+
+/// Like [`generate_startup_script`], but split into one script per
+/// distinct priority value instead of a single combined script,
+/// preserving each group's op order, so they can interleave correctly
+/// with other `startup.d` scripts at those priorities. Consecutive
+/// layers sharing a priority are grouped into the same script.
+///
+/// A priority group whose ops all render to nothing is skipped
+/// entirely, rather than adding an empty file to `startup.d` for no
+/// benefit.
+pub fn generate_startup_scripts_by_priority(
+    env: &ComposedEnvironment,
+    lock: Option<&EnvLock>,
+    shell: ShellKind,
+) -> Vec<PriorityScript> {
+    generate_startup_scripts_by_priority_with_options(env, lock, shell, RuntimeOptions::default())
+}
+
+/// Like [`generate_startup_scripts_by_priority`], with `options`
+/// controlling how each group's priority is placed in the generated
+/// filename, and whether groups are formed per distinct priority
+/// value (the default) or one per contributing spec (see
+/// [`RuntimeOptions::split_startup_scripts`]).
+pub fn generate_startup_scripts_by_priority_with_options(
+    env: &ComposedEnvironment,
+    lock: Option<&EnvLock>,
+    shell: ShellKind,
+    options: RuntimeOptions,
+) -> Vec<PriorityScript> {
+    let groups: Vec<(i32, Vec<EnvOp>)> = if options.split_startup_scripts {
+        env.effective_ops_by_layer()
+    } else {
+        let mut groups: Vec<(i32, Vec<EnvOp>)> = Vec::new();
+        for (priority, op) in env.effective_ops_with_priority() {
+            match groups.last_mut() {
+                Some((group_priority, ops)) if *group_priority == priority => ops.push(op),
+                _ => groups.push((priority, vec![op])),
+            }
+        }
+        groups
+    };
+
+    // Two groups can expand to the same ops (e.g. the same spec
+    // reached through both `inherit: true` and an unrelated spec's
+    // `includes`, see `ComposedEnvironment::cross_mechanism_duplicates`),
+    // which always render to byte-identical script text. Cache by the
+    // expanded ops so a group already rendered this call is reused
+    // instead of re-escaping and re-formatting the same lines again.
+    let mut rendered: HashMap<Vec<EnvOp>, (String, String)> = HashMap::new();
+
+    groups
+        .into_iter()
+        .filter_map(|(priority, ops)| {
+            let expanded: Vec<EnvOp> = ops
+                .into_iter()
+                .map(|op| expand_placeholders(op, lock))
+                .collect();
+            let (script, digest) = rendered
+                .entry(expanded.clone())
+                .or_insert_with(|| {
+                    let mut script = String::new();
+                    for op in expanded {
+                        let line = render_op_line(shell, op);
+                        script.push_str(&line);
+                        script.push('\n');
+                    }
+                    let digest = crate::lock::hash_contents(&script);
+                    (script, digest)
+                })
+                .clone();
+            if script.trim().is_empty() {
+                tracing::debug!(
+                    priority,
+                    "skipping empty startup.d script, no ops render any content"
+                );
+                return None;
+            }
+            Some(PriorityScript {
+                filename: format!("{:02}_spenv.sh", priority + options.priority_base),
+                priority,
+                script,
+                digest,
+            })
+        })
+        .collect()
+}
+
+/// Render a single environment op as a line of `shell`'s syntax.
+///
+/// An untrusted spec's op reaches the generated script through three
+/// distinct positions, each closed by its own defense rather than a
+/// single shared one: a value sitting inside a double-quoted literal
+/// is escaped by [`escape_value`]; a `var` name sits unquoted on the
+/// left-hand side of an `export`/`setenv`/`set -gx` and can't itself
+/// be wrapped in quotes, so it's validated by [`is_valid_var_name`]
+/// and the whole op rejected (as a comment) rather than rendered if
+/// it isn't a well-formed identifier; and `path_remove`'s value and
+/// separator are interpolated into a `sed` pipeline run under command
+/// substitution, so they're validated by
+/// [`is_safe_path_remove_component`] instead of escaped. All three
+/// must hold for the output to be safe — escaping values alone still
+/// leaves the other two positions open.
+fn render_op_line(shell: ShellKind, op: EnvOp) -> String {
+    let var = op.var();
+    if !var.is_empty() && !is_valid_var_name(var) {
+        return format!("# spenv: skipping op for invalid variable name {var:?}");
+    }
+    match op {
+        EnvOp::Set { var, value } => set_line(shell, &var, &escape_value(shell, &value)),
+        EnvOp::Prepend { var, value } => {
+            let value = escape_value(shell, &value);
+            set_line(shell, &var, &format!("{value}{}${var}", path_sep()))
+        }
+        EnvOp::Append { var, value } => {
+            let value = escape_value(shell, &value);
+            set_line(shell, &var, &format!("${var}{}{value}", path_sep()))
+        }
+        EnvOp::Default { var, value } => default_line(shell, &var, &escape_value(shell, &value)),
+        EnvOp::PathRemove {
+            var,
+            value,
+            separator,
+        } => {
+            let separator = separator.as_deref().unwrap_or(":");
+            if !is_safe_path_remove_component(&value) || !is_safe_path_remove_component(separator) {
+                return format!(
+                    "# spenv: skipping path_remove for {var}: value or separator contains unsupported characters"
+                );
+            }
+            path_remove_line(shell, &var, &value, separator)
+        }
+        EnvOp::Unset { var } => unset_line(shell, &var),
+        EnvOp::Source { source } => source_line(shell, &escape_value(shell, &source)),
+    }
+}
+
+/// Whether `var` is safe to splice, unquoted, into a generated shell
+/// line as a variable name: a leading letter or underscore, followed
+/// by only letters, digits and underscores, the same charset every
+/// shell accepts in that position. Rejecting anything else closes off
+/// variable names as an injection vector that no amount of escaping
+/// `value` can address, since the name itself is never quoted.
+fn is_valid_var_name(var: &str) -> bool {
+    let mut chars = var.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Escape `value` so it can be embedded, verbatim, inside a
+/// double-quoted string literal in `shell`'s syntax, without a
+/// `"`, `$`, backtick or embedded newline in a spec value breaking
+/// out of the literal or being interpreted by the shell.
+fn escape_value(shell: ShellKind, value: &str) -> String {
+    match shell {
+        ShellKind::Sh => escape_sh_value(value),
+        ShellKind::Csh => escape_csh_value(value),
+        ShellKind::Fish => escape_fish_value(value),
+    }
+}
+
+/// Escape `value` for a POSIX shell (bash, zsh, dash, ...)
+/// double-quoted string: backslash, `"`, `$` and backtick are
+/// backslash-escaped, and an embedded newline is rendered as the
+/// two-character sequence `\n` rather than left as a literal line
+/// break, which would otherwise turn a single assignment into a
+/// broken multi-line `export`.
+pub(crate) fn escape_sh_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | '"' | '$' | '`' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Like [`escape_sh_value`], for `csh`/`tcsh`. Also escapes `!`,
+/// since csh's history expansion triggers on an unescaped `!` even
+/// inside a double-quoted string.
+pub(crate) fn escape_csh_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | '"' | '$' | '`' | '!' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Like [`escape_sh_value`], for `fish`. Fish has no backtick
+/// command substitution, so backticks are left untouched.
+pub(crate) fn escape_fish_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' | '"' | '$' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            '\n' => escaped.push_str("\\n"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/// Render a single variable assignment in `shell`'s syntax.
+fn set_line(shell: ShellKind, var: &str, value: &str) -> String {
+    match shell {
+        ShellKind::Sh => format!("export {var}=\"{value}\""),
+        ShellKind::Csh => format!("setenv {var} \"{value}\""),
+        ShellKind::Fish => format!("set -gx {var} \"{value}\""),
+    }
+}
+
+/// Render a fallback assignment, applied only if `var` is still unset
+/// by the time the script runs, in `shell`'s syntax.
+fn default_line(shell: ShellKind, var: &str, value: &str) -> String {
+    match shell {
+        ShellKind::Sh => format!(": \"${{{var}:={value}}}\""),
+        ShellKind::Csh => format!("if (! $?{var}) setenv {var} \"{value}\""),
+        ShellKind::Fish => format!("set -q {var}; or set -gx {var} \"{value}\""),
+    }
+}
+
+/// Render a line that sources `path` in `shell`'s syntax, guarded by
+/// an existence check so a startup script composed from multiple
+/// hosts doesn't fail outright when an optional script isn't present
+/// on this one.
+fn source_line(shell: ShellKind, path: &str) -> String {
+    match shell {
+        ShellKind::Sh => format!("[ -f \"{path}\" ] && . \"{path}\""),
+        ShellKind::Csh => format!("if ( -f \"{path}\" ) source \"{path}\""),
+        ShellKind::Fish => format!("test -f \"{path}\"; and source \"{path}\""),
+    }
+}
+
+/// Render a single variable removal in `shell`'s syntax.
+fn unset_line(shell: ShellKind, var: &str) -> String {
+    match shell {
+        ShellKind::Sh => format!("unset {var}"),
+        ShellKind::Csh => format!("unsetenv {var}"),
+        ShellKind::Fish => format!("set -e {var}"),
+    }
+}
+
+/// Render a line that reassigns `var` to its current value with the
+/// `value` component stripped out, in `shell`'s syntax.
+fn path_remove_line(shell: ShellKind, var: &str, value: &str, separator: &str) -> String {
+    let filter = path_remove_filter(value, separator);
+    match shell {
+        ShellKind::Sh => format!("export {var}=\"$(printf '%s' \"${var}\" | {filter})\""),
+        ShellKind::Csh => format!("setenv {var} \"`printf '%s' \"${var}\" | {filter}`\""),
+        ShellKind::Fish => format!("set -gx {var} (printf '%s' \"${var}\" | {filter})"),
+    }
+}
+
+/// A `sed` pipeline that strips an exact `value` component out of a
+/// `separator`-joined string, wherever it occurs: at the start, the
+/// end, the middle, or as the only component.
+///
+/// Uses `#` as the `sed` delimiter, instead of the customary `/`,
+/// since `value` is usually a filesystem path and so very likely to
+/// contain `/` itself.
+fn path_remove_filter(value: &str, separator: &str) -> String {
+    // `sed` reads each pattern as a basic regular expression, so the
+    // characters special there are escaped to match literally; `$` and
+    // `\` never get this far (see `is_safe_path_remove_component`).
+    let literal = |text: &str| {
+        text.chars().fold(String::new(), |mut out, c| {
+            if matches!(c, '.' | '*' | '[' | '^') {
+                out.push('\\');
+            }
+            out.push(c);
+            out
+        })
+    };
+    let (pattern, sep) = (literal(value), literal(separator));
+    format!(
+        "sed -e 's#^{pattern}{sep}##' -e 's#{sep}{pattern}$##' -e 's#{sep}{pattern}{sep}#{separator}#' -e '\\#^{pattern}$#d'"
+    )
+}
+
+/// Whether `value` is safe to splice into [`path_remove_filter`]'s
+/// `sed` pipeline, itself embedded in a single-quoted shell literal
+/// that is run under `$(...)`/backtick command substitution.
+///
+/// `sed`'s own `'...'` quoting gives no way to escape a character that
+/// would otherwise break out of it, so unlike [`escape_value`] this
+/// rejects unsafe input outright rather than trying to neutralize it:
+/// a single quote would close the `sed -e '...'` literal early and let
+/// the rest of the value run as shell text inside the substitution,
+/// and `#`, as the pipeline's own `sed` delimiter, would be
+/// reinterpreted as a third delimiter rather than literal text.
+fn is_safe_path_remove_component(value: &str) -> bool {
+    !value
+        .chars()
+        .any(|c| matches!(c, '\'' | '"' | '`' | '$' | '\\' | '#' | '\n'))
+}
+
+/// Validate a requested `--cwd` against the filesystem, returning the
+/// directory to start the runtime in, if one was given.
+pub fn resolve_cwd(cwd: Option<&Path>) -> Result<Option<PathBuf>, RuntimeError> {
+    let Some(cwd) = cwd else {
+        return Ok(None);
+    };
+    if !cwd.is_dir() {
+        return Err(RuntimeError::CwdNotFound(cwd.to_owned()));
+    }
+    Ok(Some(cwd.to_owned()))
+}
+
+/// A user (and optional group) to spawn the runtime command as,
+/// parsed from `spenv load --as-user <uid[:gid]>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsUser {
+    /// The uid to run the command as
+    pub uid: u32,
+    /// The gid to run the command as, defaulting to the target uid's
+    /// primary group if not given
+    pub gid: Option<u32>,
+}
+
+impl std::str::FromStr for AsUser {
+    type Err = RuntimeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || RuntimeError::InvalidAsUser(s.to_string());
+        let (uid, gid) = match s.split_once(':') {
+            Some((uid, gid)) => (uid, Some(gid.parse::<u32>().map_err(|_| invalid())?)),
+            None => (s, None),
+        };
+        let uid = uid.parse::<u32>().map_err(|_| invalid())?;
+        Ok(Self { uid, gid })
+    }
+}
+
+impl std::fmt::Display for AsUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.gid {
+            Some(gid) => write!(f, "{}:{gid}", self.uid),
+            None => write!(f, "{}", self.uid),
+        }
+    }
+}
+
+/// True if the current process has enough privilege to spawn a child
+/// as a different user. Only root, or a process already running as
+/// the requested user, can do so.
+pub fn can_run_as(as_user: &AsUser) -> bool {
+    let euid = nix::unistd::geteuid();
+    euid.is_root() || euid.as_raw() == as_user.uid
+}
+
+/// Validate a requested `--as-user`, erroring if the current process
+/// lacks the privilege to spawn a command as that user.
+pub fn resolve_as_user(as_user: Option<AsUser>) -> Result<Option<AsUser>, RuntimeError> {
+    let Some(as_user) = as_user else {
+        return Ok(None);
+    };
+    if !can_run_as(&as_user) {
+        return Err(RuntimeError::AsUserNotPermitted(as_user));
+    }
+    Ok(Some(as_user))
+}
+
+/// Apply a single environment variable operation to `vars` in place.
+pub fn apply_op(vars: &mut HashMap<String, String>, op: &EnvOp) {
+    match op {
+        EnvOp::Set { var, value } => {
+            vars.insert(var.clone(), value.clone());
+        }
+        EnvOp::Prepend { var, value } => {
+            let joined = match vars.get(var) {
+                Some(existing) if !existing.is_empty() => {
+                    format!("{value}{}{existing}", path_sep())
+                }
+                _ => value.clone(),
+            };
+            vars.insert(var.clone(), joined);
+        }
+        EnvOp::Append { var, value } => {
+            let joined = match vars.get(var) {
+                Some(existing) if !existing.is_empty() => {
+                    format!("{existing}{}{value}", path_sep())
+                }
+                _ => value.clone(),
+            };
+            vars.insert(var.clone(), joined);
+        }
+        EnvOp::Default { var, value } => {
+            vars.entry(var.clone()).or_insert_with(|| value.clone());
+        }
+        EnvOp::PathRemove {
+            var,
+            value,
+            separator,
+        } => {
+            let separator = separator.as_deref().unwrap_or(":");
+            if let Some(existing) = vars.get(var) {
+                let filtered = existing
+                    .split(separator)
+                    .filter(|component| component != value)
+                    .collect::<Vec<_>>()
+                    .join(separator);
+                vars.insert(var.clone(), filtered);
+            }
+        }
+        EnvOp::Unset { var } => {
+            vars.remove(var);
+        }
+        EnvOp::Source { .. } => {
+            // Sourcing a script only makes sense inside a generated
+            // shell startup script (see `render_op_line`); it has no
+            // effect on a resolved in-process variable map, since
+            // there's no shell here to run it in.
+        }
+    }
+}
+
+#[cfg(unix)]
+fn path_sep() -> char {
+    ':'
+}
+
+#[cfg(windows)]
+fn path_sep() -> char {
+    ';'
+}
@@ -0,0 +1,584 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use super::{
+    AsUser, RuntimeOptions, ShellKind, can_run_as, escape_csh_value, escape_fish_value,
+    escape_sh_value, generate_startup_script, generate_startup_scripts_by_priority,
+    generate_startup_scripts_by_priority_with_options, resolve_env_vars,
+    resolve_env_vars_with_lock,
+};
+use crate::ComposedEnvironment;
+use crate::lock::{EnvLock, GenerationMetadata};
+use crate::spec::EnvSpec;
+
+fn layer(ops_yaml: &str) -> EnvSpec {
+    serde_yaml::from_str(&format!("ops:\n{ops_yaml}")).unwrap()
+}
+
+#[test]
+fn test_unset_with_nothing_after_it_removes_the_variable() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: PYTHONPATH\n    value: /base/python\n  - op: unset\n    var: PYTHONPATH\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert!(!vars.contains_key("PYTHONPATH"));
+}
+
+#[test]
+fn test_a_later_layers_set_still_wins_over_an_earlier_unset() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            layer(
+                "  - op: set\n    var: PYTHONPATH\n    value: /base/python\n  - op: unset\n    var: PYTHONPATH\n",
+            ),
+            layer("  - op: set\n    var: PYTHONPATH\n    value: /later/python\n"),
+        ],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("PYTHONPATH"), Some(&"/later/python".to_string()));
+}
+
+#[test]
+fn test_fingerprint_placeholder_expands_from_the_lock() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: BUILD_TAG\n    value: build-${SPENV_FINGERPRINT}\n",
+        )],
+        ..Default::default()
+    };
+    let lock = EnvLock {
+        metadata: GenerationMetadata {
+            fingerprint: "deadbeef".to_string(),
+            ..Default::default()
+        },
+    };
+
+    let vars = resolve_env_vars_with_lock(&env, Some(&lock));
+    assert_eq!(vars.get("BUILD_TAG"), Some(&"build-deadbeef".to_string()));
+}
+
+#[test]
+fn test_fingerprint_placeholder_expands_to_empty_without_a_lock() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: BUILD_TAG\n    value: build-${SPENV_FINGERPRINT}\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("BUILD_TAG"), Some(&"build-".to_string()));
+}
+
+#[test]
+fn test_generate_startup_script_uses_each_shells_native_syntax() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: DEBUG\n    value: '1'\n  - op: prepend\n    var: PATH\n    value: /pkg/bin\n  - op: unset\n    var: SCRATCH\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains("export DEBUG=\"1\""));
+    assert!(sh.contains("export PATH=\"/pkg/bin:$PATH\""));
+    assert!(sh.contains("unset SCRATCH"));
+
+    let csh = generate_startup_script(&env, None, ShellKind::Csh);
+    assert!(csh.contains("setenv DEBUG \"1\""));
+    assert!(csh.contains("setenv PATH \"/pkg/bin:$PATH\""));
+    assert!(csh.contains("unsetenv SCRATCH"));
+
+    let fish = generate_startup_script(&env, None, ShellKind::Fish);
+    assert!(fish.contains("set -gx DEBUG \"1\""));
+    assert!(fish.contains("set -gx PATH \"/pkg/bin:$PATH\""));
+    assert!(fish.contains("set -e SCRATCH"));
+}
+
+#[test]
+fn test_escape_sh_value_covers_every_special_character() {
+    let cases = [
+        ("plain", "plain"),
+        (r#"has "quotes""#, r#"has \"quotes\""#),
+        ("has $dollar", r"has \$dollar"),
+        ("has `backtick`", r"has \`backtick\`"),
+        (r"has \backslash", r"has \\backslash"),
+        ("has\nnewline", r"has\nnewline"),
+        (r#"$(rm -rf "/")"#, r#"\$(rm -rf \"/\")"#),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(escape_sh_value(input), expected, "input: {input:?}");
+    }
+}
+
+#[test]
+fn test_escape_csh_value_also_escapes_history_expansion() {
+    let cases = [
+        ("plain", "plain"),
+        (r#"has "quotes""#, r#"has \"quotes\""#),
+        ("has $dollar", r"has \$dollar"),
+        ("has `backtick`", r"has \`backtick\`"),
+        (r"has \backslash", r"has \\backslash"),
+        ("has\nnewline", r"has\nnewline"),
+        ("has !history", r"has \!history"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(escape_csh_value(input), expected, "input: {input:?}");
+    }
+}
+
+#[test]
+fn test_escape_fish_value_leaves_backticks_alone() {
+    let cases = [
+        ("plain", "plain"),
+        (r#"has "quotes""#, r#"has \"quotes\""#),
+        ("has $dollar", r"has \$dollar"),
+        ("has `backtick`", "has `backtick`"),
+        (r"has \backslash", r"has \\backslash"),
+        ("has\nnewline", r"has\nnewline"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(escape_fish_value(input), expected, "input: {input:?}");
+    }
+}
+
+#[test]
+fn test_generate_startup_script_escapes_an_untrusted_value_per_shell() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: GREETING\n    value: '$(rm -rf \"/\") `and` this'\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains(r#"export GREETING="\$(rm -rf \"/\") \`and\` this""#));
+
+    let csh = generate_startup_script(&env, None, ShellKind::Csh);
+    assert!(csh.contains(r#"setenv GREETING "\$(rm -rf \"/\") \`and\` this""#));
+
+    let fish = generate_startup_script(&env, None, ShellKind::Fish);
+    assert!(fish.contains(r#"set -gx GREETING "\$(rm -rf \"/\") `and` this""#));
+}
+
+#[test]
+fn test_generate_startup_scripts_by_priority_splits_into_one_script_per_priority() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str(
+                "priority: 10\nops:\n  - op: set\n    var: BASE\n    value: base\n",
+            )
+            .unwrap(),
+            serde_yaml::from_str("priority: 20\nops:\n  - op: set\n    var: PKG\n    value: pkg\n")
+                .unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority(&env, None, ShellKind::Sh);
+
+    assert_eq!(scripts.len(), 2);
+    assert_eq!(scripts[0].priority, 10);
+    assert_eq!(scripts[0].filename, "10_spenv.sh");
+    assert!(scripts[0].script.contains("export BASE=\"base\""));
+    assert_eq!(scripts[1].priority, 20);
+    assert_eq!(scripts[1].filename, "20_spenv.sh");
+    assert!(scripts[1].script.contains("export PKG=\"pkg\""));
+}
+
+#[test]
+fn test_generate_startup_scripts_by_priority_groups_consecutive_layers_sharing_one() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str("priority: 5\nops:\n  - op: set\n    var: A\n    value: a\n")
+                .unwrap(),
+            serde_yaml::from_str("ops:\n  - op: set\n    var: B\n    value: b\n").unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority(&env, None, ShellKind::Sh);
+
+    assert_eq!(scripts.len(), 1);
+    assert!(scripts[0].script.contains("export A=\"a\""));
+    assert!(scripts[0].script.contains("export B=\"b\""));
+}
+
+#[test]
+fn test_generate_startup_scripts_by_priority_with_options_applies_a_priority_base() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str(
+                "priority: 5\nops:\n  - op: set\n    var: BASE\n    value: base\n",
+            )
+            .unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority_with_options(
+        &env,
+        None,
+        ShellKind::Sh,
+        RuntimeOptions {
+            priority_base: 80,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(scripts.len(), 1);
+    // The group's own priority is unaffected; only the filename band shifts.
+    assert_eq!(scripts[0].priority, 5);
+    assert_eq!(scripts[0].filename, "85_spenv.sh");
+}
+
+#[test]
+fn test_generate_startup_scripts_by_priority_with_options_splits_one_script_per_spec() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str(
+                "priority: 10\nops:\n  - op: set\n    var: BASE\n    value: base\n",
+            )
+            .unwrap(),
+            // Shares priority 10 with the layer above, but should
+            // still get its own script when split_startup_scripts is set.
+            serde_yaml::from_str(
+                "priority: 10\nops:\n  - op: set\n    var: PROJECT\n    value: project\n",
+            )
+            .unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority_with_options(
+        &env,
+        None,
+        ShellKind::Sh,
+        RuntimeOptions {
+            split_startup_scripts: true,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(scripts.len(), 2);
+    assert!(scripts[0].script.contains("export BASE=\"base\""));
+    assert!(scripts[1].script.contains("export PROJECT=\"project\""));
+}
+
+#[test]
+fn test_two_identical_composed_environments_yield_the_same_script_digest() {
+    let spec_yaml = "priority: 10\nops:\n  - op: set\n    var: BASE\n    value: base\n";
+    let one = ComposedEnvironment {
+        layers: vec![serde_yaml::from_str(spec_yaml).unwrap()],
+        ..Default::default()
+    };
+    let other = ComposedEnvironment {
+        layers: vec![serde_yaml::from_str(spec_yaml).unwrap()],
+        ..Default::default()
+    };
+
+    let one_scripts = generate_startup_scripts_by_priority(&one, None, ShellKind::Sh);
+    let other_scripts = generate_startup_scripts_by_priority(&other, None, ShellKind::Sh);
+
+    assert_eq!(one_scripts[0].script, other_scripts[0].script);
+    assert_eq!(one_scripts[0].digest, other_scripts[0].digest);
+}
+
+#[test]
+fn test_a_layer_reachable_two_ways_only_renders_its_script_once() {
+    // Two groups whose ops are identical render to the same script
+    // text and must share one digest, without re-running the render
+    // loop for the second occurrence.
+    let spec_yaml = "priority: 10\nops:\n  - op: set\n    var: BASE\n    value: base\n";
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str(spec_yaml).unwrap(),
+            serde_yaml::from_str(spec_yaml).unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority_with_options(
+        &env,
+        None,
+        ShellKind::Sh,
+        RuntimeOptions {
+            split_startup_scripts: true,
+            ..Default::default()
+        },
+    );
+
+    assert_eq!(scripts.len(), 2);
+    assert_eq!(scripts[0].script, scripts[1].script);
+    assert_eq!(scripts[0].digest, scripts[1].digest);
+}
+
+#[test]
+fn test_generate_startup_scripts_by_priority_skips_a_group_excluded_down_to_nothing() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            serde_yaml::from_str("priority: 5\nops:\n  - op: set\n    var: A\n    value: a\n")
+                .unwrap(),
+            serde_yaml::from_str("environment_exclude: ['A']\n").unwrap(),
+        ],
+        ..Default::default()
+    };
+
+    let scripts = generate_startup_scripts_by_priority(&env, None, ShellKind::Sh);
+
+    assert!(
+        scripts.is_empty(),
+        "a priority group excluded down to no ops should not produce a startup.d script"
+    );
+}
+
+#[test]
+fn test_default_does_not_clobber_an_already_set_variable() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: EDITOR\n    value: nvim\n  - op: default\n    var: EDITOR\n    value: vi\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("EDITOR"), Some(&"nvim".to_string()));
+}
+
+#[test]
+fn test_default_fills_in_an_unset_variable() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: default\n    var: SPENV_TEST_EDITOR\n    value: vi\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("SPENV_TEST_EDITOR"), Some(&"vi".to_string()));
+}
+
+#[test]
+fn test_a_later_set_overrides_an_earlier_default() {
+    let env = ComposedEnvironment {
+        layers: vec![
+            layer("  - op: default\n    var: EDITOR\n    value: vi\n"),
+            layer("  - op: set\n    var: EDITOR\n    value: nvim\n"),
+        ],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("EDITOR"), Some(&"nvim".to_string()));
+}
+
+#[test]
+fn test_path_remove_strips_only_exact_matches_from_an_existing_variable() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: PATH\n    value: /opt/toolchain/bin:/opt/toolchain/bin-old:/usr/bin\n  - op: path_remove\n    var: PATH\n    value: /opt/toolchain/bin\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(
+        vars.get("PATH"),
+        Some(&"/opt/toolchain/bin-old:/usr/bin".to_string())
+    );
+}
+
+#[test]
+fn test_path_remove_is_a_no_op_when_the_component_is_absent() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: PATH\n    value: /usr/bin:/bin\n  - op: path_remove\n    var: PATH\n    value: /opt/toolchain/bin\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("PATH"), Some(&"/usr/bin:/bin".to_string()));
+}
+
+#[test]
+fn test_path_remove_respects_a_custom_separator() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: PYTHONPATH\n    value: a;b;c\n  - op: path_remove\n    var: PYTHONPATH\n    value: b\n    separator: ';'\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("PYTHONPATH"), Some(&"a;c".to_string()));
+}
+
+#[test]
+fn test_generate_startup_script_renders_path_remove_and_removes_only_exact_matches() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: path_remove\n    var: PATH\n    value: /opt/toolchain/bin\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains("export PATH=\"$(printf '%s' \"$PATH\""));
+    assert!(sh.contains("sed -e 's#^/opt/toolchain/bin:##'"));
+}
+
+#[test]
+fn test_generate_startup_script_rejects_a_path_remove_value_that_would_escape_the_sed_literal() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: path_remove\n    var: PATH\n    value: \"x'; touch /tmp/pwned; echo '\"\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(!sh.contains("touch /tmp/pwned"));
+    assert!(sh.contains("# spenv: skipping path_remove"));
+}
+
+#[test]
+fn test_generate_startup_script_rejects_a_path_remove_separator_that_would_escape_the_sed_literal()
+{
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: path_remove\n    var: PATH\n    value: /usr/bin\n    separator: \"'\"\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains("# spenv: skipping path_remove"));
+}
+
+#[test]
+fn test_generate_startup_script_rejects_a_variable_name_with_shell_metacharacters() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: \"X; touch /tmp/pwned #\"\n    value: anything\n",
+        )],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(
+        sh.lines()
+            .all(|line| line.starts_with('#') || line.trim().is_empty())
+    );
+    assert!(sh.contains("# spenv: skipping op for invalid variable name"));
+}
+
+#[test]
+fn test_generate_startup_script_renders_default_per_shell() {
+    let env = ComposedEnvironment {
+        layers: vec![layer("  - op: default\n    var: EDITOR\n    value: vi\n")],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains(": \"${EDITOR:=vi}\""));
+
+    let csh = generate_startup_script(&env, None, ShellKind::Csh);
+    assert!(csh.contains("if (! $?EDITOR) setenv EDITOR \"vi\""));
+
+    let fish = generate_startup_script(&env, None, ShellKind::Fish);
+    assert!(fish.contains("set -q EDITOR; or set -gx EDITOR \"vi\""));
+}
+
+#[test]
+fn test_generate_startup_script_renders_source_guarded_per_shell() {
+    let env = ComposedEnvironment {
+        layers: vec![layer("  - op: source\n    source: /opt/tool/env.sh\n")],
+        ..Default::default()
+    };
+
+    let sh = generate_startup_script(&env, None, ShellKind::Sh);
+    assert!(sh.contains("[ -f \"/opt/tool/env.sh\" ] && . \"/opt/tool/env.sh\""));
+
+    let csh = generate_startup_script(&env, None, ShellKind::Csh);
+    assert!(csh.contains("if ( -f \"/opt/tool/env.sh\" ) source \"/opt/tool/env.sh\""));
+
+    let fish = generate_startup_script(&env, None, ShellKind::Fish);
+    assert!(fish.contains("test -f \"/opt/tool/env.sh\"; and source \"/opt/tool/env.sh\""));
+}
+
+#[test]
+fn test_source_op_has_no_effect_on_resolved_in_process_variables() {
+    let env = ComposedEnvironment {
+        layers: vec![layer(
+            "  - op: set\n    var: FOO\n    value: bar\n  - op: source\n    source: /opt/tool/env.sh\n",
+        )],
+        ..Default::default()
+    };
+
+    let vars = resolve_env_vars(&env);
+    assert_eq!(vars.get("FOO"), Some(&"bar".to_string()));
+}
+
+#[test]
+fn test_shell_kind_detects_csh_and_fish_from_shell_path() {
+    assert_eq!(ShellKind::from_shell_path("/bin/tcsh"), ShellKind::Csh);
+    assert_eq!(ShellKind::from_shell_path("/usr/bin/fish"), ShellKind::Fish);
+    assert_eq!(ShellKind::from_shell_path("/bin/bash"), ShellKind::Sh);
+}
+
+#[test]
+fn test_as_user_parses_a_bare_uid() {
+    let as_user: AsUser = "1000".parse().unwrap();
+    assert_eq!(
+        as_user,
+        AsUser {
+            uid: 1000,
+            gid: None
+        }
+    );
+}
+
+#[test]
+fn test_as_user_parses_a_uid_and_gid() {
+    let as_user: AsUser = "1000:1000".parse().unwrap();
+    assert_eq!(
+        as_user,
+        AsUser {
+            uid: 1000,
+            gid: Some(1000)
+        }
+    );
+}
+
+#[test]
+fn test_as_user_rejects_non_numeric_input() {
+    assert!("nobody".parse::<AsUser>().is_err());
+    assert!("1000:nobody".parse::<AsUser>().is_err());
+}
+
+#[test]
+fn test_can_run_as_self_is_always_permitted() {
+    let uid = nix::unistd::getuid().as_raw();
+    assert!(can_run_as(&AsUser { uid, gid: None }));
+}
+
+#[test]
+fn test_can_run_as_another_user_requires_root() {
+    let uid = nix::unistd::getuid().as_raw();
+    if uid == 0 {
+        // Running as root in this test environment, every user is permitted.
+        return;
+    }
+    assert!(!can_run_as(&AsUser {
+        uid: uid + 1,
+        gid: None
+    }));
+}
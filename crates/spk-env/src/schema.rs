@@ -0,0 +1,44 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Generating and validating against a JSON Schema for [`EnvSpec`].
+//!
+//! Serde's deserialization is intentionally lenient about things like
+//! extra whitespace or coercible scalars; validating against the
+//! generated schema instead catches structural mistakes (a value of
+//! the wrong type or shape) with a path into the offending document.
+//! Unknown keys are not reported: [`EnvSpec`] doesn't deny unknown
+//! fields, so its schema allows additional properties.
+
+use jsonschema::Validator;
+
+use crate::error::SchemaError;
+use crate::spec::EnvSpec;
+
+#[cfg(test)]
+#[path = "schema_test.rs"]
+mod schema_test;
+
+/// Generate the JSON Schema that describes a valid `.spenv.yaml` spec.
+pub fn spec_json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(EnvSpec))
+        .expect("a generated schema always serializes to JSON")
+}
+
+/// Validate the raw YAML contents of a spec file against the
+/// generated JSON Schema, returning every structural violation found.
+///
+/// Each returned string is a human-readable description including
+/// the JSON pointer path to the offending value.
+pub fn validate_spec_yaml(contents: &str) -> Result<Vec<String>, SchemaError> {
+    let value: serde_json::Value =
+        serde_yaml::from_str(contents).map_err(SchemaError::InvalidYaml)?;
+    let validator: Validator = jsonschema::validator_for(&spec_json_schema())
+        .map_err(|source| SchemaError::InvalidSchema(source.to_string()))?;
+
+    Ok(validator
+        .iter_errors(&value)
+        .map(|err| format!("{} at {}", err, err.instance_path))
+        .collect())
+}
@@ -0,0 +1,25 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use super::validate_spec_yaml;
+
+#[test]
+fn test_validate_spec_yaml_passes_a_well_formed_spec() {
+    let violations = validate_spec_yaml("inherit: true\npriority: 10\n").unwrap();
+    assert!(
+        violations.is_empty(),
+        "expected no violations, got {violations:?}"
+    );
+}
+
+#[test]
+fn test_validate_spec_yaml_reports_a_wrongly_typed_field() {
+    let violations = validate_spec_yaml("priority: not-a-number\n").unwrap();
+    assert_eq!(
+        violations.len(),
+        1,
+        "expected exactly one violation, got {violations:?}"
+    );
+    assert!(violations[0].contains("priority"));
+}
@@ -0,0 +1,532 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! The on-disk format for `.spenv.yaml` spec files.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{LoadSpecError, SaveSpecError, SerdeYamlError};
+
+#[cfg(test)]
+#[path = "spec_test.rs"]
+mod spec_test;
+
+/// Describes a single scripting environment, usually loaded
+/// from a `.spenv.yaml` file on disk.
+///
+/// A spec is one layer in a [`crate::ComposedEnvironment`]. It may
+/// reference other specs via [`EnvSpec::includes`], and may request
+/// to discover and compose with specs in parent directories via
+/// [`EnvSpec::inherit`].
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct EnvSpec {
+    /// Other spec files to compose together with this one. An entry
+    /// may instead be written as `{ path: ..., when: {...} }` to
+    /// include it only on a matching host, see [`IncludeEntry`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<IncludeEntry>,
+    /// When true, parent directories are also searched for spec
+    /// files, which are composed in before this one
+    #[serde(default)]
+    pub inherit: bool,
+    /// The environment variable operations defined by this spec
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ops: Vec<EnvOp>,
+    /// Bind mounts to add to the resulting environment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binds: Vec<BindMount>,
+    /// Other content to make available inside the environment besides
+    /// host bind mounts, e.g. an ephemeral tmpfs scratch directory.
+    /// See [`ContentMount`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contents: Vec<ContentMount>,
+    /// Package requests to resolve into the environment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+    /// Controls the ordering of the generated startup script relative
+    /// to other scripts in `startup.d`. When multiple specs in a
+    /// cascade set this, the last one applied wins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Variable names or prefixes whose operations from earlier
+    /// layers should be dropped during composition. An entry ending
+    /// in `*` matches any variable starting with that prefix;
+    /// otherwise it must match a variable name exactly.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub environment_exclude: Vec<String>,
+    /// The default lock behavior for `spenv check` and `spenv load`,
+    /// when the equivalent CLI flag isn't given. When multiple specs
+    /// in a cascade set this, the last one applied wins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<LockPolicy>,
+    /// A free-text annotation for this layer, e.g. "pinned for CVE
+    /// fix". Carried into the lock's [`crate::lock::SourceHash`] for
+    /// the layer when one is generated, and shown by `spenv show`, so
+    /// the reason a layer is the way it is survives for later
+    /// auditing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// How this spec's layers combine with layers already composed
+    /// from ancestors or earlier includes. `replace` discards every
+    /// layer composed so far before this spec's own includes and
+    /// itself are added, letting a child project discard an inherited
+    /// layer (e.g. a platform layer) it doesn't want.
+    #[serde(default)]
+    pub layers_mode: LayersMode,
+    /// Where this layer should sort into the final composition order,
+    /// regardless of discovery or include order. Layers are
+    /// stable-sorted by weight after gathering, so two layers with the
+    /// same weight (including the default of 0) keep their relative
+    /// declaration order. A low weight sinks a layer towards the
+    /// bottom of the stack, letting e.g. a base layer declared late
+    /// through an include still apply before everything else.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<i32>,
+    /// Ops, binds and packages that only apply on a matching host, so
+    /// one spec can serve multiple platforms without duplicating
+    /// content. Each key is compared against both
+    /// [`std::env::consts::OS`] (e.g. `linux`, `macos`) and
+    /// [`std::env::consts::ARCH`] (e.g. `x86_64`, `aarch64`); an entry
+    /// whose key matches neither is ignored rather than rejected, so
+    /// unrelated platform sections don't need a guard. Merged into
+    /// this spec's own `ops`/`binds`/`packages` at compose time
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub platform: HashMap<String, PlatformOverlay>,
+
+    /// The path that this spec was loaded from, filled in by the loader
+    #[serde(skip)]
+    pub file_path: PathBuf,
+}
+
+impl EnvSpec {
+    /// The expected file name for an environment spec file
+    pub const FILE_NAME: &str = ".spenv.yaml";
+
+    /// Load a spec from a specific file on disk.
+    pub fn load_file<P: AsRef<Path>>(path: P) -> Result<Self, LoadSpecError> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(LoadSpecError::NotFound(path.to_owned()));
+        }
+        let contents =
+            std::fs::read_to_string(path).map_err(|source| LoadSpecError::ReadFailed {
+                path: path.to_owned(),
+                source,
+            })?;
+        let mut spec: Self =
+            serde_yaml::from_str(&contents).map_err(|err| LoadSpecError::InvalidYaml {
+                path: path.to_owned(),
+                source: Box::new(format_serde_error::SerdeError::new(
+                    contents.clone(),
+                    SerdeYamlError(err),
+                )),
+            })?;
+        spec.file_path = path.to_owned();
+        Ok(spec)
+    }
+
+    /// Parse a spec from YAML text already in memory, rather than
+    /// reading it from disk. `source_path` becomes [`EnvSpec::file_path`]
+    /// even though nothing is actually read from it; used by
+    /// [`crate::compose::compose_from_yaml`] to compose specs supplied
+    /// by a caller instead of discovered on the filesystem.
+    pub fn from_yaml_str(
+        source_path: impl Into<PathBuf>,
+        yaml: &str,
+    ) -> Result<Self, LoadSpecError> {
+        let source_path = source_path.into();
+        let mut spec: Self =
+            serde_yaml::from_str(yaml).map_err(|err| LoadSpecError::InvalidYaml {
+                path: source_path.clone(),
+                source: Box::new(format_serde_error::SerdeError::new(
+                    yaml.to_string(),
+                    SerdeYamlError(err),
+                )),
+            })?;
+        spec.file_path = source_path;
+        Ok(spec)
+    }
+
+    /// Parse every YAML document in `yaml`, for a file that
+    /// intentionally holds more than one spec separated by `---`,
+    /// unlike [`EnvSpec::from_yaml_str`] and [`EnvSpec::load_file`],
+    /// which treat that as the error it usually is. Every resulting
+    /// spec's [`EnvSpec::file_path`] is set to `source_path`; since
+    /// YAML documents carry no name of their own, distinguishing
+    /// which one a spec came from is left to the caller's own
+    /// convention, e.g. an index into the returned `Vec`.
+    pub fn from_yaml_multi(
+        source_path: impl Into<PathBuf>,
+        yaml: &str,
+    ) -> Result<Vec<Self>, LoadSpecError> {
+        let source_path = source_path.into();
+        serde_yaml::Deserializer::from_str(yaml)
+            .map(|document| {
+                let mut spec =
+                    Self::deserialize(document).map_err(|err| LoadSpecError::InvalidYaml {
+                        path: source_path.clone(),
+                        source: Box::new(format_serde_error::SerdeError::new(
+                            yaml.to_string(),
+                            SerdeYamlError(err),
+                        )),
+                    })?;
+                spec.file_path = source_path.clone();
+                Ok(spec)
+            })
+            .collect()
+    }
+
+    /// Load the spec that lives directly inside of the given directory.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Self, LoadSpecError> {
+        Self::load_dir_named(dir, Self::FILE_NAME)
+    }
+
+    /// Load the spec that lives directly inside of the given directory,
+    /// under `filename` instead of [`EnvSpec::FILE_NAME`].
+    pub fn load_dir_named<P: AsRef<Path>>(dir: P, filename: &str) -> Result<Self, LoadSpecError> {
+        Self::load_file(dir.as_ref().join(filename))
+    }
+
+    /// True if `self` and `other` define the same spec content,
+    /// ignoring [`EnvSpec::file_path`].
+    pub fn semantically_equal(&self, other: &Self) -> bool {
+        self.includes == other.includes
+            && self.inherit == other.inherit
+            && self.ops == other.ops
+            && self.binds == other.binds
+            && self.contents == other.contents
+            && self.packages == other.packages
+            && self.priority == other.priority
+            && self.environment_exclude == other.environment_exclude
+            && self.lock == other.lock
+            && self.note == other.note
+            && self.layers_mode == other.layers_mode
+            && self.weight == other.weight
+            && self.platform == other.platform
+    }
+
+    /// Serialize this spec back to YAML, in its field declaration
+    /// order, omitting empty collections and unset options. Loading
+    /// the result back with [`serde_yaml::from_str`] produces a spec
+    /// that is [`EnvSpec::semantically_equal`] to this one, aside from
+    /// [`EnvSpec::file_path`], which YAML never carries.
+    pub fn to_yaml(&self) -> Result<String, SaveSpecError> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+}
+
+/// A section of [`EnvSpec::platform`] that's merged in only on a
+/// matching host. See [`EnvSpec::platform`] for how its key is
+/// matched.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct PlatformOverlay {
+    /// Environment variable operations to merge in on a match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ops: Vec<EnvOp>,
+    /// Bind mounts to merge in on a match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binds: Vec<BindMount>,
+    /// Package requests to merge in on a match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+}
+
+/// True if `key` names the OS or architecture of the host this
+/// process is actually running on, for matching an [`EnvSpec::platform`] key.
+pub fn matches_running_platform_key(key: &str) -> bool {
+    key == std::env::consts::OS || key == std::env::consts::ARCH
+}
+
+/// How a spec's layers combine with layers already composed from
+/// ancestors or earlier includes. See [`EnvSpec::layers_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LayersMode {
+    /// Keep every layer composed so far, adding this spec's on top
+    #[default]
+    Append,
+    /// Discard every layer composed so far before adding this spec's
+    Replace,
+}
+
+/// The default lock behavior for `spenv check` and `spenv load`.
+///
+/// A CLI flag always overrides the policy a spec requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct LockPolicy {
+    /// Whether a lock file should be consulted at all
+    #[serde(default = "LockPolicy::default_enabled")]
+    pub enabled: bool,
+    /// Whether a missing or drifted lock should be treated as an
+    /// error rather than a warning
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl LockPolicy {
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            strict: false,
+        }
+    }
+}
+
+/// A single environment variable operation to perform.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize, JsonSchema)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum EnvOp {
+    /// Set a variable to an exact value
+    Set {
+        /// The variable to set
+        var: String,
+        /// The value to give it
+        value: String,
+    },
+    /// Prepend a value to an existing variable, using the platform path separator
+    Prepend {
+        /// The variable to modify
+        var: String,
+        /// The value to prepend
+        value: String,
+    },
+    /// Append a value to an existing variable, using the platform path separator
+    Append {
+        /// The variable to modify
+        var: String,
+        /// The value to append
+        value: String,
+    },
+    /// Remove a variable entirely, regardless of what earlier layers set it to
+    Unset {
+        /// The variable to remove
+        var: String,
+    },
+    /// Set a variable only if nothing earlier in the cascade, nor the
+    /// inherited process environment, has already given it a value
+    Default {
+        /// The variable to set
+        var: String,
+        /// The fallback value to give it
+        value: String,
+    },
+    /// Remove a single component from a separator-joined variable,
+    /// leaving the rest of it untouched. A no-op if the component
+    /// isn't present.
+    PathRemove {
+        /// The variable to modify
+        var: String,
+        /// The component to remove
+        value: String,
+        /// The separator joining the variable's components, defaults to `:`
+        #[serde(default)]
+        separator: Option<String>,
+    },
+    /// Source an external script into the shell when it runs this
+    /// spec's startup script, e.g. for tooling that ships its own
+    /// `env.sh` that's easier to source than to re-express as
+    /// `set`/`prepend` ops.
+    ///
+    /// This runs arbitrary code from the sourced file, so a `source`
+    /// op declared by an inherited spec is only honored when that
+    /// spec passes the same ownership and permission check as
+    /// `--trusted-only` (see
+    /// [`crate::discovery::DiscoveryOptions::trusted_only`]), even if
+    /// that flag wasn't actually given.
+    Source {
+        /// The path to the script to source, interpolated like any
+        /// other op value
+        source: String,
+    },
+}
+
+impl EnvOp {
+    /// The variable that this operation targets, or the empty string
+    /// for an op that doesn't target one (currently only [`EnvOp::Source`]).
+    pub fn var(&self) -> &str {
+        match self {
+            EnvOp::Set { var, .. }
+            | EnvOp::Prepend { var, .. }
+            | EnvOp::Append { var, .. }
+            | EnvOp::Default { var, .. }
+            | EnvOp::PathRemove { var, .. }
+            | EnvOp::Unset { var } => var,
+            EnvOp::Source { .. } => "",
+        }
+    }
+}
+
+/// Returns true if `var` matches an exclusion `pattern`. A pattern
+/// ending in `*` matches any variable starting with that prefix;
+/// otherwise the match must be exact.
+pub fn matches_exclude(var: &str, pattern: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => var.starts_with(prefix),
+        None => var == pattern,
+    }
+}
+
+/// A single entry in [`EnvSpec::includes`].
+///
+/// Most entries are a bare path, unconditionally included. An entry
+/// can instead be written as a mapping with a `when` predicate,
+/// evaluated against the host actually running `spenv`, so a single
+/// spec can serve multiple platforms without duplicating content, an
+/// `optional` flag, for an include that may not exist on every
+/// machine (e.g. an opt-in personal or site override) — a missing
+/// optional include is skipped with a debug log instead of failing
+/// discovery; a missing required include still fails hard — and a
+/// `weight` override, for pinning where the included layer sorts
+/// into the composition regardless of the [`EnvSpec::weight`] it
+/// declares for itself (e.g. a shared base layer that one project
+/// needs bottom-most without editing the shared file).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum IncludeEntry {
+    /// An include with no predicate, required, and sorted by whatever
+    /// weight the included spec declares for itself
+    Path(PathBuf),
+    /// An include resolved only when `when` matches the running host,
+    /// and/or marked `optional` and/or `weight`-overridden
+    Conditional {
+        /// The spec file to include
+        path: PathBuf,
+        /// The host attributes this include requires
+        #[serde(default)]
+        when: HostPredicate,
+        /// If true, a missing include is skipped rather than
+        /// reported as [`crate::error::ComposeError::IncludeNotFound`]
+        /// (or, for a glob, [`crate::error::ComposeError::IncludeGlobEmpty`])
+        #[serde(default)]
+        optional: bool,
+        /// If set, overrides the included spec's own
+        /// [`EnvSpec::weight`] for sorting purposes, without modifying
+        /// the included file. A glob entry applies the same override
+        /// to every file it matches.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weight: Option<i32>,
+    },
+}
+
+impl IncludeEntry {
+    /// The spec file this entry refers to, regardless of whether it
+    /// carries a predicate.
+    pub fn path(&self) -> &Path {
+        match self {
+            IncludeEntry::Path(path) => path,
+            IncludeEntry::Conditional { path, .. } => path,
+        }
+    }
+
+    /// Whether this entry should be resolved on the host this process
+    /// is actually running on.
+    pub fn matches_host(&self) -> bool {
+        match self {
+            IncludeEntry::Path(_) => true,
+            IncludeEntry::Conditional { when, .. } => when.matches_running_host(),
+        }
+    }
+
+    /// Whether a missing copy of this include should be tolerated.
+    pub fn is_optional(&self) -> bool {
+        match self {
+            IncludeEntry::Path(_) => false,
+            IncludeEntry::Conditional { optional, .. } => *optional,
+        }
+    }
+
+    /// The weight this entry overrides the included spec's own
+    /// [`EnvSpec::weight`] with, if any.
+    pub fn weight(&self) -> Option<i32> {
+        match self {
+            IncludeEntry::Path(_) => None,
+            IncludeEntry::Conditional { weight, .. } => *weight,
+        }
+    }
+}
+
+/// A predicate that gates a conditional [`IncludeEntry`] on
+/// attributes of the host running `spenv`. Every field that is set
+/// must match for the predicate as a whole to match; an unknown key
+/// under `when` is rejected at load time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HostPredicate {
+    /// Only match hosts running this OS (e.g. `linux`, `macos`,
+    /// `windows`), compared against `std::env::consts::OS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<String>,
+    /// Only match hosts with this CPU architecture (e.g. `x86_64`,
+    /// `aarch64`), compared against `std::env::consts::ARCH`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+}
+
+impl HostPredicate {
+    /// True if every field set on this predicate matches the host
+    /// this process is actually running on.
+    pub fn matches_running_host(&self) -> bool {
+        self.os
+            .as_deref()
+            .is_none_or(|os| os == std::env::consts::OS)
+            && self
+                .arch
+                .as_deref()
+                .is_none_or(|arch| arch == std::env::consts::ARCH)
+    }
+}
+
+/// A bind mount to create inside of the composed environment.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct BindMount {
+    /// The path on the host to mount from
+    pub source: PathBuf,
+    /// The path inside the environment to mount to
+    pub dest: PathBuf,
+}
+
+/// A single entry of [`EnvSpec::contents`]: either a host [`BindMount`]
+/// or an ephemeral [`TmpfsMount`] that doesn't touch the host at all.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ContentMount {
+    /// A bind mount from the host, written the same way as an entry
+    /// under [`EnvSpec::binds`]
+    Bind(BindMount),
+    /// An ephemeral, writable scratch directory with no host backing
+    Tmpfs(TmpfsMount),
+}
+
+impl ContentMount {
+    /// The path this entry makes available inside the environment,
+    /// for validating it lands under `/spfs`.
+    pub fn dest(&self) -> &Path {
+        match self {
+            ContentMount::Bind(bind) => &bind.dest,
+            ContentMount::Tmpfs(tmpfs) => &tmpfs.tmpfs,
+        }
+    }
+}
+
+/// An ephemeral, writable scratch directory with no host backing,
+/// e.g. `- tmpfs: /spfs/scratch`. See [`EnvSpec::contents`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+pub struct TmpfsMount {
+    /// The path inside the environment to mount the tmpfs at
+    pub tmpfs: PathBuf,
+    /// The maximum size of the tmpfs, e.g. `"512m"`. Left to the
+    /// runtime's default (usually half of available RAM) when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<String>,
+}
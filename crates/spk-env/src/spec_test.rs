@@ -0,0 +1,220 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::Path;
+
+use super::{ContentMount, EnvOp, EnvSpec, LockPolicy, TmpfsMount};
+use crate::error::LoadSpecError;
+
+#[test]
+fn test_lock_policy_parses_enabled_and_strict() {
+    let spec: EnvSpec = serde_yaml::from_str("lock:\n  enabled: false\n  strict: true\n").unwrap();
+    assert_eq!(
+        spec.lock,
+        Some(LockPolicy {
+            enabled: false,
+            strict: true,
+        })
+    );
+}
+
+#[test]
+fn test_lock_policy_defaults_to_enabled_and_not_strict_when_fields_are_omitted() {
+    let spec: EnvSpec = serde_yaml::from_str("lock: {}\n").unwrap();
+    assert_eq!(spec.lock, Some(LockPolicy::default()));
+    assert!(spec.lock.unwrap().enabled);
+}
+
+#[test]
+fn test_no_lock_section_leaves_the_field_unset() {
+    let spec: EnvSpec = serde_yaml::from_str("priority: 1\n").unwrap();
+    assert_eq!(spec.lock, None);
+}
+
+#[test]
+fn test_note_parses_when_present_and_defaults_to_none() {
+    let spec: EnvSpec = serde_yaml::from_str("note: pinned for CVE fix\n").unwrap();
+    assert_eq!(spec.note, Some("pinned for CVE fix".to_string()));
+
+    let spec: EnvSpec = serde_yaml::from_str("priority: 1\n").unwrap();
+    assert_eq!(spec.note, None);
+}
+
+#[test]
+fn test_contents_parses_a_tmpfs_entry_with_and_without_a_size() {
+    let spec: EnvSpec =
+        serde_yaml::from_str("contents:\n  - tmpfs: /spfs/scratch\n    size: 512m\n").unwrap();
+    assert_eq!(
+        spec.contents,
+        vec![ContentMount::Tmpfs(TmpfsMount {
+            tmpfs: "/spfs/scratch".into(),
+            size: Some("512m".to_string()),
+        })]
+    );
+
+    let spec: EnvSpec = serde_yaml::from_str("contents:\n  - tmpfs: /spfs/scratch\n").unwrap();
+    assert_eq!(
+        spec.contents,
+        vec![ContentMount::Tmpfs(TmpfsMount {
+            tmpfs: "/spfs/scratch".into(),
+            size: None,
+        })]
+    );
+}
+
+#[test]
+fn test_contents_parses_a_bind_entry_the_same_way_as_the_binds_list() {
+    let spec: EnvSpec =
+        serde_yaml::from_str("contents:\n  - source: /host/data\n    dest: /spfs/data\n").unwrap();
+    assert_eq!(
+        spec.contents,
+        vec![ContentMount::Bind(crate::spec::BindMount {
+            source: "/host/data".into(),
+            dest: "/spfs/data".into(),
+        })]
+    );
+}
+
+#[test]
+fn test_source_op_parses_and_targets_no_variable() {
+    let spec: EnvSpec =
+        serde_yaml::from_str("ops:\n  - op: source\n    source: /opt/tool/env.sh\n").unwrap();
+    assert_eq!(
+        spec.ops,
+        vec![EnvOp::Source {
+            source: "/opt/tool/env.sh".to_string(),
+        }]
+    );
+    assert_eq!(spec.ops[0].var(), "");
+}
+
+#[test]
+fn test_anchors_and_aliases_resolve_through_from_yaml_str() {
+    let spec = EnvSpec::from_yaml_str(
+        "spec",
+        "ops:\n  - &base\n    op: set\n    var: BASE\n    value: base\n  - *base\n",
+    )
+    .unwrap();
+    assert_eq!(
+        spec.ops,
+        vec![
+            EnvOp::Set {
+                var: "BASE".to_string(),
+                value: "base".to_string(),
+            },
+            EnvOp::Set {
+                var: "BASE".to_string(),
+                value: "base".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_from_yaml_multi_parses_every_document_in_declaration_order() {
+    let specs =
+        EnvSpec::from_yaml_multi("multi", "packages: [one-pkg]\n---\npackages: [two-pkg]\n")
+            .unwrap();
+
+    assert_eq!(specs.len(), 2);
+    assert_eq!(specs[0].packages, vec!["one-pkg".to_string()]);
+    assert_eq!(specs[1].packages, vec!["two-pkg".to_string()]);
+    assert!(
+        specs
+            .iter()
+            .all(|spec| spec.file_path == Path::new("multi")),
+        "each document should carry the same source path"
+    );
+}
+
+#[test]
+fn test_from_yaml_multi_rejects_an_alias_to_an_anchor_from_an_earlier_document() {
+    let specs = EnvSpec::from_yaml_multi(
+        "multi",
+        "common: &common\n  op: set\n  var: SHARED\n  value: shared\nops:\n  - *common\n---\nops:\n  - *common\n",
+    )
+    .unwrap_err();
+
+    // An anchor is only in scope within the document that declares
+    // it; `serde_yaml` does not carry one across the `---` separator.
+    assert!(matches!(specs, LoadSpecError::InvalidYaml { .. }));
+}
+
+#[test]
+fn test_from_yaml_str_reports_an_unexpected_second_document_clearly() {
+    let err = EnvSpec::from_yaml_str("spec", "packages: [one-pkg]\n---\npackages: [two-pkg]\n")
+        .unwrap_err();
+
+    let LoadSpecError::InvalidYaml { source, .. } = err else {
+        panic!("expected InvalidYaml, got {err:?}");
+    };
+    assert!(
+        source.to_string().contains("more than one document"),
+        "error should clearly call out the unexpected extra document: {source}"
+    );
+}
+
+#[test]
+fn test_to_yaml_omits_empty_collections_and_unset_options() {
+    let spec = EnvSpec::default();
+    assert_eq!(
+        spec.to_yaml().unwrap(),
+        "inherit: false\nlayers_mode: append\n"
+    );
+}
+
+#[test]
+fn test_to_yaml_round_trips_through_from_str_for_a_variety_of_specs() {
+    let specs = [
+        EnvSpec::default(),
+        serde_yaml::from_str("priority: 1\nnote: pinned for CVE fix\n").unwrap(),
+        serde_yaml::from_str("includes:\n  - base.yaml\n  - path: linux.yaml\n    when:\n      os: linux\n").unwrap(),
+        serde_yaml::from_str(
+            "ops:\n  - op: set\n    var: FOO\n    value: bar\n  - op: path_remove\n    var: PATH\n    value: /opt/bin\n    separator: ':'\n  - op: source\n    source: /opt/tool/env.sh\n",
+        )
+        .unwrap(),
+        serde_yaml::from_str("binds:\n  - source: /host\n    dest: /container\n").unwrap(),
+        serde_yaml::from_str(
+            "contents:\n  - tmpfs: /spfs/scratch\n    size: 1g\n  - source: /host\n    dest: /spfs/data\n",
+        )
+        .unwrap(),
+        serde_yaml::from_str(
+            "lock:\n  enabled: false\n  strict: true\nlayers_mode: replace\nweight: -1\n",
+        )
+        .unwrap(),
+        serde_yaml::from_str(
+            "platform:\n  linux:\n    ops:\n      - op: unset\n        var: FOO\n    packages:\n      - some-pkg\n",
+        )
+        .unwrap(),
+    ];
+
+    for spec in specs {
+        let yaml = spec.to_yaml().unwrap();
+        let round_tripped: EnvSpec = serde_yaml::from_str(&yaml).unwrap();
+        assert!(
+            spec.semantically_equal(&round_tripped),
+            "round trip changed the spec: {yaml}"
+        );
+    }
+}
+
+#[test]
+fn test_to_yaml_orders_fields_as_declared_on_the_struct() {
+    let spec = EnvSpec {
+        ops: vec![EnvOp::Set {
+            var: "FOO".to_string(),
+            value: "bar".to_string(),
+        }],
+        packages: vec!["some-pkg".to_string()],
+        priority: Some(1),
+        ..Default::default()
+    };
+
+    let yaml = spec.to_yaml().unwrap();
+    let ops_pos = yaml.find("ops:").unwrap();
+    let packages_pos = yaml.find("packages:").unwrap();
+    let priority_pos = yaml.find("priority:").unwrap();
+    assert!(ops_pos < packages_pos);
+    assert!(packages_pos < priority_pos);
+}
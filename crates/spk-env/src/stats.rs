@@ -0,0 +1,103 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Computing summary statistics over a [`ComposedEnvironment`].
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use crate::ComposedEnvironment;
+use crate::error::StatsError;
+
+#[cfg(test)]
+#[path = "stats_test.rs"]
+mod stats_test;
+
+/// A single countable aspect of a composed environment, as requested
+/// via `spenv show --count-only <dimension>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountDimension {
+    /// The total number of layers in the composition, including duplicates
+    Layers,
+    /// The number of distinct spec files contributing layers
+    UniqueLayers,
+    /// The number of distinct files bind-mounted into the environment
+    Files,
+    /// The total number of environment variable operations
+    EnvOps,
+    /// The total number of bind mounts
+    Binds,
+    /// The total number of package requests
+    Packages,
+}
+
+impl FromStr for CountDimension {
+    type Err = StatsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "layers" => Ok(Self::Layers),
+            "unique-layers" => Ok(Self::UniqueLayers),
+            "files" => Ok(Self::Files),
+            "env-ops" => Ok(Self::EnvOps),
+            "binds" => Ok(Self::Binds),
+            "packages" => Ok(Self::Packages),
+            other => Err(StatsError::UnknownDimension(other.to_string())),
+        }
+    }
+}
+
+/// Summary counts over a composed environment.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EnvStats {
+    /// The total number of layers, including duplicates
+    pub layers: usize,
+    /// The number of distinct spec files contributing layers
+    pub unique_layers: usize,
+    /// The number of distinct bind-mounted source files
+    pub files: usize,
+    /// The total number of environment variable operations
+    pub env_ops: usize,
+    /// The total number of bind mounts
+    pub binds: usize,
+    /// The total number of package requests
+    pub packages: usize,
+}
+
+impl EnvStats {
+    /// Compute stats for a composed environment.
+    pub fn compute(env: &ComposedEnvironment) -> Self {
+        let unique_layers: HashSet<_> = env
+            .layers
+            .iter()
+            .map(|l| l.file_path.canonicalize().unwrap_or(l.file_path.clone()))
+            .collect();
+        let unique_files: HashSet<_> = env
+            .layers
+            .iter()
+            .flat_map(|l| l.binds.iter().map(|b| b.source.clone()))
+            .collect();
+
+        Self {
+            layers: env.layers.len(),
+            unique_layers: unique_layers.len(),
+            files: unique_files.len(),
+            env_ops: env.layers.iter().map(|l| l.ops.len()).sum(),
+            binds: env.layers.iter().map(|l| l.binds.len()).sum(),
+            packages: env.layers.iter().map(|l| l.packages.len()).sum(),
+        }
+    }
+
+    /// Get the value of a single dimension.
+    pub fn get(&self, dimension: CountDimension) -> usize {
+        match dimension {
+            CountDimension::Layers => self.layers,
+            CountDimension::UniqueLayers => self.unique_layers,
+            CountDimension::Files => self.files,
+            CountDimension::EnvOps => self.env_ops,
+            CountDimension::Binds => self.binds,
+            CountDimension::Packages => self.packages,
+        }
+    }
+}
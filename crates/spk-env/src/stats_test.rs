@@ -0,0 +1,39 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{CountDimension, EnvStats};
+use crate::ComposedEnvironment;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+/// Builds a cascade where `root` includes both `a` and `b`, and
+/// `a` and `b` both include the same `shared` spec, so the
+/// resolved layer list contains a duplicate.
+#[rstest]
+fn test_count_only_unique_layers_with_duplicate(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("shared.spenv.yaml"), "packages: [shared-pkg]\n").unwrap();
+    std::fs::write(root.join("a.spenv.yaml"), "includes: [shared.spenv.yaml]\n").unwrap();
+    std::fs::write(root.join("b.spenv.yaml"), "includes: [shared.spenv.yaml]\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [a.spenv.yaml, b.spenv.yaml]\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).expect("failed to resolve cascade");
+    // shared, a, shared, b, root == 5 layers but only 4 unique files
+    assert_eq!(env.layers.len(), 5);
+
+    let stats = EnvStats::compute(&env);
+    assert_eq!(stats.get(CountDimension::UniqueLayers), 4);
+}
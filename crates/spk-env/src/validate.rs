@@ -0,0 +1,189 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Linting a [`ComposedEnvironment`] for common spec authoring
+//! mistakes that deserializing and composing successfully doesn't
+//! catch on its own.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::ComposedEnvironment;
+use crate::compose::EnvConflict;
+use crate::spec::EnvOp;
+
+#[cfg(test)]
+#[path = "validate_test.rs"]
+mod validate_test;
+
+/// The on-disk directory that bind mounts are expected to land
+/// inside of, for a composed environment to be usable at run time.
+const SPFS_ROOT: &str = "/spfs";
+
+/// The top-level keys that [`crate::spec::EnvSpec`] understands.
+/// Kept in sync with its fields by hand, since the lenient
+/// deserializer otherwise silently drops anything else.
+const KNOWN_KEYS: &[&str] = &[
+    "includes",
+    "inherit",
+    "ops",
+    "binds",
+    "contents",
+    "packages",
+    "priority",
+    "environment_exclude",
+    "lock",
+    "note",
+    "layers_mode",
+    "weight",
+    "platform",
+];
+
+/// A single spec-authoring mistake found while linting a composed
+/// environment. Unlike the errors in [`crate::error`], these don't
+/// stop composition; [`validate`] collects every one it can find.
+#[derive(thiserror::Error, miette::Diagnostic, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// A top-level key that `EnvSpec` does not recognize, most often a typo.
+    #[error("{layer:?}: unknown key {key:?}")]
+    UnknownKey {
+        /// The spec the unknown key was found in
+        layer: PathBuf,
+        /// The key that isn't recognized
+        key: String,
+    },
+    /// A `set` operation with an empty variable name.
+    #[error("{layer:?}: a `set` operation has an empty variable name")]
+    EmptySetName {
+        /// The spec the empty `set` was found in
+        layer: PathBuf,
+    },
+    /// A `set` operation for a variable that another `set` in the
+    /// same layer already targets, so one of them is always dead.
+    #[error("{layer:?}: {var} is set more than once")]
+    DuplicateSet {
+        /// The spec the duplicate `set` was found in
+        layer: PathBuf,
+        /// The variable that is set more than once
+        var: String,
+    },
+    /// A bind mount whose destination is outside of `/spfs`, where
+    /// nothing in the composed environment will be able to see it.
+    /// This also catches a relative `dest`, since a relative path
+    /// never starts with `/spfs` either.
+    #[error("{layer:?}: bind destination {dest:?} is outside of {SPFS_ROOT}")]
+    BindOutsideSpfs {
+        /// The spec that declared the bind
+        layer: PathBuf,
+        /// The destination path that falls outside `/spfs`
+        dest: PathBuf,
+    },
+    /// A [`crate::spec::ContentMount`] entry whose destination is
+    /// outside of `/spfs`, for the same reason as [`Self::BindOutsideSpfs`].
+    #[error("{layer:?}: content destination {dest:?} is outside of {SPFS_ROOT}")]
+    ContentOutsideSpfs {
+        /// The spec that declared the content entry
+        layer: PathBuf,
+        /// The destination path that falls outside `/spfs`
+        dest: PathBuf,
+    },
+    /// Two or more layers `set`/`default` the same variable to
+    /// differing values, so which one wins depends on layer order.
+    #[error(
+        "{var} is set to conflicting values across layers: {}",
+        values.join(", ")
+    )]
+    ConflictingSet {
+        /// The variable set to conflicting values
+        var: String,
+        /// Each distinct value contributed
+        values: Vec<String>,
+    },
+}
+
+impl ValidationIssue {
+    /// False for mistakes that are always broken, true for ones a
+    /// spec author might plausibly have intended, and which only
+    /// fail a `--warnings-as-errors` check.
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            ValidationIssue::UnknownKey { .. }
+                | ValidationIssue::DuplicateSet { .. }
+                | ValidationIssue::ConflictingSet { .. }
+        )
+    }
+}
+
+/// Lint every layer of `env`, collecting every [`ValidationIssue`]
+/// found rather than stopping at the first one.
+pub fn validate(env: &ComposedEnvironment) -> Vec<ValidationIssue> {
+    env.layers
+        .iter()
+        .flat_map(validate_layer)
+        .chain(env.conflicts().into_iter().map(conflict_issue))
+        .collect()
+}
+
+fn conflict_issue(conflict: EnvConflict) -> ValidationIssue {
+    ValidationIssue::ConflictingSet {
+        var: conflict.name,
+        values: conflict.values,
+    }
+}
+
+fn validate_layer(layer: &crate::spec::EnvSpec) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if let Ok(contents) = std::fs::read_to_string(&layer.file_path)
+        && let Ok(serde_yaml::Value::Mapping(keys)) = serde_yaml::from_str(&contents)
+    {
+        for key in keys.keys() {
+            if let Some(key) = key.as_str()
+                && !KNOWN_KEYS.contains(&key)
+            {
+                issues.push(ValidationIssue::UnknownKey {
+                    layer: layer.file_path.clone(),
+                    key: key.to_string(),
+                });
+            }
+        }
+    }
+
+    let mut set_vars = HashSet::new();
+    for op in &layer.ops {
+        if let EnvOp::Set { var, .. } = op {
+            if var.is_empty() {
+                issues.push(ValidationIssue::EmptySetName {
+                    layer: layer.file_path.clone(),
+                });
+            } else if !set_vars.insert(var.clone()) {
+                issues.push(ValidationIssue::DuplicateSet {
+                    layer: layer.file_path.clone(),
+                    var: var.clone(),
+                });
+            }
+        }
+    }
+
+    for bind in &layer.binds {
+        if !bind.dest.starts_with(SPFS_ROOT) {
+            issues.push(ValidationIssue::BindOutsideSpfs {
+                layer: layer.file_path.clone(),
+                dest: bind.dest.clone(),
+            });
+        }
+    }
+
+    for content in &layer.contents {
+        if !content.dest().starts_with(SPFS_ROOT) {
+            issues.push(ValidationIssue::ContentOutsideSpfs {
+                layer: layer.file_path.clone(),
+                dest: content.dest().to_owned(),
+            });
+        }
+    }
+
+    issues
+}
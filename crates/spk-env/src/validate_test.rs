@@ -0,0 +1,205 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{ValidationIssue, validate};
+use crate::ComposedEnvironment;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_a_well_formed_spec_has_no_issues(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: PATH\n    value: /spfs/bin\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(validate(&env).is_empty());
+}
+
+#[rstest]
+fn test_unknown_top_level_key_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "pakages: [foo]\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::UnknownKey {
+            layer: root.join(".spenv.yaml"),
+            key: "pakages".to_string(),
+        }]
+    );
+    assert!(issues[0].is_warning());
+}
+
+#[rstest]
+fn test_empty_set_name_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: ''\n    value: x\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::EmptySetName {
+            layer: root.join(".spenv.yaml"),
+        }]
+    );
+    assert!(!issues[0].is_warning());
+}
+
+#[rstest]
+fn test_duplicate_set_in_the_same_layer_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "ops:\n  - op: set\n    var: EDITOR\n    value: vi\n  - op: set\n    var: EDITOR\n    value: nvim\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::DuplicateSet {
+            layer: root.join(".spenv.yaml"),
+            var: "EDITOR".to_string(),
+        }]
+    );
+    assert!(issues[0].is_warning());
+}
+
+#[rstest]
+fn test_conflicting_set_across_layers_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join("base.spenv.yaml"),
+        "ops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /base\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [base.spenv.yaml]\nops:\n  - op: set\n    var: PROJECT_ROOT\n    value: /override\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::ConflictingSet {
+            var: "PROJECT_ROOT".to_string(),
+            values: vec!["/base".to_string(), "/override".to_string()],
+        }]
+    );
+    assert!(issues[0].is_warning());
+}
+
+#[rstest]
+fn test_bind_destination_outside_spfs_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "binds:\n  - source: /host/data\n    dest: /data\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::BindOutsideSpfs {
+            layer: root.join(".spenv.yaml"),
+            dest: std::path::PathBuf::from("/data"),
+        }]
+    );
+    assert!(!issues[0].is_warning());
+}
+
+#[rstest]
+fn test_relative_bind_destination_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "binds:\n  - source: /host/data\n    dest: data\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::BindOutsideSpfs {
+            layer: root.join(".spenv.yaml"),
+            dest: std::path::PathBuf::from("data"),
+        }]
+    );
+    assert!(!issues[0].is_warning());
+}
+
+#[rstest]
+fn test_tmpfs_content_destination_outside_spfs_is_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "contents:\n  - tmpfs: /scratch\n").unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    let issues = validate(&env);
+
+    assert_eq!(
+        issues,
+        vec![ValidationIssue::ContentOutsideSpfs {
+            layer: root.join(".spenv.yaml"),
+            dest: std::path::PathBuf::from("/scratch"),
+        }]
+    );
+    assert!(!issues[0].is_warning());
+}
+
+#[rstest]
+fn test_tmpfs_content_destination_under_spfs_has_no_issues(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "contents:\n  - tmpfs: /spfs/scratch\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(validate(&env).is_empty());
+}
+
+#[rstest]
+fn test_bind_destination_under_spfs_has_no_issues(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "binds:\n  - source: /host/data\n    dest: /spfs/data\n",
+    )
+    .unwrap();
+
+    let env = ComposedEnvironment::resolve(root).unwrap();
+    assert!(validate(&env).is_empty());
+}
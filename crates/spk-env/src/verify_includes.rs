@@ -0,0 +1,123 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Checking that every include declared by a spec resolves to a real,
+//! loadable file, without composing layers or applying interpolation,
+//! for `spenv verify-includes`.
+
+use std::path::{Path, PathBuf};
+
+use crate::compose::resolve_include;
+use crate::discovery::{DiscoveryOptions, discover_in_tree};
+use crate::error::LoadSpecError;
+use crate::spec::EnvSpec;
+
+#[cfg(test)]
+#[path = "verify_includes_test.rs"]
+mod verify_includes_test;
+
+/// The outcome of checking a single `includes` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncludeStatus {
+    /// The include resolved to a file that could be loaded
+    Reachable,
+    /// The include path doesn't exist, couldn't be parsed, or (for a
+    /// glob entry) matched nothing
+    Missing,
+    /// The include leads back to a spec already being resolved along
+    /// the same chain
+    Circular,
+}
+
+/// A single `includes` entry found while transitively checking a
+/// spec's include graph, reported by [`verify_includes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeCheck {
+    /// The spec that declared the include
+    pub from: PathBuf,
+    /// The include, resolved against `from`'s directory; an entry
+    /// that couldn't be resolved at all (e.g. a glob that matched
+    /// nothing) is reported as written in `from`
+    pub include: PathBuf,
+    /// Whether the include could be loaded
+    pub status: IncludeStatus,
+}
+
+/// Check every include reachable from `start`, transitively, for
+/// every discovered spec.
+///
+/// This walks the same include graph that
+/// [`crate::compose::ComposedEnvironment::resolve`] would, including
+/// glob expansion, but never loads layer operations into an actual
+/// composition: it's meant as a fast, offline pre-flight that a spec
+/// author can run before committing, not a substitute for resolving
+/// the environment itself. A missing or circular include is reported
+/// in the result rather than stopping the walk, so a single pass
+/// surfaces every problem in the graph at once.
+pub fn verify_includes<P: AsRef<Path>>(
+    start: P,
+    options: DiscoveryOptions,
+) -> Result<Vec<IncludeCheck>, LoadSpecError> {
+    let mut checks = Vec::new();
+    for spec in discover_in_tree(start, options)? {
+        let mut stack = vec![canonical(&spec.file_path)];
+        check_includes(&spec, &mut stack, &mut checks);
+    }
+    Ok(checks)
+}
+
+fn check_includes(spec: &EnvSpec, stack: &mut Vec<PathBuf>, checks: &mut Vec<IncludeCheck>) {
+    let base_dir = spec.file_path.parent().unwrap_or_else(|| Path::new(""));
+    for include in &spec.includes {
+        if !include.matches_host() {
+            continue;
+        }
+
+        let resolved = match resolve_include(&spec.file_path, base_dir, include.path()) {
+            Ok(paths) => paths,
+            Err(_) => {
+                checks.push(IncludeCheck {
+                    from: spec.file_path.clone(),
+                    include: include.path().to_owned(),
+                    status: IncludeStatus::Missing,
+                });
+                continue;
+            }
+        };
+
+        for path in resolved {
+            let canonical_path = canonical(&path);
+            if stack.contains(&canonical_path) {
+                checks.push(IncludeCheck {
+                    from: spec.file_path.clone(),
+                    include: path,
+                    status: IncludeStatus::Circular,
+                });
+                continue;
+            }
+
+            match EnvSpec::load_file(&path) {
+                Ok(included) => {
+                    checks.push(IncludeCheck {
+                        from: spec.file_path.clone(),
+                        include: path,
+                        status: IncludeStatus::Reachable,
+                    });
+                    stack.push(canonical_path);
+                    check_includes(&included, stack, checks);
+                    stack.pop();
+                }
+                Err(_) => checks.push(IncludeCheck {
+                    from: spec.file_path.clone(),
+                    include: path,
+                    status: IncludeStatus::Missing,
+                }),
+            }
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
@@ -0,0 +1,52 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::{fixture, rstest};
+
+use super::{IncludeStatus, verify_includes};
+use crate::discovery::DiscoveryOptions;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spenv-test-")
+        .tempdir()
+        .expect("create a temp directory for test files")
+}
+
+#[rstest]
+fn test_a_reachable_and_a_missing_include_are_both_reported(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join("present.spenv.yaml"), "packages: [present-pkg]\n").unwrap();
+    std::fs::write(
+        root.join(".spenv.yaml"),
+        "includes: [present.spenv.yaml, missing.spenv.yaml]\n",
+    )
+    .unwrap();
+
+    let checks = verify_includes(root, DiscoveryOptions::default()).unwrap();
+
+    let present = checks
+        .iter()
+        .find(|c| c.include.ends_with("present.spenv.yaml"))
+        .unwrap();
+    assert_eq!(present.status, IncludeStatus::Reachable);
+
+    let missing = checks
+        .iter()
+        .find(|c| c.include.ends_with("missing.spenv.yaml"))
+        .unwrap();
+    assert_eq!(missing.status, IncludeStatus::Missing);
+}
+
+#[rstest]
+fn test_a_self_including_spec_is_reported_as_circular(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path();
+    std::fs::write(root.join(".spenv.yaml"), "includes: [.spenv.yaml]\n").unwrap();
+
+    let checks = verify_includes(root, DiscoveryOptions::default()).unwrap();
+
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].status, IncludeStatus::Circular);
+}
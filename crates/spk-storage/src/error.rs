@@ -67,6 +67,11 @@ pub enum Error {
     DiskUsageVersionNotFound(String),
     #[error("No disk usage: build '{0}' not found")]
     DiskUsageBuildNotFound(String),
+    #[error("timed out opening remote repository {name:?} after {elapsed:?}")]
+    RemoteRepositoryTimeout {
+        name: String,
+        elapsed: std::time::Duration,
+    },
     #[error("{0}")]
     String(String),
 }
@@ -7,6 +7,7 @@ use std::convert::{TryFrom, TryInto};
 use std::path::Path;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use arc_swap::ArcSwap;
 use dashmap::DashMap;
@@ -1179,7 +1180,7 @@ pub async fn local_repository() -> Result<SpfsRepository> {
 /// If not name is specified, return the default spfs repository.
 pub async fn remote_repository<S: AsRef<str>>(name: S) -> Result<SpfsRepository> {
     let config = spfs::get_config()?;
-    let inner = config.get_remote(&name).await?;
+    let inner = open_remote_with_retry(&config, name.as_ref()).await?;
     let address = inner.address().into_owned();
     Ok(SpfsRepository {
         caches: CachesForAddress::new(&address),
@@ -1190,6 +1191,68 @@ pub async fn remote_repository<S: AsRef<str>>(name: S) -> Result<SpfsRepository>
     })
 }
 
+/// The default timeout for a single remote repository open attempt,
+/// used unless overridden by `SPK_REPO_OPEN_TIMEOUT`.
+///
+/// This lives alongside [`remote_repository`] rather than in `spk-env`
+/// because opening a named remote is implemented here, not there;
+/// `spk-env` has no repository resolution of its own to time out. The
+/// env var is prefixed `SPK_`, matching this crate, rather than
+/// `SPENV_`, which is reserved for the `spenv` binary's own settings.
+const DEFAULT_REMOTE_OPEN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The number of retries after a timed-out open attempt, before
+/// giving up and returning [`Error::RemoteRepositoryTimeout`].
+const REMOTE_OPEN_RETRIES: u32 = 2;
+
+/// The timeout for a single remote repository open attempt, read from
+/// `SPK_REPO_OPEN_TIMEOUT` (whole seconds) or [`DEFAULT_REMOTE_OPEN_TIMEOUT`]
+/// if that's unset or not a valid number.
+fn remote_open_timeout() -> Duration {
+    std::env::var("SPK_REPO_OPEN_TIMEOUT")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_REMOTE_OPEN_TIMEOUT)
+}
+
+/// Open `name` from `config`, retrying with backoff if an attempt
+/// times out, so a flaky link to a remote can't hang repository
+/// resolution indefinitely. An `origin` that simply doesn't exist
+/// ([`spfs::Error::UnknownRemoteName`]) is a normal, non-retryable
+/// outcome and is returned immediately, unchanged.
+///
+/// NOTE: this crate depends on `spfs-proto`, whose build script
+/// requires the `flatc` binary, so environments without it can't
+/// compile or run `spfs_test.rs`'s tests of [`remote_open_timeout`].
+/// Give it a real `cargo build`/`test`/`clippy` pass wherever `flatc`
+/// is available before relying on a diff review alone.
+async fn open_remote_with_retry(
+    config: &spfs::Config,
+    name: &str,
+) -> Result<spfs::storage::RepositoryHandle> {
+    let timeout = remote_open_timeout();
+    let mut attempt = 0;
+    loop {
+        match tokio::time::timeout(timeout, config.get_remote(name)).await {
+            Ok(result) => return Ok(result?),
+            Err(_elapsed) if attempt < REMOTE_OPEN_RETRIES => {
+                attempt += 1;
+                tracing::warn!(
+                    "timed out opening remote repository {name:?} after {timeout:?}, retrying ({attempt}/{REMOTE_OPEN_RETRIES})"
+                );
+                tokio::time::sleep(Duration::from_millis(250 * 2u64.pow(attempt))).await;
+            }
+            Err(_elapsed) => {
+                return Err(Error::RemoteRepositoryTimeout {
+                    name: name.to_string(),
+                    elapsed: timeout,
+                });
+            }
+        }
+    }
+}
+
 // Helper to inject a given filesystem path into the current spfs
 // config as a proxy wrapper repo around the existing origin repo.
 //
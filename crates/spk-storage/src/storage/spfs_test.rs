@@ -27,6 +27,54 @@ fn test_repo_version_is_valid() {
         .expect("repo current version must be a valid spk version string");
 }
 
+// Environment manipulation is not thread safe, so run these test cases serially.
+#[rstest]
+#[serial_test::serial(env)]
+fn test_remote_open_timeout_defaults_when_unset() {
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::remove_var("SPK_REPO_OPEN_TIMEOUT");
+    }
+    assert_eq!(
+        super::remote_open_timeout(),
+        super::DEFAULT_REMOTE_OPEN_TIMEOUT
+    );
+}
+
+#[rstest]
+#[serial_test::serial(env)]
+fn test_remote_open_timeout_reads_seconds_from_the_env_var() {
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::set_var("SPK_REPO_OPEN_TIMEOUT", "30");
+    }
+    assert_eq!(
+        super::remote_open_timeout(),
+        std::time::Duration::from_secs(30)
+    );
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::remove_var("SPK_REPO_OPEN_TIMEOUT");
+    }
+}
+
+#[rstest]
+#[serial_test::serial(env)]
+fn test_remote_open_timeout_falls_back_on_a_non_numeric_value() {
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::set_var("SPK_REPO_OPEN_TIMEOUT", "not-a-number");
+    }
+    assert_eq!(
+        super::remote_open_timeout(),
+        super::DEFAULT_REMOTE_OPEN_TIMEOUT
+    );
+    // SAFETY: test-only, single-threaded access to this process's environment.
+    unsafe {
+        std::env::remove_var("SPK_REPO_OPEN_TIMEOUT");
+    }
+}
+
 #[rstest]
 #[tokio::test]
 async fn test_metadata_io(tmpdir: tempfile::TempDir) {
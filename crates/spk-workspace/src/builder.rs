@@ -14,6 +14,7 @@ use crate::error;
 pub struct WorkspaceBuilder {
     root: Option<std::path::PathBuf>,
     spec_files: HashMap<std::path::PathBuf, crate::file::TemplateConfig>,
+    repositories: Vec<String>,
 }
 
 impl WorkspaceBuilder {
@@ -35,9 +36,10 @@ impl WorkspaceBuilder {
 
     /// Load all data from a workspace specification.
     pub fn load_from_file(
-        self,
+        mut self,
         file: crate::file::WorkspaceFile,
     ) -> Result<Self, error::FromFileError> {
+        self.repositories = file.repositories.clone();
         file.recipes
             .iter()
             .try_fold(self, |builder, item| builder.with_recipes_item(item))
@@ -96,6 +98,7 @@ impl WorkspaceBuilder {
     /// Build the workspace as configured.
     pub fn build(self) -> Result<super::Workspace, error::BuildError> {
         let mut workspace = super::Workspace::default();
+        workspace.repositories = self.repositories;
         for (file, config) in self.spec_files {
             match workspace.load_template_file_with_config(&file, config) {
                 Ok(_) => {}
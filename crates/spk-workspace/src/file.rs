@@ -29,6 +29,12 @@ pub struct WorkspaceFile {
     /// The package recipes that are part of this workspace
     #[serde(default)]
     pub recipes: Vec<RecipesItem>,
+
+    /// Repositories this workspace needs, enabled in addition to
+    /// whatever a command's own `--enable-repo`/`--disable-repo` flags
+    /// select
+    #[serde(default)]
+    pub repositories: Vec<String>,
 }
 
 impl WorkspaceFile {
@@ -38,6 +38,25 @@ fn test_workspace_from_yaml(#[case] yaml: &str) {
     let _deserialized: WorkspaceFile = serde_yaml::from_str(yaml).unwrap();
 }
 
+#[rstest]
+fn test_repositories_defaults_to_empty() {
+    let deserialized: WorkspaceFile = serde_yaml::from_str(EMPTY_WORKSPACE).unwrap();
+    assert!(deserialized.repositories.is_empty());
+}
+
+#[rstest]
+fn test_repositories_is_parsed_from_the_spec() {
+    let deserialized: WorkspaceFile = serde_yaml::from_str(
+        r#"
+api: v0/workspace
+recipes: []
+repositories: [staging]
+"#,
+    )
+    .unwrap();
+    assert_eq!(deserialized.repositories, vec!["staging".to_string()]);
+}
+
 #[rstest]
 fn test_empty_workspace_loading(tmpdir: tempfile::TempDir) {
     let root = tmpdir.path();
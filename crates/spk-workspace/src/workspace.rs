@@ -30,6 +30,9 @@ pub struct Workspace {
     /// A workspace may contain multiple recipes for a single
     /// package.
     pub(crate) templates: HashMap<PkgNameBuf, Vec<ConfiguredTemplate>>,
+
+    /// Repositories declared by the workspace's `repositories:` list
+    pub(crate) repositories: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -51,6 +54,12 @@ impl Workspace {
             .flat_map(|(name, templates)| templates.iter().map(|t| (name.as_ref(), t)))
     }
 
+    /// The repositories this workspace declares it needs, from its
+    /// `repositories:` list
+    pub fn repositories(&self) -> &[String] {
+        &self.repositories
+    }
+
     /// Returns the default package template file for the current workspace.
     ///
     /// The default template in a workspace is a lone template file, and
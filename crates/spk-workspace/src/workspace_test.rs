@@ -54,6 +54,7 @@ fn test_config_specialization(tmpdir: tempfile::TempDir) {
                     },
                 },
             ],
+            repositories: Vec::new(),
         })
         .unwrap()
         .build()
@@ -67,6 +68,21 @@ fn test_config_specialization(tmpdir: tempfile::TempDir) {
     )
 }
 
+#[rstest]
+fn test_repositories_declared_in_the_workspace_file_are_exposed(tmpdir: tempfile::TempDir) {
+    let workspace = Workspace::builder()
+        .with_root(tmpdir.path())
+        .load_from_file(crate::file::WorkspaceFile {
+            recipes: Vec::new(),
+            repositories: vec!["staging".to_string()],
+        })
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(workspace.repositories(), &["staging".to_string()]);
+}
+
 #[rstest]
 #[case::default_request_with_one_spec(
     &[("my-package.spk.yaml", "my-package/1.0.0")],
@@ -176,6 +192,7 @@ fn test_workspace_find_by_version(tmpdir: tempfile::TempDir) {
                     },
                 },
             ],
+            repositories: Vec::new(),
         })
         .unwrap()
         .build()